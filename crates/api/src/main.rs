@@ -6,15 +6,17 @@ use std::{env, net::SocketAddr, sync::Arc};
 use axum::{
     Extension, Json, Router,
     body::Body,
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::{
         HeaderValue, Method, Request, StatusCode,
-        header::{AUTHORIZATION, CONTENT_TYPE, LOCATION, SET_COOKIE},
+        header::{AUTHORIZATION, CONTENT_TYPE, ETAG, LOCATION, SET_COOKIE},
     },
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, patch, post},
 };
+use domain::files::{DIRECT_UPLOAD_MAX_SIZE, FileQuota, FileUrlConfig};
+use domain::tickets::SlaTargets;
 use dotenvy::dotenv;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
@@ -22,37 +24,62 @@ use tower_http::{catch_panic::CatchPanicLayer, cors::CorsLayer};
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
 use application::{
-    files::{FileUsecase, FileUsecaseImpl},
+    alerts::{AlertUsecase, AlertUsecaseImpl},
+    files::{FileUsecase, FileUsecaseImpl, start_upload_reaper, start_visibility_backfill},
     items::{ItemUsecase, ItemUsecaseImpl},
     recipes::{RecipeUsecase, RecipeUsecaseImpl},
     status::{StatusUsecase, StatusUsecaseImpl},
-    tickets::{TicketUsecase, TicketUsecaseImpl},
+    tickets::{TicketUsecase, TicketUsecaseImpl, start_ticket_autoclose, ticket_notifier_from_env},
 };
 use infrastructure::{
+    alert_dispatcher::{start_alert_dispatcher, webhook_sender},
     postgres::pools::connect_pg,
     repositorys::{
-        file::PostgresFileRepository, item::PostgresItemRepository,
+        alert::PostgresAlertRepository, file::PostgresFileRepository,
+        incident::PostgresIncidentRepository, item::PostgresItemRepository,
         recipe::PostgresRecipeRepository, status::PostgresStatusRepository,
         ticket::PostgresTicketRepository,
     },
-    status_watcher::start_status_watcher,
+    status_rollup::start_status_rollup,
+    status_watcher::{load_server_config, start_status_watcher},
+    storage::{LocalObjectStore, ObjectStore, S3ObjectStore, UnconfiguredObjectStore},
+};
+use routes::alerts::{
+    create_alert_rule, delete_alert_rule, find_alert_rule, list_alert_deliveries, list_alert_rules,
+    patch_alert_rule, test_alert_rule,
 };
 use routes::audit_logs::list_audit_logs;
 use routes::auth::{OAuthStateStore, discord_exchange, discord_login};
-use routes::items::{create_item, delete_item, find_all_items, find_item_by_id, patch_item};
+use routes::ids::decode_generated_id;
+use routes::items::{
+    create_item, delete_item, find_all_items, find_item_by_id, list_item_key_aliases, patch_item,
+    rename_item,
+};
 use routes::recipes::{
     create_recipe, delete_recipe, find_all_recipes, find_recipes_by_id, patch_recipe,
 };
-use routes::status::{get_status, list_status};
+use routes::status::{
+    get_network_history, get_network_status, get_status, get_status_history, get_status_uptime,
+    list_status, list_status_incidents, patch_status_incident,
+};
 use routes::tickets::{create_ticket, find_ticket_by_id, list_tickets};
 use routes::{
     files::{
-        abort_upload, complete_upload, create_upload, delete_file, get_file_by_id, get_part_url,
-        get_upload, list_files, register_part,
+        abort_upload, add_file_reference, complete_upload, create_upload, delete_file,
+        get_file_by_id, get_part_url, get_storage_usage, get_upload, get_user_storage_usage,
+        list_file_folders, list_file_usages, list_files, list_upload_reaper_runs, register_part,
+        remove_file_reference, update_file, upload_file,
+    },
+    tickets::{
+        create_canned_response, create_discord_ticket_message, create_ticket_message,
+        create_ticket_transition, delete_canned_response, delete_ticket, delete_ticket_message,
+        delete_ticket_staff, edit_ticket_message, find_canned_response, get_ticket_attachment,
+        list_canned_responses, list_ticket_staff, list_ticket_transitions, my_ticket_queue,
+        patch_canned_response, patch_ticket, ticket_message_history, ticket_sla_report,
+        upsert_ticket_staff,
     },
-    tickets::{delete_ticket, patch_ticket},
 };
-use shared::error::not_found_handler;
+use shared::{IdGenerator, error::not_found_handler};
 
 #[derive(Clone)]
 pub struct AuthState {
@@ -124,25 +151,177 @@ async fn main() {
         tracing::warn!("API_SECRET_KEY is not set; all requests will be rejected");
     }
 
+    let ids = Arc::new(IdGenerator::new());
+
+    let storage_backend = env::var("STORAGE_BACKEND").unwrap_or_else(|_| "r2".to_string());
+    let mut url_defaults = FileUrlConfig::default();
+    let local_store = match storage_backend.as_str() {
+        "local" => {
+            let root = env::var("STORAGE_LOCAL_ROOT").unwrap_or_else(|_| "./storage".to_string());
+            let public_url = env::var("STORAGE_LOCAL_PUBLIC_URL")
+                .unwrap_or_else(|_| format!("http://localhost:{}", port));
+            let secret = env::var("STORAGE_LOCAL_SECRET")
+                .map(String::into_bytes)
+                .unwrap_or_else(|_| {
+                    tracing::warn!(
+                        "STORAGE_LOCAL_SECRET is not set; upload URLs will not survive a restart"
+                    );
+                    uuid::Uuid::new_v4().as_bytes().to_vec()
+                });
+            url_defaults.cdn_base_url =
+                format!("{}/v1/storage/objects", public_url.trim_end_matches('/'));
+            tracing::info!("Storing files on local disk under {}", root);
+            Some(Arc::new(LocalObjectStore::new(
+                root.into(),
+                &public_url,
+                secret,
+            )))
+        }
+        "r2" | "s3" => None,
+        other => panic!("Unknown STORAGE_BACKEND '{}'; use r2 or local", other),
+    };
+    let object_store: Arc<dyn ObjectStore> = match &local_store {
+        Some(store) => store.clone(),
+        // Without storage only the file endpoints fail; everything else runs.
+        None => match S3ObjectStore::from_env() {
+            Ok(store) => Arc::new(store),
+            Err(err) => {
+                tracing::error!(
+                    "File storage is unavailable, missing or invalid R2_* configuration ({}); set STORAGE_BACKEND=local to store files on disk",
+                    err
+                );
+                Arc::new(UnconfiguredObjectStore::new(format!(
+                    "R2_* configuration is missing or invalid ({})",
+                    err
+                )))
+            }
+        },
+    };
+
     let file_repo = PostgresFileRepository::new(pool.clone());
-    let file_usecase = Arc::new(FileUsecaseImpl::new(file_repo)) as Arc<dyn FileUsecase>;
+    let file_urls = FileUrlConfig {
+        cdn_base_url: env::var("CDN_BASE_URL")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or(url_defaults.cdn_base_url),
+        private_url_expires_secs: env::var("FILE_URL_EXPIRES_SECS")
+            .ok()
+            .map(|v| {
+                v.parse::<u64>()
+                    .expect("Invalid number of seconds in FILE_URL_EXPIRES_SECS")
+            })
+            .unwrap_or(url_defaults.private_url_expires_secs),
+    };
+    let quota_bytes = |name: &str| {
+        env::var(name)
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| {
+                v.trim()
+                    .parse::<i64>()
+                    .unwrap_or_else(|_| panic!("Invalid number of bytes in {}", name))
+            })
+            .filter(|&bytes| bytes > 0)
+    };
+    let file_quota = FileQuota {
+        per_user_bytes: quota_bytes("FILE_QUOTA_PER_USER_BYTES"),
+        total_bytes: quota_bytes("FILE_QUOTA_TOTAL_BYTES"),
+    };
+    let file_usecase = Arc::new(FileUsecaseImpl::new(
+        file_repo,
+        ids.clone(),
+        object_store,
+        file_urls,
+        file_quota,
+    )) as Arc<dyn FileUsecase>;
 
     let item_repo = PostgresItemRepository::new(pool.clone());
-    let item_usecase = Arc::new(ItemUsecaseImpl::new(item_repo)) as Arc<dyn ItemUsecase>;
+    let item_usecase =
+        Arc::new(ItemUsecaseImpl::new(item_repo, ids.clone())) as Arc<dyn ItemUsecase>;
 
     let recipe_repo = PostgresRecipeRepository::new(pool.clone());
-    let recipe_usecase = Arc::new(RecipeUsecaseImpl::new(recipe_repo)) as Arc<dyn RecipeUsecase>;
+    let recipe_usecase =
+        Arc::new(RecipeUsecaseImpl::new(recipe_repo, ids.clone())) as Arc<dyn RecipeUsecase>;
+
+    let server_config = load_server_config().expect("Failed to load config/status.toml");
 
     let status_repo = PostgresStatusRepository::new(pool.clone());
-    let status_usecase = Arc::new(StatusUsecaseImpl::new(status_repo)) as Arc<dyn StatusUsecase>;
+    let incident_repo = PostgresIncidentRepository::new(pool.clone());
+    let status_usecase = Arc::new(StatusUsecaseImpl::new(
+        status_repo,
+        incident_repo,
+        server_config.clone(),
+    )) as Arc<dyn StatusUsecase>;
+
+    let alert_repo = PostgresAlertRepository::new(pool.clone());
+    let alert_usecase = Arc::new(AlertUsecaseImpl::new(
+        alert_repo,
+        webhook_sender(&server_config.alerts),
+    )) as Arc<dyn AlertUsecase>;
 
     let ticket_repo: PostgresTicketRepository = PostgresTicketRepository::new(pool.clone());
-    let ticket_usecase = Arc::new(TicketUsecaseImpl::new(ticket_repo)) as Arc<dyn TicketUsecase>;
+    let sla_defaults = SlaTargets::default();
+    let sla_targets = SlaTargets {
+        first_response_secs: env::var("TICKET_SLA_FIRST_RESPONSE_MINS")
+            .ok()
+            .map(|v| {
+                v.parse::<i64>()
+                    .expect("Invalid number of minutes in TICKET_SLA_FIRST_RESPONSE_MINS")
+                    * 60
+            })
+            .unwrap_or(sla_defaults.first_response_secs),
+        resolution_secs: env::var("TICKET_SLA_RESOLUTION_HOURS")
+            .ok()
+            .map(|v| {
+                v.parse::<i64>()
+                    .expect("Invalid number of hours in TICKET_SLA_RESOLUTION_HOURS")
+                    * 60
+                    * 60
+            })
+            .unwrap_or(sla_defaults.resolution_secs),
+    };
+    let ticket_usecase = Arc::new(TicketUsecaseImpl::new(
+        ticket_repo,
+        file_usecase.clone(),
+        sla_targets,
+        ticket_notifier_from_env(),
+        ids,
+    )) as Arc<dyn TicketUsecase>;
 
     let (tx, _rx) = broadcast::channel::<String>(100);
     let tx = std::sync::Arc::new(tx);
 
     start_status_watcher(pool.clone()).await.unwrap();
+    start_status_rollup(pool.clone(), server_config.clone())
+        .await
+        .unwrap();
+    start_alert_dispatcher(pool.clone(), server_config.clone())
+        .await
+        .unwrap();
+
+    let ticket_auto_close_days = env::var("TICKET_AUTO_CLOSE_DAYS")
+        .ok()
+        .map(|v| {
+            v.parse::<u32>()
+                .expect("Invalid number of days in TICKET_AUTO_CLOSE_DAYS")
+        })
+        .unwrap_or(7);
+    start_ticket_autoclose(ticket_usecase.clone(), ticket_auto_close_days)
+        .await
+        .unwrap();
+
+    let upload_ttl_hours = env::var("UPLOAD_TTL_HOURS")
+        .ok()
+        .map(|v| {
+            v.parse::<u64>()
+                .expect("Invalid number of hours in UPLOAD_TTL_HOURS")
+        })
+        .unwrap_or(24);
+    start_upload_reaper(file_usecase.clone(), upload_ttl_hours)
+        .await
+        .unwrap();
+    start_visibility_backfill(file_usecase.clone())
+        .await
+        .unwrap();
 
     let app = Router::new()
         .route("/v1/auth/discord/login", get(discord_login))
@@ -152,6 +331,8 @@ async fn main() {
             "/v1/items/{id}",
             get(find_item_by_id).patch(patch_item).delete(delete_item),
         )
+        .route("/v1/items/{id}/rename", post(rename_item))
+        .route("/v1/items/{id}/aliases", get(list_item_key_aliases))
         .layer(Extension(item_usecase))
         .route("/v1/recipes", get(find_all_recipes).post(create_recipe))
         .route(
@@ -161,9 +342,35 @@ async fn main() {
                 .delete(delete_recipe),
         )
         .layer(Extension(recipe_usecase))
-        .route("/v1/files", get(list_files))
-        .route("/v1/files/{id}", get(get_file_by_id).delete(delete_file))
+        .route(
+            "/v1/files",
+            get(list_files)
+                .post(upload_file)
+                .layer(DefaultBodyLimit::max(
+                    // Room for the form fields and part headers around the file.
+                    DIRECT_UPLOAD_MAX_SIZE as usize + 1024 * 1024,
+                )),
+        )
+        .route("/v1/files/folders", get(list_file_folders))
+        .route("/v1/files/usage", get(get_storage_usage))
+        .route("/v1/files/usage/{user_id}", get(get_user_storage_usage))
+        .route(
+            "/v1/files/{id}",
+            get(get_file_by_id).patch(update_file).delete(delete_file),
+        )
+        .route(
+            "/v1/files/{id}/usages",
+            get(list_file_usages).post(add_file_reference),
+        )
+        .route(
+            "/v1/files/{id}/usages/{kind}/{entity_id}",
+            delete(remove_file_reference),
+        )
         .route("/v1/files/uploads", post(create_upload))
+        .route(
+            "/v1/files/uploads/reaper/runs",
+            get(list_upload_reaper_runs),
+        )
         .route("/v1/files/uploads/{upload_id}", get(get_upload))
         .route(
             "/v1/files/uploads/{upload_id}/parts/{part_number}/url",
@@ -180,8 +387,33 @@ async fn main() {
         .route("/v1/files/uploads/{upload_id}/abort", post(abort_upload))
         .layer(Extension(file_usecase))
         .route("/v1/status", get(list_status))
+        .route("/v1/status/network", get(get_network_status))
+        .route("/v1/status/network/history", get(get_network_history))
         .route("/v1/status/{server_id}", get(get_status))
+        .route("/v1/status/{server_id}/history", get(get_status_history))
+        .route("/v1/status/{server_id}/uptime", get(get_status_uptime))
+        .route(
+            "/v1/status/{server_id}/incidents",
+            get(list_status_incidents),
+        )
+        .route(
+            "/v1/status/incidents/{incident_id}",
+            patch(patch_status_incident),
+        )
         .layer(Extension(status_usecase))
+        .route(
+            "/v1/alerts/rules",
+            get(list_alert_rules).post(create_alert_rule),
+        )
+        .route(
+            "/v1/alerts/rules/{id}",
+            get(find_alert_rule)
+                .patch(patch_alert_rule)
+                .delete(delete_alert_rule),
+        )
+        .route("/v1/alerts/rules/{id}/test", post(test_alert_rule))
+        .route("/v1/alerts/deliveries", get(list_alert_deliveries))
+        .layer(Extension(alert_usecase))
         .route("/v1/tickets", get(list_tickets).post(create_ticket))
         .route(
             "/v1/tickets/{id}",
@@ -189,13 +421,60 @@ async fn main() {
                 .patch(patch_ticket)
                 .delete(delete_ticket),
         )
+        .route("/v1/tickets/queue", get(my_ticket_queue))
+        .route("/v1/tickets/sla", get(ticket_sla_report))
+        .route(
+            "/v1/tickets/discord/messages",
+            post(create_discord_ticket_message),
+        )
+        .route(
+            "/v1/tickets/canned-responses",
+            get(list_canned_responses).post(create_canned_response),
+        )
+        .route(
+            "/v1/tickets/canned-responses/{canned_id}",
+            get(find_canned_response)
+                .patch(patch_canned_response)
+                .delete(delete_canned_response),
+        )
+        .route(
+            "/v1/tickets/staff",
+            get(list_ticket_staff).put(upsert_ticket_staff),
+        )
+        .route(
+            "/v1/tickets/staff/{discord_id}/{category}",
+            delete(delete_ticket_staff),
+        )
+        .route(
+            "/v1/tickets/{id}/transitions",
+            get(list_ticket_transitions).post(create_ticket_transition),
+        )
+        .route("/v1/tickets/{id}/messages", post(create_ticket_message))
+        .route(
+            "/v1/tickets/{id}/attachments/{file_id}",
+            get(get_ticket_attachment),
+        )
+        .route(
+            "/v1/tickets/{id}/messages/{message_id}",
+            patch(edit_ticket_message).delete(delete_ticket_message),
+        )
+        .route(
+            "/v1/tickets/{id}/messages/{message_id}/history",
+            get(ticket_message_history),
+        )
         .layer(Extension(ticket_usecase))
         .route("/v1/audit-logs", get(list_audit_logs))
+        .route("/v1/ids/{id}", get(decode_generated_id))
         .merge(routes::ws::ws_router(tx.clone()))
         .layer(Extension(tx.clone()))
         .layer(Extension(pool.clone()))
         .layer(Extension(oauth_state_store))
         .layer(middleware::from_fn_with_state(auth_state, auth_middleware))
+        .merge(
+            local_store
+                .map(routes::storage::local_storage_router)
+                .unwrap_or_default(),
+        )
         .layer(CatchPanicLayer::new())
         .layer(
             CorsLayer::new()
@@ -221,7 +500,7 @@ async fn main() {
                     "x-actor-discord-global-name".parse().unwrap(),
                     "x-actor-discord-avatar".parse().unwrap(),
                 ])
-                .expose_headers([LOCATION, SET_COOKIE])
+                .expose_headers([LOCATION, SET_COOKIE, ETAG])
                 .allow_credentials(true),
         )
         .fallback(not_found_handler);
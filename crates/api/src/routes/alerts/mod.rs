@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Extension, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use domain::{
+    alerts::{AlertRule, AlertRulePatch},
+    response::ApiResponse,
+};
+use sqlx::PgPool;
+
+use crate::audit::{actor_from_headers, insert_audit_log};
+use application::alerts::AlertUsecase;
+use shared::error::error_response;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DeliveryListQuery {
+    pub rule_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct TestAlertRequest {
+    pub server_id: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TestAlertResponse {
+    pub delivered: bool,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub attempts: i32,
+}
+
+pub async fn list_alert_rules(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+) -> impl IntoResponse {
+    match usecase.find_all().await {
+        Ok(rules) => Json(ApiResponse {
+            status: 200,
+            data: rules,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn find_alert_rule(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match usecase.find_by_id(id).await {
+        Ok(rule) => Json(ApiResponse {
+            status: 200,
+            data: rule,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::NOT_FOUND, "not_found"),
+    }
+}
+
+pub async fn create_alert_rule(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Json(rule): Json<AlertRule>,
+) -> impl IntoResponse {
+    match usecase.create(rule).await {
+        Ok(rule) => {
+            let actor = actor_from_headers(&headers);
+            insert_audit_log(
+                &pool,
+                "alert_rule",
+                &rule.id.to_string(),
+                "create",
+                None,
+                serde_json::to_value(&rule).ok(),
+                actor,
+            )
+            .await;
+
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse {
+                    status: 201,
+                    data: rule,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
+    }
+}
+
+pub async fn patch_alert_rule(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(patch): Json<AlertRulePatch>,
+) -> impl IntoResponse {
+    let before = match usecase.find_by_id(id).await {
+        Ok(rule) => rule,
+        Err(e) => return error_response(e, StatusCode::NOT_FOUND, "not_found"),
+    };
+
+    match usecase.patch(id, patch).await {
+        Ok(rule) => {
+            let actor = actor_from_headers(&headers);
+            insert_audit_log(
+                &pool,
+                "alert_rule",
+                &id.to_string(),
+                "update",
+                serde_json::to_value(&before).ok(),
+                serde_json::to_value(&rule).ok(),
+                actor,
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: rule,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
+    }
+}
+
+pub async fn delete_alert_rule(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let before = match usecase.find_by_id(id).await {
+        Ok(rule) => rule,
+        Err(e) => return error_response(e, StatusCode::NOT_FOUND, "not_found"),
+    };
+
+    match usecase.delete(id).await {
+        Ok(_) => {
+            let actor = actor_from_headers(&headers);
+            insert_audit_log(
+                &pool,
+                "alert_rule",
+                &id.to_string(),
+                "delete",
+                serde_json::to_value(&before).ok(),
+                None,
+                actor,
+            )
+            .await;
+
+            Json(serde_json::json!({
+                "status": 200,
+                "message": "Alert rule deleted"
+            }))
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_delete_error"),
+    }
+}
+
+pub async fn test_alert_rule(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Path(id): Path<i64>,
+    body: Option<Json<TestAlertRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+
+    match usecase.send_test(id, req.server_id).await {
+        Ok(outcome) => {
+            // A webhook that could not be reached is an upstream failure.
+            let status = if outcome.is_success() {
+                StatusCode::OK
+            } else {
+                StatusCode::BAD_GATEWAY
+            };
+            (
+                status,
+                Json(ApiResponse {
+                    status: status.as_u16(),
+                    data: TestAlertResponse {
+                        delivered: outcome.is_success(),
+                        status_code: outcome.status_code,
+                        error: outcome.error,
+                        attempts: outcome.attempts,
+                    },
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "test_failed"),
+    }
+}
+
+pub async fn list_alert_deliveries(
+    Extension(usecase): Extension<Arc<dyn AlertUsecase>>,
+    Query(query): Query<DeliveryListQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    match usecase.list_deliveries(query.rule_id, limit).await {
+        Ok(deliveries) => Json(ApiResponse {
+            status: 200,
+            data: deliveries,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
@@ -1,41 +1,152 @@
 use axum::{
     Json,
-    extract::{Extension, Path, Query},
+    extract::{Extension, Multipart, Path, Query},
     http::HeaderMap,
     http::StatusCode,
     response::IntoResponse,
 };
-use domain::response::ApiResponse;
+use domain::{
+    files::{FileFilter, FilePatch, FileUsageKind, FileVisibility, NewFile, UploadPurpose},
+    response::ApiResponse,
+};
+use futures::StreamExt;
+use shared::error::{ApiError, error_response};
 use sqlx::PgPool;
 use std::sync::Arc;
 
 use crate::audit::{actor_from_headers, insert_audit_log};
 use application::files::FileUsecase;
 
+pub async fn list_files(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Query(filter): Query<FileFilter>,
+) -> impl IntoResponse {
+    match usecase.find_files(filter).await {
+        Ok(page) => Json(ApiResponse {
+            status: 200,
+            data: page,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
-pub struct FileListQuery {
+pub struct FolderListQuery {
     pub user_id: Option<String>,
 }
 
-pub async fn list_files(
+pub async fn list_file_folders(
     Extension(usecase): Extension<Arc<dyn FileUsecase>>,
-    Query(FileListQuery { user_id }): Query<FileListQuery>,
+    Query(query): Query<FolderListQuery>,
 ) -> impl IntoResponse {
-    match usecase.find_all_files(user_id).await {
-        Ok(files) => Json(ApiResponse {
+    match usecase.list_folders(query.user_id.as_deref()).await {
+        Ok(folders) => Json(ApiResponse {
             status: 200,
-            data: files,
+            data: folders,
         })
         .into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "fetch_failed",
-                "message": e.to_string()
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
+    }
+}
+
+/// Items, tickets and resource packs using the file.
+pub async fn list_file_usages(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Path(file_id): Path<String>,
+) -> impl IntoResponse {
+    match usecase.list_usages(&file_id).await {
+        Ok(usages) => Json(ApiResponse {
+            status: 200,
+            data: usages,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct FileReferenceRequest {
+    /// `item` or `resource_pack`.
+    pub kind: String,
+    pub entity_id: String,
+}
+
+fn parse_usage_kind(raw: &str) -> Result<FileUsageKind, ApiError> {
+    FileUsageKind::parse(raw).ok_or_else(|| {
+        ApiError::bad_request("invalid_kind", "kind must be item, ticket or resource_pack")
+    })
+}
+
+pub async fn add_file_reference(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+    Json(req): Json<FileReferenceRequest>,
+) -> impl IntoResponse {
+    let kind = match parse_usage_kind(&req.kind) {
+        Ok(kind) => kind,
+        Err(e) => return e.into_response(),
+    };
+
+    match usecase.add_reference(&file_id, kind, &req.entity_id).await {
+        Ok(usages) => {
+            let actor = actor_from_headers(&headers);
+            let after_data = serde_json::to_value(&req).ok();
+            insert_audit_log(
+                &pool,
+                "file_reference",
+                &file_id,
+                "create",
+                None,
+                after_data,
+                actor,
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: usages,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "create_failed"),
+    }
+}
+
+pub async fn remove_file_reference(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path((file_id, kind, entity_id)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    let usage_kind = match parse_usage_kind(&kind) {
+        Ok(kind) => kind,
+        Err(e) => return e.into_response(),
+    };
+
+    match usecase
+        .remove_reference(&file_id, usage_kind, &entity_id)
+        .await
+    {
+        Ok(()) => {
+            let actor = actor_from_headers(&headers);
+            let before_data = serde_json::to_value(FileReferenceRequest { kind, entity_id }).ok();
+            insert_audit_log(
+                &pool,
+                "file_reference",
+                &file_id,
+                "delete",
+                before_data,
+                None,
+                actor,
+            )
+            .await;
+
+            Json(serde_json::json!({ "message": "Reference removed" })).into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "delete_failed"),
     }
 }
 
@@ -49,15 +160,7 @@ pub async fn get_file_by_id(
             data: meta,
         })
         .into_response(),
-        Err(e) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({
-                "status": 404,
-                "code": "not_found",
-                "message": e.to_string()
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
     }
 }
 
@@ -80,15 +183,65 @@ pub async fn delete_file(
 
             Json(serde_json::json!({ "message": "File deleted" })).into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "delete_failed",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "delete_failed"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct UpdateFileRequest {
+    /// `public` or `private`.
+    pub visibility: Option<String>,
+    /// `/`-separated virtual folder; empty moves the file to the root.
+    pub folder: Option<String>,
+    /// Replaces all tags.
+    pub tags: Option<Vec<String>>,
+}
+
+pub async fn update_file(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+    Json(req): Json<UpdateFileRequest>,
+) -> impl IntoResponse {
+    let visibility = match req.visibility.as_deref().map(FileVisibility::parse) {
+        None => None,
+        Some(Some(visibility)) => Some(visibility),
+        Some(None) => return invalid_visibility().into_response(),
+    };
+    let patch = FilePatch {
+        visibility,
+        folder: req.folder,
+        tags: req.tags,
+    };
+
+    let before_data = match usecase.get_file_by_id(&file_id).await {
+        Ok(meta) => serde_json::to_value(meta).ok(),
+        Err(e) => return error_response(e, StatusCode::NOT_FOUND, "not_found"),
+    };
+
+    match usecase.update_file(&file_id, patch).await {
+        Ok(meta) => {
+            let after_data = serde_json::to_value(&meta).ok();
+            let actor = actor_from_headers(&headers);
+            insert_audit_log(
+                &pool,
+                "file",
+                &file_id,
+                "update",
+                before_data,
+                after_data,
+                actor,
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: meta,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "update_failed"),
     }
 }
 
@@ -98,6 +251,10 @@ pub struct CreateUploadRequest {
     pub filename: String,
     pub content_type: String,
     pub size: i64,
+    /// One of `general`, `item_texture`, `avatar` or `ticket_attachment`.
+    pub purpose: Option<String>,
+    /// `public` or `private`; defaults by purpose.
+    pub visibility: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -110,16 +267,115 @@ pub struct UploadInitResponse {
     pub content_type: String,
     pub size: i64,
     pub part_size: i64,
-    pub url: String,
+    pub purpose: UploadPurpose,
+    pub visibility: FileVisibility,
+    /// CDN link the file will have once completed; private files have none.
+    pub url: Option<String>,
 }
 
-fn cdn_url(user_id: &str, file_id: &str, filename: &str) -> String {
-    format!(
-        "https://cdn.alcaris.net/files/{}/{}/{}",
-        user_id, file_id, filename
+fn invalid_purpose() -> ApiError {
+    ApiError::bad_request(
+        "invalid_purpose",
+        "purpose must be general, item_texture, avatar or ticket_attachment",
     )
 }
 
+fn invalid_visibility() -> ApiError {
+    ApiError::bad_request("invalid_visibility", "visibility must be public or private")
+}
+
+fn public_url(usecase: &dyn FileUsecase, visibility: FileVisibility, key: &str) -> Option<String> {
+    (visibility == FileVisibility::Public).then(|| usecase.cdn_url(key))
+}
+
+/// Uploads a small file in one `multipart/form-data` request. The form fields
+/// `user_id`, `purpose`, `visibility`, `folder` and `tags` must come before
+/// the `file` part, whose body is streamed to storage as it arrives.
+pub async fn upload_file(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut user_id = String::new();
+    let mut purpose = UploadPurpose::default();
+    let mut visibility = None;
+    let mut folder = None;
+    let mut tags = Vec::new();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return ApiError::bad_request("invalid_form", e.body_text()).into_response(),
+        };
+
+        if field.name() == Some("file") {
+            if user_id.trim().is_empty() {
+                return ApiError::bad_request("bad_request", "user_id is required").into_response();
+            }
+
+            let actor = actor_from_headers(&headers);
+            let file = NewFile {
+                user_id,
+                filename: field.file_name().unwrap_or("file").to_string(),
+                content_type: field
+                    .content_type()
+                    .unwrap_or("application/octet-stream")
+                    .to_string(),
+                purpose,
+                visibility,
+                folder,
+                tags,
+                uploader_username: (actor.username != "unknown").then(|| actor.username.clone()),
+                uploader_global_name: actor.global_name.clone(),
+                uploader_avatar_url: actor.avatar_url.clone(),
+            };
+            let body = field
+                .map(|chunk| chunk.map_err(std::io::Error::other))
+                .boxed();
+
+            return match usecase.upload_file(file, body).await {
+                Ok(meta) => {
+                    let after_data = serde_json::to_value(&meta).ok();
+                    insert_audit_log(&pool, "file", &meta.id, "create", None, after_data, actor)
+                        .await;
+
+                    Json(ApiResponse {
+                        status: 200,
+                        data: meta,
+                    })
+                    .into_response()
+                }
+                Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "upload_failed"),
+            };
+        }
+
+        let name = field.name().unwrap_or_default().to_string();
+        let value = match field.text().await {
+            Ok(value) => value,
+            Err(e) => return ApiError::bad_request("invalid_form", e.body_text()).into_response(),
+        };
+        match name.as_str() {
+            "user_id" => user_id = value,
+            "purpose" => match UploadPurpose::parse(&value) {
+                Some(parsed) => purpose = parsed,
+                None => return invalid_purpose().into_response(),
+            },
+            "visibility" => match FileVisibility::parse(&value) {
+                Some(parsed) => visibility = Some(parsed),
+                None => return invalid_visibility().into_response(),
+            },
+            "folder" => folder = Some(value),
+            // Either repeated or comma-separated.
+            "tags" => tags.extend(value.split(',').map(str::to_string)),
+            _ => {}
+        }
+    }
+
+    ApiError::bad_request("bad_request", "file is required").into_response()
+}
+
 pub async fn create_upload(
     Extension(usecase): Extension<Arc<dyn FileUsecase>>,
     Json(req): Json<CreateUploadRequest>,
@@ -136,12 +392,31 @@ pub async fn create_upload(
             .into_response();
     }
 
+    let purpose = match req.purpose.as_deref().map(UploadPurpose::parse) {
+        None => UploadPurpose::default(),
+        Some(Some(purpose)) => purpose,
+        Some(None) => return invalid_purpose().into_response(),
+    };
+    let visibility = match req.visibility.as_deref().map(FileVisibility::parse) {
+        None => None,
+        Some(Some(visibility)) => Some(visibility),
+        Some(None) => return invalid_visibility().into_response(),
+    };
+
     match usecase
-        .create_upload(&req.user_id, &req.filename, &req.content_type, req.size)
+        .create_upload(
+            &req.user_id,
+            &req.filename,
+            &req.content_type,
+            req.size,
+            purpose,
+            visibility,
+        )
         .await
     {
         Ok(upload) => {
             let res = UploadInitResponse {
+                url: public_url(usecase.as_ref(), upload.visibility, &upload.key),
                 upload_id: upload.upload_id,
                 file_id: upload.file_id.clone(),
                 user_id: upload.user_id.clone(),
@@ -150,7 +425,8 @@ pub async fn create_upload(
                 content_type: upload.content_type,
                 size: upload.size,
                 part_size: upload.part_size,
-                url: cdn_url(&upload.user_id, &upload.file_id, &upload.filename),
+                purpose: upload.purpose,
+                visibility: upload.visibility,
             };
             Json(ApiResponse {
                 status: 200,
@@ -158,15 +434,7 @@ pub async fn create_upload(
             })
             .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "create_failed",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "create_failed"),
     }
 }
 
@@ -180,7 +448,10 @@ pub struct UploadStatusResponse {
     pub content_type: String,
     pub size: i64,
     pub part_size: i64,
-    pub url: String,
+    pub status: String,
+    pub quarantine_reason: Option<String>,
+    pub visibility: FileVisibility,
+    pub url: Option<String>,
     pub parts: Vec<domain::files::FileUploadPart>,
 }
 
@@ -191,6 +462,7 @@ pub async fn get_upload(
     match usecase.get_upload(&upload_id).await {
         Ok((upload, parts)) => {
             let res = UploadStatusResponse {
+                url: public_url(usecase.as_ref(), upload.visibility, &upload.key),
                 upload_id: upload.upload_id,
                 file_id: upload.file_id.clone(),
                 user_id: upload.user_id.clone(),
@@ -199,7 +471,9 @@ pub async fn get_upload(
                 content_type: upload.content_type,
                 size: upload.size,
                 part_size: upload.part_size,
-                url: cdn_url(&upload.user_id, &upload.file_id, &upload.filename),
+                status: upload.status,
+                quarantine_reason: upload.quarantine_reason,
+                visibility: upload.visibility,
                 parts,
             };
             Json(ApiResponse {
@@ -274,12 +548,20 @@ pub async fn register_part(
     }
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CompleteUploadRequest {
+    /// Hex SHA-256 of the whole file, checked against the stored object.
+    pub sha256: Option<String>,
+}
+
 pub async fn complete_upload(
     Extension(usecase): Extension<Arc<dyn FileUsecase>>,
     Extension(pool): Extension<PgPool>,
     headers: HeaderMap,
     Path(upload_id): Path<String>,
+    body: Option<Json<CompleteUploadRequest>>,
 ) -> impl IntoResponse {
+    let Json(req) = body.unwrap_or_default();
     let actor = actor_from_headers(&headers);
 
     let uploader_username = actor.username.clone();
@@ -298,6 +580,7 @@ pub async fn complete_upload(
             uploader_username_opt,
             uploader_global_name,
             uploader_avatar_url,
+            req.sha256,
         )
         .await
     {
@@ -312,15 +595,7 @@ pub async fn complete_upload(
             })
             .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "complete_failed",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "complete_failed"),
     }
 }
 
@@ -341,3 +616,60 @@ pub async fn abort_upload(
             .into_response(),
     }
 }
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ReaperRunListQuery {
+    pub limit: Option<i64>,
+}
+
+pub async fn list_upload_reaper_runs(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Query(query): Query<ReaperRunListQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+
+    match usecase.list_reaper_runs(limit).await {
+        Ok(runs) => Json(ApiResponse {
+            status: 200,
+            data: runs,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StorageUsageQuery {
+    /// How many of the users using the most storage to list.
+    pub top_users: Option<i64>,
+}
+
+pub async fn get_storage_usage(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Query(query): Query<StorageUsageQuery>,
+) -> impl IntoResponse {
+    let top_users = query.top_users.unwrap_or(20).clamp(0, 100);
+
+    match usecase.storage_usage(top_users).await {
+        Ok(report) => Json(ApiResponse {
+            status: 200,
+            data: report,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
+    }
+}
+
+pub async fn get_user_storage_usage(
+    Extension(usecase): Extension<Arc<dyn FileUsecase>>,
+    Path(user_id): Path<String>,
+) -> impl IntoResponse {
+    match usecase.user_storage_usage(&user_id).await {
+        Ok(report) => Json(ApiResponse {
+            status: 200,
+            data: report,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
+    }
+}
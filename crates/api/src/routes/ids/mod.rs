@@ -0,0 +1,46 @@
+use axum::{Json, extract::Path, http::StatusCode, response::IntoResponse};
+use chrono::DateTime;
+use domain::response::ApiResponse;
+use serde::Serialize;
+use shared::decode_id;
+use shared::error::ApiError;
+
+#[derive(Debug, Serialize)]
+pub struct IdInfo {
+    pub id: String,
+    /// `null` for an entity type this server does not know.
+    pub entity_type: Option<&'static str>,
+    pub entity_type_code: u8,
+    pub timestamp_ms: u64,
+    pub created_at: Option<String>,
+    pub sequence: u8,
+}
+
+pub async fn decode_generated_id(Path(id): Path<String>) -> impl IntoResponse {
+    let Some(decoded) = decode_id(&id) else {
+        return ApiError::bad_request(
+            "invalid_id",
+            format!("'{}' is not a server-generated id", id),
+        )
+        .into_response();
+    };
+
+    let created_at =
+        DateTime::from_timestamp_millis(decoded.timestamp_ms as i64).map(|dt| dt.to_rfc3339());
+
+    (
+        StatusCode::OK,
+        Json(ApiResponse {
+            status: 200,
+            data: IdInfo {
+                id: decoded.id.to_string(),
+                entity_type: decoded.entity().map(|e| e.as_str()),
+                entity_type_code: decoded.entity_type,
+                timestamp_ms: decoded.timestamp_ms,
+                created_at,
+                sequence: decoded.sequence,
+            },
+        }),
+    )
+        .into_response()
+}
@@ -16,7 +16,7 @@ use crate::audit::{actor_from_headers, insert_audit_log};
 use crate::routes::ws::make_message;
 use application::items::ItemUsecase;
 use domain::{items::Item, response::ApiResponse};
-use shared::error::item_not_found;
+use shared::error::{error_response, item_not_found};
 
 #[derive(Debug, serde::Deserialize)]
 pub struct ListItemQuery {
@@ -133,11 +133,10 @@ pub async fn create_item(
     headers: HeaderMap,
     Json(item): Json<Item>,
 ) -> impl IntoResponse {
-    let item_id = item.id.clone();
-    let after_data = serde_json::to_value(&item).ok();
-
     match usecase.create(item).await {
-        Ok(_) => {
+        Ok(item) => {
+            let item_id = item.id.clone();
+            let after_data = serde_json::to_value(&item).ok();
             let actor = actor_from_headers(&headers);
             let actor_name = actor.username.clone();
 
@@ -165,20 +164,12 @@ pub async fn create_item(
                 StatusCode::CREATED,
                 Json(ApiResponse {
                     status: 201,
-                    data: "Item created",
+                    data: item,
                 }),
             )
                 .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "db_insert_error",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
     }
 }
 
@@ -190,11 +181,10 @@ pub async fn patch_item(
     Path(id): Path<String>,
     Json(patch): Json<Value>,
 ) -> impl IntoResponse {
-    let before_data = usecase
-        .find_by_id(&id)
-        .await
-        .ok()
-        .and_then(|item| serde_json::to_value(item).ok());
+    let before = usecase.find_by_id(&id).await.ok();
+    // The path may carry the slug; audit under the canonical id.
+    let id = before.as_ref().map(|item| item.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|item| serde_json::to_value(item).ok());
 
     match usecase.patch(&id, patch).await {
         Ok(_) => {
@@ -233,24 +223,22 @@ pub async fn patch_item(
             }))
             .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "db_update_error",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
     }
 }
 
-pub async fn delete_item(
+#[derive(Debug, serde::Deserialize)]
+pub struct RenameItemRequest {
+    pub key: String,
+}
+
+pub async fn rename_item(
     Extension(usecase): Extension<Arc<dyn ItemUsecase>>,
     Extension(tx): Extension<Arc<Sender<String>>>,
     Extension(pool): Extension<PgPool>,
     headers: HeaderMap,
     Path(id): Path<String>,
+    Json(req): Json<RenameItemRequest>,
 ) -> impl IntoResponse {
     let before_data = usecase
         .find_by_id(&id)
@@ -258,6 +246,60 @@ pub async fn delete_item(
         .ok()
         .and_then(|item| serde_json::to_value(item).ok());
 
+    match usecase.rename(&id, &req.key).await {
+        Ok(item) => {
+            let actor = actor_from_headers(&headers);
+            let actor_name = actor.username.clone();
+
+            insert_audit_log(
+                &pool,
+                "item",
+                &item.id,
+                "rename",
+                before_data,
+                serde_json::to_value(&item).ok(),
+                actor,
+            )
+            .await;
+
+            let msg = make_message("update", "item", &actor_name, "web");
+            let _ = tx.send(msg);
+
+            Json(ApiResponse {
+                status: 200,
+                data: item,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
+    }
+}
+
+pub async fn list_item_key_aliases(
+    Extension(usecase): Extension<Arc<dyn ItemUsecase>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match usecase.key_aliases(&id).await {
+        Ok(aliases) => Json(ApiResponse {
+            status: 200,
+            data: aliases,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn delete_item(
+    Extension(usecase): Extension<Arc<dyn ItemUsecase>>,
+    Extension(tx): Extension<Arc<Sender<String>>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let before = usecase.find_by_id(&id).await.ok();
+    let id = before.as_ref().map(|item| item.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|item| serde_json::to_value(item).ok());
+
     match usecase.delete(&id).await {
         Ok(_) => {
             let actor = actor_from_headers(&headers);
@@ -289,14 +331,6 @@ pub async fn delete_item(
             }))
             .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "db_delete_error",
-                "message": e.to_string(),
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_delete_error"),
     }
 }
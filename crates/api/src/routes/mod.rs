@@ -1,8 +1,11 @@
+pub mod alerts;
 pub mod audit_logs;
 pub mod auth;
 pub mod files;
+pub mod ids;
 pub mod items;
 pub mod recipes;
 pub mod status;
+pub mod storage;
 pub mod tickets;
 pub mod ws;
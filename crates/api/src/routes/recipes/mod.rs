@@ -10,6 +10,7 @@ use axum::{
 };
 use domain::{recipes::Recipe, response::ApiResponse};
 use serde_json::Value;
+use shared::error::error_response;
 use sqlx::PgPool;
 
 use crate::audit::{actor_from_headers, insert_audit_log};
@@ -69,32 +70,30 @@ pub async fn create_recipe(
     headers: HeaderMap,
     Json(recipe): Json<Recipe>,
 ) -> impl IntoResponse {
-    let recipe_id = recipe.id.clone();
-    let after_data = serde_json::to_value(&recipe).ok();
-
     match usecase.create(recipe).await {
-        Ok(_) => {
+        Ok(recipe) => {
             let actor = actor_from_headers(&headers);
             insert_audit_log(
-                &pool, "recipe", &recipe_id, "create", None, after_data, actor,
+                &pool,
+                "recipe",
+                &recipe.id,
+                "create",
+                None,
+                serde_json::to_value(&recipe).ok(),
+                actor,
             )
             .await;
 
             (
                 StatusCode::CREATED,
-                Json(serde_json::json!({"status": 201})),
+                Json(ApiResponse {
+                    status: 201,
+                    data: recipe,
+                }),
             )
                 .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": 500,
-                "code": "create_failed",
-                "message": e.to_string()
-            })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "create_failed"),
     }
 }
 
@@ -105,11 +104,9 @@ pub async fn patch_recipe(
     Path(id): Path<String>,
     Json(patch): Json<Value>,
 ) -> impl IntoResponse {
-    let before_data = usecase
-        .find_by_id(&id)
-        .await
-        .ok()
-        .and_then(|r| serde_json::to_value(r).ok());
+    let before = usecase.find_by_id(&id).await.ok();
+    let id = before.as_ref().map(|r| r.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|r| serde_json::to_value(r).ok());
 
     match usecase.patch(&id, patch).await {
         Ok(_) => {
@@ -155,11 +152,9 @@ pub async fn delete_recipe(
     headers: HeaderMap,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    let before_data = usecase
-        .find_by_id(&id)
-        .await
-        .ok()
-        .and_then(|r| serde_json::to_value(r).ok());
+    let before = usecase.find_by_id(&id).await.ok();
+    let id = before.as_ref().map(|r| r.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|r| serde_json::to_value(r).ok());
 
     match usecase.delete(&id).await {
         Ok(_) => {
@@ -1,13 +1,47 @@
 use application::status::StatusUsecase;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::{
     Json,
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     response::IntoResponse,
 };
-use domain::response::ApiResponse;
+use chrono::Utc;
+use domain::{response::ApiResponse, status::HistoryResolution};
+use sqlx::PgPool;
 use std::sync::Arc;
 
+use crate::audit::{actor_from_headers, insert_audit_log};
+
+const DEFAULT_HISTORY_SPAN_SECS: i64 = 24 * 60 * 60;
+const MAX_RAW_HISTORY_SPAN_SECS: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StatusHistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub resolution: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct NetworkHistoryQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub bucket_secs: Option<i64>,
+}
+
+const MIN_NETWORK_BUCKET_SECS: i64 = 60;
+const MAX_NETWORK_HISTORY_POINTS: i64 = 2000;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct IncidentListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct IncidentNotesRequest {
+    pub notes: Option<String>,
+}
+
 pub async fn get_status(
     Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
     Path(server_id): Path<String>,
@@ -50,3 +84,252 @@ pub async fn list_status(
             .into_response(),
     }
 }
+
+pub async fn get_status_history(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+    Path(server_id): Path<String>,
+    Query(query): Query<StatusHistoryQuery>,
+) -> impl IntoResponse {
+    let to = query.to.unwrap_or_else(|| Utc::now().timestamp());
+    let from = query.from.unwrap_or(to - DEFAULT_HISTORY_SPAN_SECS);
+
+    if from >= to {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "status": 400,
+                "code": "invalid_range",
+                "message": "from must be earlier than to"
+            })),
+        )
+            .into_response();
+    }
+
+    let resolution = match query.resolution.as_deref() {
+        None | Some("auto") => None,
+        Some(value) => match HistoryResolution::parse(value) {
+            Some(resolution) => Some(resolution),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "status": 400,
+                        "code": "invalid_resolution",
+                        "message": "resolution must be one of auto, raw, hour, day"
+                    })),
+                )
+                    .into_response();
+            }
+        },
+    };
+
+    if resolution == Some(HistoryResolution::Raw) && to - from > MAX_RAW_HISTORY_SPAN_SECS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "status": 400,
+                "code": "range_too_large",
+                "message": "raw history is limited to 7 days; use hour or day resolution"
+            })),
+        )
+            .into_response();
+    }
+
+    match usecase.history(&server_id, from, to, resolution).await {
+        Ok(history) => Json(ApiResponse {
+            status: 200,
+            data: history,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_fetch_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_status_uptime(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+    Path(server_id): Path<String>,
+) -> impl IntoResponse {
+    match usecase.uptime(&server_id).await {
+        Ok(summary) => Json(ApiResponse {
+            status: 200,
+            data: summary,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_fetch_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_status_incidents(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+    Path(server_id): Path<String>,
+    Query(query): Query<IncidentListQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    match usecase.list_incidents(&server_id, limit).await {
+        Ok(incidents) => Json(ApiResponse {
+            status: 200,
+            data: incidents,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_fetch_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn patch_status_incident(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(incident_id): Path<i64>,
+    Json(req): Json<IncidentNotesRequest>,
+) -> impl IntoResponse {
+    let before = match usecase.find_incident(incident_id).await {
+        Ok(incident) => incident,
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "status": 404,
+                    "code": "not_found",
+                    "message": e.to_string()
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let actor = actor_from_headers(&headers);
+
+    match usecase
+        .update_incident_notes(incident_id, req.notes, &actor.username)
+        .await
+    {
+        Ok(incident) => {
+            insert_audit_log(
+                &pool,
+                "status_incident",
+                &incident_id.to_string(),
+                "update",
+                serde_json::to_value(&before).ok(),
+                serde_json::to_value(&incident).ok(),
+                actor,
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: incident,
+            })
+            .into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_update_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_network_status(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+) -> impl IntoResponse {
+    match usecase.network().await {
+        Ok(network) => Json(ApiResponse {
+            status: 200,
+            data: network,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_fetch_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn get_network_history(
+    Extension(usecase): Extension<Arc<dyn StatusUsecase>>,
+    Query(query): Query<NetworkHistoryQuery>,
+) -> impl IntoResponse {
+    let to = query.to.unwrap_or_else(|| Utc::now().timestamp());
+    let from = query.from.unwrap_or(to - DEFAULT_HISTORY_SPAN_SECS);
+
+    if from >= to {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "status": 400,
+                "code": "invalid_range",
+                "message": "from must be earlier than to"
+            })),
+        )
+            .into_response();
+    }
+
+    if let Some(bucket_secs) = query.bucket_secs
+        && (bucket_secs < MIN_NETWORK_BUCKET_SECS
+            || (to - from) / bucket_secs > MAX_NETWORK_HISTORY_POINTS)
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "status": 400,
+                "code": "invalid_bucket",
+                "message": format!(
+                    "bucket_secs must be at least {} and yield at most {} points",
+                    MIN_NETWORK_BUCKET_SECS, MAX_NETWORK_HISTORY_POINTS
+                )
+            })),
+        )
+            .into_response();
+    }
+
+    match usecase.network_history(from, to, query.bucket_secs).await {
+        Ok(history) => Json(ApiResponse {
+            status: 200,
+            data: history,
+        })
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "status": 500,
+                "code": "db_fetch_error",
+                "message": e.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
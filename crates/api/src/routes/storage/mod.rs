@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Extension, Path, Query},
+    http::{
+        HeaderValue, StatusCode,
+        header::{CONTENT_TYPE, ETAG},
+    },
+    response::IntoResponse,
+    routing::{get, put},
+};
+use domain::files::{SNIFF_LEN, is_public_key, sniff_content_type};
+use infrastructure::storage::LocalObjectStore;
+use shared::error::{ApiError, error_response};
+use std::io::ErrorKind;
+
+/// Parts are 16 MiB; leave headroom for clients using a larger part size.
+const MAX_PART_BYTES: usize = 64 * 1024 * 1024;
+
+/// Part uploads and downloads for the local storage backend. These URLs are
+/// signed or serve public objects, so the router is merged outside the
+/// bearer-token check, like presigned R2 URLs and the CDN.
+pub fn local_storage_router(store: Arc<LocalObjectStore>) -> Router {
+    Router::new()
+        .route(
+            "/v1/storage/uploads/{upload_id}/parts/{part_number}",
+            put(upload_part),
+        )
+        .route("/v1/storage/objects/{*key}", get(download_object))
+        .layer(DefaultBodyLimit::max(MAX_PART_BYTES))
+        .layer(Extension(store))
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SignedPartQuery {
+    pub expires: i64,
+    pub signature: String,
+}
+
+pub async fn upload_part(
+    Extension(store): Extension<Arc<LocalObjectStore>>,
+    Path((upload_id, part_number)): Path<(String, i32)>,
+    Query(query): Query<SignedPartQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    match store
+        .write_part(
+            &upload_id,
+            part_number,
+            query.expires,
+            &query.signature,
+            &body,
+        )
+        .await
+    {
+        Ok(etag) => {
+            let etag = HeaderValue::from_str(&format!("\"{}\"", etag))
+                .expect("hex ETag is a valid header value");
+            (StatusCode::OK, [(ETAG, etag)]).into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "upload_failed"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SignedDownloadQuery {
+    pub expires: Option<i64>,
+    pub signature: Option<String>,
+}
+
+/// Serves public objects to anyone and private ones only through a URL from
+/// `presign_get`.
+pub async fn download_object(
+    Extension(store): Extension<Arc<LocalObjectStore>>,
+    Path(key): Path<String>,
+    Query(query): Query<SignedDownloadQuery>,
+) -> impl IntoResponse {
+    if !is_public_key(&key) {
+        let (Some(expires), Some(signature)) = (query.expires, query.signature.as_deref()) else {
+            return ApiError::forbidden("signature_required", "This object requires a signed URL")
+                .into_response();
+        };
+        if let Err(e) = store.verify_download(&key, expires, signature) {
+            return error_response(e, StatusCode::FORBIDDEN, "invalid_signature");
+        }
+    }
+
+    let path = match store.object_path(&key) {
+        Ok(path) => path,
+        Err(e) => return ApiError::bad_request("invalid_key", e.to_string()).into_response(),
+    };
+    match tokio::fs::read(path).await {
+        Ok(data) => {
+            let head = &data[..data.len().min(SNIFF_LEN)];
+            let content_type = sniff_content_type(head).unwrap_or("application/octet-stream");
+            (StatusCode::OK, [(CONTENT_TYPE, content_type)], data).into_response()
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => ApiError::not_found(
+            "object_not_found",
+            format!("Object '{}' does not exist", key),
+        )
+        .into_response(),
+        Err(e) => error_response(
+            e.into(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "download_failed",
+        ),
+    }
+}
@@ -5,42 +5,81 @@ use axum::{
     Json,
     extract::{Extension, Path, Query},
 };
-use domain::tickets::Ticket;
+use domain::{
+    response::ApiResponse,
+    tickets::{
+        CannedResponse, CannedResponsePatch, NewTicketMessage, Ticket, TicketCategory,
+        TicketFilter, TicketStaff, TicketStatus,
+    },
+};
+use shared::error::{ApiError, error_response};
 use sqlx::PgPool;
 use std::sync::Arc;
 
-use crate::audit::{actor_from_headers, insert_audit_log};
+use crate::audit::{Actor, actor_from_headers, insert_audit_log};
 
-#[derive(Debug, serde::Deserialize)]
-pub struct TicketQuery {
-    pub user_id: Option<String>,
+/// Identifier stored on messages, revisions and transitions.
+fn actor_key(actor: &Actor) -> String {
+    actor
+        .discord_id
+        .clone()
+        .unwrap_or_else(|| actor.username.clone())
 }
 
 pub async fn list_tickets(
     Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
-    Query(TicketQuery { user_id }): Query<TicketQuery>,
+    headers: HeaderMap,
+    Query(filter): Query<TicketFilter>,
 ) -> impl IntoResponse {
-    match usecase.find_all(user_id).await {
-        Ok(tickets) => Json(tickets).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "message": e.to_string() })),
+    let viewer = actor_from_headers(&headers).discord_id;
+
+    match usecase.find_all(filter, viewer.as_deref()).await {
+        Ok(page) => Json(ApiResponse {
+            status: 200,
+            data: page,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+/// Active tickets assigned to the calling staff member.
+pub async fn my_ticket_queue(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(discord_id) = actor_from_headers(&headers).discord_id else {
+        return ApiError::bad_request(
+            "missing_actor",
+            "x-actor-discord-id is required to load the queue",
         )
-            .into_response(),
+        .into_response();
+    };
+
+    match usecase.queue(&discord_id).await {
+        Ok(tickets) => Json(ApiResponse {
+            status: 200,
+            data: tickets,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
     }
 }
 
 pub async fn find_ticket_by_id(
     Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    headers: HeaderMap,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    match usecase.find_by_id(&id).await {
-        Ok(ticket) => Json(ticket).into_response(),
-        Err(e) => (
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({ "message": e.to_string() })),
-        )
-            .into_response(),
+    let viewer = actor_from_headers(&headers).discord_id;
+
+    match usecase.view(&id, viewer.as_deref()).await {
+        Ok(ticket) => Json(ApiResponse {
+            status: 200,
+            data: ticket,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "fetch_failed"),
     }
 }
 
@@ -50,28 +89,31 @@ pub async fn create_ticket(
     headers: HeaderMap,
     Json(ticket): Json<Ticket>,
 ) -> impl IntoResponse {
-    let ticket_id = ticket.id.clone();
-    let after_data = serde_json::to_value(&ticket).ok();
+    let actor = actor_from_headers(&headers);
 
-    match usecase.create(ticket).await {
-        Ok(_) => {
-            let actor = actor_from_headers(&headers);
+    match usecase.create(ticket, &actor_key(&actor)).await {
+        Ok(ticket) => {
             insert_audit_log(
-                &pool, "ticket", &ticket_id, "create", None, after_data, actor,
+                &pool,
+                "ticket",
+                &ticket.id,
+                "create",
+                None,
+                serde_json::to_value(&ticket).ok(),
+                actor,
             )
             .await;
 
             (
                 StatusCode::CREATED,
-                Json(serde_json::json!({ "message": "Created" })),
+                Json(ApiResponse {
+                    status: 201,
+                    data: ticket,
+                }),
             )
                 .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "message": e.to_string() })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
     }
 }
 
@@ -82,16 +124,14 @@ pub async fn patch_ticket(
     Path(id): Path<String>,
     Json(ticket): Json<Ticket>,
 ) -> impl IntoResponse {
-    let before_data = usecase
-        .find_by_id(&id)
-        .await
-        .ok()
-        .and_then(|t| serde_json::to_value(t).ok());
+    let before = usecase.find_by_id(&id).await.ok();
+    let id = before.as_ref().map(|t| t.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|t| serde_json::to_value(t).ok());
     let after_data = serde_json::to_value(&ticket).ok();
+    let actor = actor_from_headers(&headers);
 
-    match usecase.update(&id, ticket).await {
+    match usecase.update(&id, ticket, &actor_key(&actor)).await {
         Ok(_) => {
-            let actor = actor_from_headers(&headers);
             insert_audit_log(
                 &pool,
                 "ticket",
@@ -109,11 +149,7 @@ pub async fn patch_ticket(
             )
                 .into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "message": e.to_string() })),
-        )
-            .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
     }
 }
 
@@ -123,11 +159,9 @@ pub async fn delete_ticket(
     headers: HeaderMap,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    let before_data = usecase
-        .find_by_id(&id)
-        .await
-        .ok()
-        .and_then(|t| serde_json::to_value(t).ok());
+    let before = usecase.find_by_id(&id).await.ok();
+    let id = before.as_ref().map(|t| t.id.clone()).unwrap_or(id);
+    let before_data = before.and_then(|t| serde_json::to_value(t).ok());
 
     match usecase.delete(&id).await {
         Ok(_) => {
@@ -147,3 +181,549 @@ pub async fn delete_ticket(
             .into_response(),
     }
 }
+
+/// The sender is always the actor from the request headers.
+#[derive(Debug, serde::Deserialize)]
+pub struct CreateMessageRequest {
+    #[serde(default)]
+    pub content: String,
+    /// Ids of files uploaded through `/v1/files/uploads`.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    /// Staff-only note hidden from the player.
+    #[serde(default)]
+    pub internal: bool,
+    pub canned_response_id: Option<i64>,
+    /// Overrides the `{player_name}` placeholder, which defaults to the
+    /// ticket's user id.
+    pub player_name: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct EditMessageRequest {
+    pub content: String,
+}
+
+pub async fn create_ticket_message(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<CreateMessageRequest>,
+) -> impl IntoResponse {
+    let actor = actor_from_headers(&headers);
+    let message = NewTicketMessage {
+        sender: actor_key(&actor),
+        content: req.content,
+        attachments: req.attachments,
+        internal: req.internal,
+        canned_response_id: req.canned_response_id,
+        player_name: req.player_name,
+        staff_name: Some(
+            actor
+                .global_name
+                .clone()
+                .unwrap_or_else(|| actor.username.clone()),
+        ),
+        via_discord: false,
+    };
+
+    match usecase.add_message(&id, message).await {
+        Ok(message) => {
+            if let Some(canned_response_id) = message.canned_response_id {
+                // `id` may be the slug; the ticket's audit trail is kept under its id.
+                let ticket_id = usecase
+                    .find_by_id(&id)
+                    .await
+                    .map(|ticket| ticket.id)
+                    .unwrap_or(id);
+                insert_audit_log(
+                    &pool,
+                    "ticket",
+                    &ticket_id,
+                    "canned_response",
+                    None,
+                    Some(serde_json::json!({
+                        "message_id": message.id,
+                        "canned_response_id": canned_response_id,
+                    })),
+                    actor.clone(),
+                )
+                .await;
+            }
+
+            insert_audit_log(
+                &pool,
+                "ticket_message",
+                &message.id.to_string(),
+                "create",
+                None,
+                serde_json::to_value(&message).ok(),
+                actor,
+            )
+            .await;
+
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse {
+                    status: 201,
+                    data: message,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct DiscordMessageRequest {
+    pub thread_id: String,
+    pub author_id: String,
+    pub author_name: Option<String>,
+    pub content: String,
+}
+
+/// Called by the Discord bot for replies posted in a ticket thread.
+pub async fn create_discord_ticket_message(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    Json(req): Json<DiscordMessageRequest>,
+) -> impl IntoResponse {
+    match usecase
+        .add_discord_message(&req.thread_id, &req.author_id, &req.content)
+        .await
+    {
+        Ok((ticket_id, message)) => {
+            insert_audit_log(
+                &pool,
+                "ticket_message",
+                &message.id.to_string(),
+                "create",
+                None,
+                Some(serde_json::json!({
+                    "ticket_id": ticket_id,
+                    "thread_id": req.thread_id,
+                    "message": message,
+                })),
+                Actor {
+                    username: req.author_name.unwrap_or_else(|| req.author_id.clone()),
+                    discord_id: Some(req.author_id),
+                    global_name: None,
+                    avatar_url: None,
+                },
+            )
+            .await;
+
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse {
+                    status: 201,
+                    data: message,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
+    }
+}
+
+pub async fn edit_ticket_message(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path((id, message_id)): Path<(String, i64)>,
+    Json(req): Json<EditMessageRequest>,
+) -> impl IntoResponse {
+    let actor = actor_from_headers(&headers);
+    let editor = actor_key(&actor);
+
+    match usecase
+        .edit_message(&id, message_id, &req.content, &editor)
+        .await
+    {
+        Ok((before, after)) => {
+            insert_audit_log(
+                &pool,
+                "ticket_message",
+                &message_id.to_string(),
+                "update",
+                serde_json::to_value(&before).ok(),
+                serde_json::to_value(&after).ok(),
+                actor,
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: after,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
+    }
+}
+
+pub async fn delete_ticket_message(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path((id, message_id)): Path<(String, i64)>,
+) -> impl IntoResponse {
+    let actor = actor_from_headers(&headers);
+    let editor = actor_key(&actor);
+
+    match usecase.delete_message(&id, message_id, &editor).await {
+        Ok(before) => {
+            insert_audit_log(
+                &pool,
+                "ticket_message",
+                &message_id.to_string(),
+                "delete",
+                serde_json::to_value(&before).ok(),
+                None,
+                actor,
+            )
+            .await;
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "message": "Deleted" })),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_delete_error"),
+    }
+}
+
+pub async fn ticket_message_history(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    headers: HeaderMap,
+    Path((id, message_id)): Path<(String, i64)>,
+) -> impl IntoResponse {
+    let viewer = actor_from_headers(&headers).discord_id;
+
+    match usecase
+        .message_history(&id, message_id, viewer.as_deref())
+        .await
+    {
+        Ok(revisions) => Json(ApiResponse {
+            status: 200,
+            data: revisions,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TransitionRequest {
+    pub status: TicketStatus,
+    pub reason: Option<String>,
+}
+
+/// Automatic transitions (message replies, auto-close) are only recorded in
+/// the ticket's transition history.
+pub async fn create_ticket_transition(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<TransitionRequest>,
+) -> impl IntoResponse {
+    let actor = actor_from_headers(&headers);
+
+    match usecase
+        .transition(&id, req.status, &actor_key(&actor), req.reason.as_deref())
+        .await
+    {
+        Ok(transition) => {
+            insert_audit_log(
+                &pool,
+                "ticket",
+                &transition.ticket_id,
+                "transition",
+                Some(serde_json::json!({ "status": transition.from_status })),
+                Some(serde_json::json!({
+                    "status": transition.to_status,
+                    "reason": transition.reason,
+                })),
+                actor,
+            )
+            .await;
+
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse {
+                    status: 201,
+                    data: transition,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
+    }
+}
+
+pub async fn list_ticket_transitions(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match usecase.list_transitions(&id).await {
+        Ok(transitions) => Json(ApiResponse {
+            status: 200,
+            data: transitions,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn list_ticket_staff(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+) -> impl IntoResponse {
+    match usecase.list_staff().await {
+        Ok(staff) => Json(ApiResponse {
+            status: 200,
+            data: staff,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn upsert_ticket_staff(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Json(staff): Json<TicketStaff>,
+) -> impl IntoResponse {
+    match usecase.upsert_staff(staff).await {
+        Ok(staff) => {
+            insert_audit_log(
+                &pool,
+                "ticket_staff",
+                &format!("{}:{}", staff.discord_id, staff.category),
+                "upsert",
+                None,
+                serde_json::to_value(&staff).ok(),
+                actor_from_headers(&headers),
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: staff,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
+    }
+}
+
+pub async fn delete_ticket_staff(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path((discord_id, category)): Path<(String, TicketCategory)>,
+) -> impl IntoResponse {
+    match usecase.remove_staff(&discord_id, category).await {
+        Ok(_) => {
+            insert_audit_log(
+                &pool,
+                "ticket_staff",
+                &format!("{}:{}", discord_id, category),
+                "delete",
+                None,
+                None,
+                actor_from_headers(&headers),
+            )
+            .await;
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "message": "Deleted" })),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_delete_error"),
+    }
+}
+
+pub async fn get_ticket_attachment(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    headers: HeaderMap,
+    Path((id, file_id)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let viewer = actor_from_headers(&headers).discord_id;
+
+    match usecase
+        .get_attachment(&id, &file_id, viewer.as_deref())
+        .await
+    {
+        Ok(file) => Json(ApiResponse {
+            status: 200,
+            data: file,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CannedListQuery {
+    pub category: Option<TicketCategory>,
+}
+
+pub async fn list_canned_responses(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Query(CannedListQuery { category }): Query<CannedListQuery>,
+) -> impl IntoResponse {
+    match usecase.list_canned(category).await {
+        Ok(responses) => Json(ApiResponse {
+            status: 200,
+            data: responses,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn find_canned_response(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match usecase.find_canned(id).await {
+        Ok(response) => Json(ApiResponse {
+            status: 200,
+            data: response,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
+
+pub async fn create_canned_response(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Json(response): Json<CannedResponse>,
+) -> impl IntoResponse {
+    let actor = actor_from_headers(&headers);
+
+    match usecase.create_canned(response, &actor_key(&actor)).await {
+        Ok(response) => {
+            insert_audit_log(
+                &pool,
+                "ticket_canned_response",
+                &response.id.to_string(),
+                "create",
+                None,
+                serde_json::to_value(&response).ok(),
+                actor,
+            )
+            .await;
+
+            (
+                StatusCode::CREATED,
+                Json(ApiResponse {
+                    status: 201,
+                    data: response,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_insert_error"),
+    }
+}
+
+pub async fn patch_canned_response(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Json(patch): Json<CannedResponsePatch>,
+) -> impl IntoResponse {
+    let before_data = usecase
+        .find_canned(id)
+        .await
+        .ok()
+        .and_then(|r| serde_json::to_value(r).ok());
+
+    match usecase.update_canned(id, patch).await {
+        Ok(response) => {
+            insert_audit_log(
+                &pool,
+                "ticket_canned_response",
+                &id.to_string(),
+                "update",
+                before_data,
+                serde_json::to_value(&response).ok(),
+                actor_from_headers(&headers),
+            )
+            .await;
+
+            Json(ApiResponse {
+                status: 200,
+                data: response,
+            })
+            .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_update_error"),
+    }
+}
+
+pub async fn delete_canned_response(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Extension(pool): Extension<PgPool>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match usecase.delete_canned(id).await {
+        Ok(response) => {
+            insert_audit_log(
+                &pool,
+                "ticket_canned_response",
+                &id.to_string(),
+                "delete",
+                serde_json::to_value(&response).ok(),
+                None,
+                actor_from_headers(&headers),
+            )
+            .await;
+
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "message": "Deleted" })),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_delete_error"),
+    }
+}
+
+const DEFAULT_SLA_RANGE_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SlaReportQuery {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+/// Defaults to the last 30 days.
+pub async fn ticket_sla_report(
+    Extension(usecase): Extension<Arc<dyn TicketUsecase>>,
+    Query(query): Query<SlaReportQuery>,
+) -> impl IntoResponse {
+    let to = query.to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = query.from.unwrap_or(to - DEFAULT_SLA_RANGE_SECS);
+
+    match usecase.sla_report(from, to).await {
+        Ok(report) => Json(ApiResponse {
+            status: 200,
+            data: report,
+        })
+        .into_response(),
+        Err(e) => error_response(e, StatusCode::INTERNAL_SERVER_ERROR, "db_fetch_error"),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod usecase;
+
+pub use usecase::*;
@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use domain::alerts::{AlertDelivery, AlertEvent, AlertRule, AlertRulePatch, AlertWindowStats};
+use infrastructure::{
+    alert_dispatcher::alert_payload,
+    repositorys::{alert::AlertRepository, is_not_found},
+    webhook::{WebhookOutcome, WebhookSender},
+};
+use shared::error::{ApiError, AppResult};
+
+pub struct AlertUsecaseImpl<R: AlertRepository + Send + Sync> {
+    pub repo: R,
+    pub sender: WebhookSender,
+}
+
+impl<R: AlertRepository + Send + Sync> AlertUsecaseImpl<R> {
+    pub fn new(repo: R, sender: WebhookSender) -> Self {
+        Self { repo, sender }
+    }
+
+    async fn ensure_rule(&self, id: i64) -> AppResult<AlertRule> {
+        self.repo.find_rule(id).await.map_err(|err| {
+            if is_not_found(&err) {
+                ApiError::not_found("rule_not_found", format!("Alert rule {} not found", id)).into()
+            } else {
+                err
+            }
+        })
+    }
+}
+
+#[async_trait]
+pub trait AlertUsecase: Send + Sync {
+    async fn find_all(&self) -> AppResult<Vec<AlertRule>>;
+    async fn find_by_id(&self, id: i64) -> AppResult<AlertRule>;
+    async fn create(&self, rule: AlertRule) -> AppResult<AlertRule>;
+    async fn patch(&self, id: i64, patch: AlertRulePatch) -> AppResult<AlertRule>;
+    async fn delete(&self, id: i64) -> AppResult<()>;
+    async fn list_deliveries(
+        &self,
+        rule_id: Option<i64>,
+        limit: i64,
+    ) -> AppResult<Vec<AlertDelivery>>;
+    async fn send_test(&self, id: i64, server_id: Option<String>) -> AppResult<WebhookOutcome>;
+}
+
+#[async_trait]
+impl<R: AlertRepository + Send + Sync> AlertUsecase for AlertUsecaseImpl<R> {
+    async fn find_all(&self) -> AppResult<Vec<AlertRule>> {
+        self.repo.list_rules(false).await
+    }
+
+    async fn find_by_id(&self, id: i64) -> AppResult<AlertRule> {
+        self.repo.find_rule(id).await
+    }
+
+    async fn create(&self, mut rule: AlertRule) -> AppResult<AlertRule> {
+        rule.validate()
+            .map_err(|message| ApiError::bad_request("invalid_rule", message))?;
+        rule.id = self.repo.insert_rule(&rule).await?;
+        Ok(rule)
+    }
+
+    async fn patch(&self, id: i64, patch: AlertRulePatch) -> AppResult<AlertRule> {
+        let mut rule = self.repo.find_rule(id).await?;
+        rule.apply(patch);
+        rule.validate()
+            .map_err(|message| ApiError::bad_request("invalid_rule", message))?;
+        self.repo.update_rule(id, &rule).await?;
+        Ok(rule)
+    }
+
+    async fn delete(&self, id: i64) -> AppResult<()> {
+        self.repo.delete_rule(id).await
+    }
+
+    async fn list_deliveries(
+        &self,
+        rule_id: Option<i64>,
+        limit: i64,
+    ) -> AppResult<Vec<AlertDelivery>> {
+        self.repo.list_deliveries(rule_id, limit).await
+    }
+
+    async fn send_test(&self, id: i64, server_id: Option<String>) -> AppResult<WebhookOutcome> {
+        let rule = self.ensure_rule(id).await?;
+        let server_id = server_id
+            .or_else(|| rule.server_id.clone())
+            .unwrap_or_else(|| "test".to_string());
+
+        let event = AlertEvent::Test;
+        let payload = alert_payload(
+            &rule,
+            &server_id,
+            event,
+            &AlertWindowStats::default(),
+            Utc::now().timestamp(),
+        );
+        let outcome = self
+            .sender
+            .send(
+                &rule.webhook_url,
+                rule.secret.as_deref(),
+                &event.to_string(),
+                &payload,
+            )
+            .await;
+
+        self.repo
+            .insert_delivery(rule.id, &server_id, event, &payload, &outcome)
+            .await?;
+        Ok(outcome)
+    }
+}
@@ -0,0 +1,160 @@
+use domain::files::texture_frames;
+use image::{DynamicImage, GenericImageView, ImageFormat, imageops::FilterType};
+use shared::error::AppResult;
+use std::io::Cursor;
+
+const THUMBNAIL_SIZE: u32 = 128;
+const PREVIEW_SIZE: u32 = 512;
+/// Images no larger than this on either side are treated as pixel art and
+/// scaled with nearest-neighbour so their pixels stay crisp.
+const PIXEL_ART_MAX_SIDE: u32 = 64;
+
+/// Renditions and dimensions of a decoded image upload.
+pub(crate) struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub thumbnail: Vec<u8>,
+    pub preview: Vec<u8>,
+}
+
+/// Decodes `data` and renders a PNG thumbnail and preview of its first
+/// frame. Minecraft textures are split into their animation frames and always
+/// rendered as pixel art.
+pub(crate) fn process_image(data: &[u8], texture: bool) -> AppResult<ProcessedImage> {
+    let format = image::guess_format(data)?;
+    let image = image::load_from_memory_with_format(data, format)?;
+    let (width, height) = image.dimensions();
+
+    let (first_frame, frames) = if texture {
+        let frames = texture_frames(width, height).map_err(anyhow::Error::msg)?;
+        (image.crop_imm(0, 0, width, width), frames)
+    } else if format == ImageFormat::Gif {
+        (image, gif_frame_count(data))
+    } else {
+        (image, 1)
+    };
+
+    let pixel_art = texture
+        || (first_frame.width() <= PIXEL_ART_MAX_SIDE
+            && first_frame.height() <= PIXEL_ART_MAX_SIDE);
+
+    Ok(ProcessedImage {
+        width,
+        height,
+        frames,
+        thumbnail: encode_png(&render(&first_frame, THUMBNAIL_SIZE, pixel_art))?,
+        preview: encode_png(&render(&first_frame, PREVIEW_SIZE, pixel_art))?,
+    })
+}
+
+/// Number of image descriptors in a GIF, found by walking its blocks rather
+/// than decoding and compositing every frame. Stops at the first malformed or
+/// truncated block; the first frame has already been decoded by then.
+fn gif_frame_count(data: &[u8]) -> u32 {
+    // Header and logical screen descriptor.
+    let Some(&flags) = data.get(10) else {
+        return 1;
+    };
+    let mut pos = 13 + color_table_len(flags);
+    let mut frames = 0;
+
+    while let Some(&block) = data.get(pos) {
+        pos = match block {
+            // Extension: label, then data sub-blocks.
+            0x21 => skip_sub_blocks(data, pos + 2),
+            // Image descriptor, optional local colour table, LZW code size,
+            // then image data sub-blocks.
+            0x2c => {
+                let Some(&flags) = data.get(pos + 9) else {
+                    break;
+                };
+                frames += 1;
+                skip_sub_blocks(data, pos + 10 + color_table_len(flags) + 1)
+            }
+            _ => break,
+        }
+        .unwrap_or(data.len());
+    }
+    frames.max(1)
+}
+
+fn color_table_len(flags: u8) -> usize {
+    if flags & 0x80 == 0 {
+        return 0;
+    }
+    3 << ((flags & 0x07) + 1)
+}
+
+/// Position after the sub-block chain starting at `pos`, if it is complete.
+fn skip_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(pos)? as usize;
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// Fits `image` into a `size` square. Pixel art is scaled up by a whole
+/// factor; anything else is only ever scaled down.
+fn render(image: &DynamicImage, size: u32, pixel_art: bool) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let side = width.max(height);
+
+    if pixel_art {
+        if side <= size {
+            let factor = size / side;
+            return image.resize_exact(width * factor, height * factor, FilterType::Nearest);
+        }
+        return image.resize(size, size, FilterType::Nearest);
+    }
+    if side <= size {
+        return image.clone();
+    }
+    image.resize(size, size, FilterType::Lanczos3)
+}
+
+fn encode_png(image: &DynamicImage) -> AppResult<Vec<u8>> {
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gif_frame_count;
+    use image::{
+        Delay, Frame, RgbaImage,
+        codecs::gif::{GifEncoder, Repeat},
+    };
+
+    fn gif(frames: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+            for i in 0..frames {
+                let image = RgbaImage::from_pixel(4, 4, image::Rgba([i as u8 * 40, 0, 0, 255]));
+                let frame = Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(100, 1));
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn counts_gif_frames() {
+        for frames in [1, 2, 5] {
+            assert_eq!(gif_frame_count(&gif(frames)), frames as u32);
+        }
+    }
+
+    #[test]
+    fn counts_frames_before_truncation() {
+        let data = gif(3);
+        assert_eq!(gif_frame_count(&data[..data.len() - 1]), 3);
+        assert_eq!(gif_frame_count(&data[..8]), 1);
+    }
+}
@@ -1,3 +1,6 @@
+mod images;
+pub mod reaper;
 pub mod usecase;
 
+pub use reaper::*;
 pub use usecase::*;
@@ -0,0 +1,49 @@
+use shared::error::AppResult;
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+use crate::files::FileUsecase;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Aborts multipart uploads that saw no activity for `ttl_hours`. A value of
+/// `0` disables the job.
+pub async fn start_upload_reaper(usecase: Arc<dyn FileUsecase>, ttl_hours: u64) -> AppResult<()> {
+    if ttl_hours == 0 {
+        tracing::info!("Stale upload reaper is disabled");
+        return Ok(());
+    }
+
+    let ttl = Duration::from_secs(ttl_hours * 60 * 60);
+    tokio::spawn(async move {
+        loop {
+            match usecase.reap_stale_uploads(ttl).await {
+                Ok(run) if run.expired.is_empty() && run.orphaned.is_empty() => {}
+                Ok(run) => tracing::info!(
+                    "Reaped {} expired and {} orphaned uploads ({} failures)",
+                    run.expired.len(),
+                    run.orphaned.len(),
+                    run.failures.len()
+                ),
+                Err(err) => tracing::error!("Failed to reap stale uploads: {}", err),
+            }
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// Moves files queued by migrations to their new visibility in the
+/// background, once per start.
+pub async fn start_visibility_backfill(usecase: Arc<dyn FileUsecase>) -> AppResult<()> {
+    tokio::spawn(async move {
+        match usecase.apply_pending_visibility_changes().await {
+            Ok(0) => {}
+            Ok(moved) => tracing::info!("Moved {} files to their new visibility", moved),
+            Err(err) => tracing::error!("Failed to move files to their new visibility: {}", err),
+        }
+    });
+
+    Ok(())
+}
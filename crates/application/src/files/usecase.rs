@@ -1,21 +1,393 @@
 use async_trait::async_trait;
+use axum::{body::Bytes, http::StatusCode};
 use chrono::Utc;
-use domain::files::{FileMetadata, FileUploadPart, FileUploadSession};
-use infrastructure::repositorys::file::FileRepository;
-use shared::error::AppResult;
-use std::{collections::HashMap, env};
-use uuid::Uuid;
+use domain::files::{
+    DIRECT_UPLOAD_MAX_SIZE, FILES_PREFIX, FileFilter, FileFolder, FileMetadata, FilePatch,
+    FileQuota, FileUploadPart, FileUploadSession, FileUrlConfig, FileUsage, FileUsageKind,
+    FileVisibility, NewFile, PREVIEW_NAME, PRIVATE_PREFIX, ReapedUpload, SNIFF_LEN,
+    StorageUsageReport, THUMBNAIL_NAME, UPLOAD_IN_PROGRESS, UPLOAD_QUARANTINED, UploadPurpose,
+    UploadReaperRun, UserUsageReport, content_type_essence, content_type_matches, derived_key,
+    normalize_folder, normalize_tags, png_dimensions, remaining_bytes, sniff_content_type,
+    texture_frames,
+};
+use domain::response::Paginated;
+use futures::{StreamExt, stream::BoxStream};
+use infrastructure::repositorys::{file::FileRepository, is_not_found};
+use infrastructure::storage::{ObjectStore, UploadedPart};
+use sha2::{Digest, Sha256};
+use shared::error::{ApiError, AppResult};
+use shared::{EntityType, IdGenerator};
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
+use tokio_util::io::StreamReader;
 
-use s3::creds::Credentials;
-use s3::{Bucket, Region};
+use crate::files::images::{self, ProcessedImage};
+
+/// Prefix objects are moved under when they fail verification.
+const QUARANTINE_PREFIX: &str = "quarantine/";
+const PRESIGN_EXPIRES: Duration = Duration::from_secs(60 * 10);
+/// Image types that get a thumbnail and preview.
+const PROCESSED_IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+/// Larger images are stored without renditions rather than decoded.
+const MAX_PROCESSED_SIZE: i64 = 32 * 1024 * 1024;
 
 pub struct FileUsecaseImpl<R: FileRepository + Send + Sync> {
     pub repo: R,
+    pub ids: Arc<IdGenerator>,
+    pub store: Arc<dyn ObjectStore>,
+    pub urls: FileUrlConfig,
+    pub quota: FileQuota,
 }
 
 impl<R: FileRepository + Send + Sync> FileUsecaseImpl<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: R,
+        ids: Arc<IdGenerator>,
+        store: Arc<dyn ObjectStore>,
+        urls: FileUrlConfig,
+        quota: FileQuota,
+    ) -> Self {
+        Self {
+            repo,
+            ids,
+            store,
+            urls,
+            quota,
+        }
+    }
+
+    /// Fails with `quota_exceeded` when storing `size` more bytes for
+    /// `user_id` would go over their quota or the global one. Otherwise
+    /// returns the bytes left under the tighter of the two, if any is set.
+    /// Usage is read without a lock, so concurrent uploads can each pass the
+    /// check.
+    async fn ensure_quota(&self, user_id: &str, size: i64) -> AppResult<Option<i64>> {
+        let mut left: Option<i64> = None;
+        if let Some(quota) = self.quota.per_user_bytes {
+            let used = self.repo.storage_usage(Some(user_id)).await?.used_bytes;
+            let user_left = (quota - used).max(0);
+            if used + size > quota {
+                return Err(quota_exceeded(format!(
+                    "User '{}' has {} of {} bytes left and cannot store {} more",
+                    user_id, user_left, quota, size
+                )));
+            }
+            left = Some(user_left);
+        }
+        if let Some(quota) = self.quota.total_bytes {
+            let used = self.repo.storage_usage(None).await?.used_bytes;
+            let total_left = (quota - used).max(0);
+            if used + size > quota {
+                return Err(quota_exceeded(format!(
+                    "Storage has {} of {} bytes left and cannot store {} more",
+                    total_left, quota, size
+                )));
+            }
+            left = Some(left.map_or(total_left, |left| left.min(total_left)));
+        }
+        Ok(left)
+    }
+
+    async fn ensure_file(&self, file_id: &str) -> AppResult<FileMetadata> {
+        self.repo.find_metadata(file_id).await.map_err(|err| {
+            if is_not_found(&err) {
+                ApiError::not_found("file_not_found", format!("File '{}' not found", file_id))
+                    .into()
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Id to record a reference under: items may be given by slug.
+    async fn resolve_entity(&self, kind: FileUsageKind, entity_id: &str) -> AppResult<String> {
+        let entity_id = entity_id.trim();
+        match kind {
+            FileUsageKind::Ticket => Err(ApiError::bad_request(
+                "ticket_usage_implicit",
+                "Tickets use a file by attaching it to a message",
+            )
+            .into()),
+            FileUsageKind::Item => self.repo.find_item_id(entity_id).await?.ok_or_else(|| {
+                ApiError::not_found("item_not_found", format!("Item '{}' not found", entity_id))
+                    .into()
+            }),
+            FileUsageKind::ResourcePack if entity_id.is_empty() => {
+                Err(ApiError::bad_request("invalid_entity_id", "entity_id is required").into())
+            }
+            FileUsageKind::ResourcePack => Ok(entity_id.to_string()),
+        }
+    }
+
+    /// Moves the file, and its renditions, to the prefix for `visibility`.
+    async fn change_visibility(
+        &self,
+        metadata: &FileMetadata,
+        visibility: FileVisibility,
+    ) -> AppResult<()> {
+        if metadata.visibility == visibility {
+            return Ok(());
+        }
+
+        // Copy first so the file stays readable whichever way this fails.
+        let target = visibility.object_key(&metadata.key);
+        self.store.copy_object(&metadata.key, &target).await?;
+        let (key, released) = self
+            .repo
+            .set_visibility(&metadata.id, visibility, &target)
+            .await?;
+
+        let mut unused = released.into_iter().collect::<Vec<_>>();
+        if key != target {
+            // Identical content already has this visibility.
+            unused.push(target);
+        }
+        for key in unused {
+            if let Err(err) = self.store.delete_object(&key).await {
+                tracing::warn!("Failed to delete unused object {}: {}", key, err);
+            }
+        }
+
+        if metadata.has_previews {
+            for name in [THUMBNAIL_NAME, PREVIEW_NAME] {
+                self.store
+                    .move_object(
+                        &derived_key(&metadata.id, name, metadata.visibility),
+                        &derived_key(&metadata.id, name, visibility),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Download link for the object `key` of a file with `visibility`.
+    async fn download_url(&self, key: &str, visibility: FileVisibility) -> AppResult<String> {
+        match visibility {
+            FileVisibility::Public => Ok(self.cdn_url(key)),
+            FileVisibility::Private => {
+                let expires_in = Duration::from_secs(self.urls.private_url_expires_secs);
+                self.store.presign_get(key, expires_in).await
+            }
+        }
+    }
+
+    /// Fills in the download links of the file and its renditions.
+    async fn with_urls(&self, mut metadata: FileMetadata) -> AppResult<FileMetadata> {
+        metadata.url = Some(
+            self.download_url(&metadata.key, metadata.visibility)
+                .await?,
+        );
+        if metadata.has_previews {
+            let rendition = |name| derived_key(&metadata.id, name, metadata.visibility);
+            metadata.thumbnail_url = Some(
+                self.download_url(&rendition(THUMBNAIL_NAME), metadata.visibility)
+                    .await?,
+            );
+            metadata.preview_url = Some(
+                self.download_url(&rendition(PREVIEW_NAME), metadata.visibility)
+                    .await?,
+            );
+        }
+        Ok(metadata)
+    }
+
+    /// Moves the assembled object out of the public prefix and keeps the
+    /// session around for inspection. Returns the error to report.
+    async fn quarantine(
+        &self,
+        upload: &FileUploadSession,
+        code: &'static str,
+        reason: String,
+    ) -> AppResult<anyhow::Error> {
+        let quarantine_key = format!("{}{}", QUARANTINE_PREFIX, upload.key);
+        self.store.move_object(&upload.key, &quarantine_key).await?;
+        self.repo
+            .quarantine_upload(&upload.upload_id, &quarantine_key, &reason)
+            .await?;
+
+        tracing::warn!("Quarantined upload {}: {}", upload.upload_id, reason);
+        Ok(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, code, reason).into())
+    }
+
+    /// Checks the object assembled for `upload` against the declared size
+    /// and checksum, then records it. Returns the error code and reason when
+    /// the content is rejected.
+    async fn verify_assembled(
+        &self,
+        upload: &FileUploadSession,
+        metadata: FileMetadata,
+        sha256: Option<String>,
+    ) -> AppResult<Result<FileMetadata, (&'static str, String)>> {
+        let stored = self.store.head_object(&upload.key).await?;
+        if stored.size != upload.size {
+            return Ok(Err((
+                "size_mismatch",
+                format!(
+                    "Stored object is {} bytes, expected {}",
+                    stored.size, upload.size
+                ),
+            )));
+        }
+
+        if let Some(expected) = &sha256 {
+            let actual = self.store.object_sha256(&upload.key).await?;
+            if &actual != expected {
+                return Ok(Err((
+                    "checksum_mismatch",
+                    format!(
+                        "Stored object has SHA-256 {}, expected {}",
+                        actual, expected
+                    ),
+                )));
+            }
+        }
+
+        // A checksum the client sent has been verified above, so it is the hash.
+        self.record_file(metadata, sha256).await
+    }
+
+    /// Takes an assembled object that could not be verified out of reach:
+    /// its renditions are deleted and the object quarantined with `err` as
+    /// the reason, or deleted too if even that fails.
+    async fn discard_assembled(&self, upload: &FileUploadSession, err: &anyhow::Error) {
+        for name in [THUMBNAIL_NAME, PREVIEW_NAME] {
+            let key = derived_key(&upload.file_id, name, upload.visibility);
+            if let Err(err) = self.store.delete_object(&key).await {
+                tracing::warn!("Failed to delete rendition {}: {}", key, err);
+            }
+        }
+
+        let reason = format!("Verification failed: {}", err);
+        if let Err(quarantine_err) = self.quarantine(upload, "verification_failed", reason).await {
+            tracing::warn!(
+                "Failed to quarantine upload {}, deleting it: {}",
+                upload.upload_id,
+                quarantine_err
+            );
+            if let Err(err) = self.store.delete_object(&upload.key).await {
+                tracing::warn!("Failed to delete object {}: {}", upload.key, err);
+            }
+        }
+    }
+
+    /// Checks the stored bytes against the declared type and the policy of
+    /// the file's purpose, returning the error code and reason of the first violation.
+    async fn content_violation(
+        &self,
+        file: &FileMetadata,
+    ) -> AppResult<Option<(&'static str, String)>> {
+        let declared = content_type_essence(&file.content_type);
+        let head = if file.size == 0 {
+            Vec::new()
+        } else {
+            self.store.read_prefix(&file.key, SNIFF_LEN).await?
+        };
+
+        if !content_type_matches(&declared, &head) {
+            let sniffed = sniff_content_type(&head).unwrap_or("unrecognised data");
+            return Ok(Some((
+                "content_type_mismatch",
+                format!("Declared as {} but the content is {}", declared, sniffed),
+            )));
+        }
+
+        if file.purpose.policy().minecraft_texture {
+            let Some((width, height)) = png_dimensions(&head) else {
+                return Ok(Some((
+                    "invalid_image",
+                    "Could not read the image dimensions".to_string(),
+                )));
+            };
+            if let Err(reason) = texture_frames(width, height) {
+                return Ok(Some(("invalid_texture", reason)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Decodes an image upload and stores its thumbnail and preview. Returns
+    /// `Ok(None)` for uploads that are not images we process, and the decode
+    /// error when the stored bytes are not a readable image.
+    async fn process_image(
+        &self,
+        file: &FileMetadata,
+    ) -> AppResult<Result<Option<ProcessedImage>, String>> {
+        let declared = content_type_essence(&file.content_type);
+        if !PROCESSED_IMAGE_TYPES.contains(&declared.as_str()) || file.size > MAX_PROCESSED_SIZE {
+            return Ok(Ok(None));
+        }
+
+        let data = self.store.get_object(&file.key).await?;
+        let texture = file.purpose.policy().minecraft_texture;
+        let processed =
+            match tokio::task::spawn_blocking(move || images::process_image(&data, texture)).await?
+            {
+                Ok(processed) => processed,
+                Err(err) => return Ok(Err(err.to_string())),
+            };
+
+        for (name, data) in [
+            (THUMBNAIL_NAME, &processed.thumbnail),
+            (PREVIEW_NAME, &processed.preview),
+        ] {
+            self.store
+                .put_object(
+                    &derived_key(&file.id, name, file.visibility),
+                    data,
+                    "image/png",
+                )
+                .await?;
+        }
+        Ok(Ok(Some(processed)))
+    }
+
+    /// Checks the stored object, renders previews and records the file,
+    /// sharing the object of identical content if there is one. `sha256` is
+    /// the object's hash when already known. Returns the error code and
+    /// reason when the content is rejected.
+    async fn record_file(
+        &self,
+        mut metadata: FileMetadata,
+        sha256: Option<String>,
+    ) -> AppResult<Result<FileMetadata, (&'static str, String)>> {
+        if let Some(violation) = self.content_violation(&metadata).await? {
+            return Ok(Err(violation));
+        }
+        let image = match self.process_image(&metadata).await? {
+            Ok(image) => image,
+            Err(reason) => {
+                return Ok(Err((
+                    "invalid_image",
+                    format!("Could not decode the image: {}", reason),
+                )));
+            }
+        };
+
+        metadata.sha256 = Some(match sha256 {
+            Some(sha256) => sha256,
+            None => self.store.object_sha256(&metadata.key).await?,
+        });
+        if let Some(image) = &image {
+            metadata.width = Some(image.width as i32);
+            metadata.height = Some(image.height as i32);
+            metadata.frames = Some(image.frames as i32);
+            metadata.has_previews = true;
+        }
+
+        let key = self.repo.insert_metadata(&metadata).await?;
+        if key != metadata.key {
+            // Identical content is already stored; drop this copy.
+            if let Err(err) = self.store.delete_object(&metadata.key).await {
+                tracing::warn!(
+                    "Failed to delete duplicate object {}: {}",
+                    metadata.key,
+                    err
+                );
+            }
+            metadata.key = key;
+        }
+        Ok(Ok(metadata))
     }
 }
 
@@ -27,8 +399,13 @@ pub trait FileUsecase: Send + Sync {
         filename: &str,
         content_type: &str,
         size: i64,
+        purpose: UploadPurpose,
+        visibility: Option<FileVisibility>,
     ) -> AppResult<FileUploadSession>;
 
+    /// Public CDN link of the object `key`.
+    fn cdn_url(&self, key: &str) -> String;
+
     async fn get_upload(
         &self,
         upload_id: &str,
@@ -44,15 +421,63 @@ pub trait FileUsecase: Send + Sync {
         uploader_username: Option<String>,
         uploader_global_name: Option<String>,
         uploader_avatar_url: Option<String>,
+        sha256: Option<String>,
+    ) -> AppResult<FileMetadata>;
+
+    /// Stores a file sent in one request, streaming `body` to storage. Files
+    /// are limited to [`DIRECT_UPLOAD_MAX_SIZE`] on top of their policy.
+    async fn upload_file(
+        &self,
+        file: NewFile,
+        body: BoxStream<'_, io::Result<Bytes>>,
     ) -> AppResult<FileMetadata>;
 
     async fn abort_upload(&self, upload_id: &str) -> AppResult<()>;
 
     async fn get_file_by_id(&self, file_id: &str) -> AppResult<FileMetadata>;
 
-    async fn find_all_files(&self, user_id: Option<String>) -> AppResult<Vec<FileMetadata>>;
+    async fn find_files(&self, filter: FileFilter) -> AppResult<Paginated<FileMetadata>>;
 
+    /// Refuses to delete a file that is still in use.
     async fn delete_file(&self, file_id: &str) -> AppResult<()>;
+
+    /// Changing the visibility moves the file and its renditions to the
+    /// matching prefix.
+    async fn update_file(&self, file_id: &str, patch: FilePatch) -> AppResult<FileMetadata>;
+
+    async fn list_folders(&self, user_id: Option<&str>) -> AppResult<Vec<FileFolder>>;
+
+    /// Where the file is used.
+    async fn list_usages(&self, file_id: &str) -> AppResult<Vec<FileUsage>>;
+
+    async fn add_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<Vec<FileUsage>>;
+
+    async fn remove_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<()>;
+
+    /// Aborts upload sessions idle for longer than `ttl`, along with multipart
+    /// uploads left in the bucket without a session, and records the run.
+    async fn reap_stale_uploads(&self, ttl: Duration) -> AppResult<UploadReaperRun>;
+
+    async fn list_reaper_runs(&self, limit: i64) -> AppResult<Vec<UploadReaperRun>>;
+
+    /// Moves the files a migration queued for another visibility. Files that
+    /// fail to move stay queued for the next call. Returns how many moved.
+    async fn apply_pending_visibility_changes(&self) -> AppResult<usize>;
+
+    /// Usage across all users, with the `top_users` using the most storage.
+    async fn storage_usage(&self, top_users: i64) -> AppResult<StorageUsageReport>;
+
+    async fn user_storage_usage(&self, user_id: &str) -> AppResult<UserUsageReport>;
 }
 
 #[async_trait]
@@ -63,18 +488,33 @@ impl<R: FileRepository + Send + Sync> FileUsecase for FileUsecaseImpl<R> {
         filename: &str,
         content_type: &str,
         size: i64,
+        purpose: UploadPurpose,
+        visibility: Option<FileVisibility>,
     ) -> AppResult<FileUploadSession> {
         const DEFAULT_PART_SIZE: i64 = 16 * 1024 * 1024;
 
+        let policy = purpose.policy();
+        if size < 0 {
+            return Err(ApiError::bad_request("invalid_size", "size must not be negative").into());
+        }
+        if size > policy.max_size {
+            return Err(file_too_large(purpose, policy.max_size));
+        }
+        ensure_type_allowed(purpose, content_type)?;
+        self.ensure_quota(user_id, size).await?;
+
         let filename = sanitize_filename(filename);
-        let file_id = Uuid::new_v4().to_string();
-        let key = format!("files/{}/{}/{}", user_id, file_id, filename);
+        let file_id = self.ids.generate_id(EntityType::File);
+        let visibility = visibility.unwrap_or_else(|| purpose.default_visibility());
+        let key = visibility.object_key(&format!(
+            "{}{}/{}/{}",
+            FILES_PREFIX, user_id, file_id, filename
+        ));
 
-        let bucket = make_bucket().await?;
-        let initiated = bucket.initiate_multipart_upload(&key, content_type).await?;
+        let upload_id = self.store.create_multipart(&key, content_type).await?;
 
         let upload = FileUploadSession {
-            upload_id: initiated.upload_id,
+            upload_id,
             file_id,
             user_id: user_id.to_string(),
             key,
@@ -82,12 +522,20 @@ impl<R: FileRepository + Send + Sync> FileUsecase for FileUsecaseImpl<R> {
             content_type: content_type.to_string(),
             size,
             part_size: DEFAULT_PART_SIZE,
+            purpose,
+            visibility,
+            status: UPLOAD_IN_PROGRESS.to_string(),
+            quarantine_reason: None,
         };
 
         self.repo.create_upload(&upload).await?;
         Ok(upload)
     }
 
+    fn cdn_url(&self, key: &str) -> String {
+        format!("{}/{}", self.urls.cdn_base_url, key)
+    }
+
     async fn get_upload(
         &self,
         upload_id: &str,
@@ -98,20 +546,10 @@ impl<R: FileRepository + Send + Sync> FileUsecase for FileUsecaseImpl<R> {
     }
 
     async fn get_part_upload_url(&self, upload_id: &str, part_number: i32) -> AppResult<String> {
-        const PRESIGN_EXPIRES_SECS: u32 = 60 * 10;
-
         let upload = self.repo.find_upload(upload_id).await?;
-        let bucket = make_bucket().await?;
-
-        let mut queries = HashMap::new();
-        queries.insert("partNumber".to_string(), part_number.to_string());
-        queries.insert("uploadId".to_string(), upload.upload_id.clone());
-
-        let url = bucket
-            .presign_put(&upload.key, PRESIGN_EXPIRES_SECS, None, Some(queries))
-            .await?;
-
-        Ok(url)
+        self.store
+            .presign_part(&upload.key, &upload.upload_id, part_number, PRESIGN_EXPIRES)
+            .await
     }
 
     async fn register_part(&self, upload_id: &str, part_number: i32, etag: &str) -> AppResult<()> {
@@ -126,30 +564,58 @@ impl<R: FileRepository + Send + Sync> FileUsecase for FileUsecaseImpl<R> {
         uploader_username: Option<String>,
         uploader_global_name: Option<String>,
         uploader_avatar_url: Option<String>,
+        sha256: Option<String>,
     ) -> AppResult<FileMetadata> {
         let (upload, mut parts) = self.get_upload(upload_id).await?;
+        if upload.status != UPLOAD_IN_PROGRESS {
+            return Err(ApiError::conflict(
+                "upload_not_in_progress",
+                format!("Upload '{}' is {}", upload_id, upload.status),
+            )
+            .into());
+        }
+
+        let sha256 = sha256.map(|s| s.to_ascii_lowercase());
+        if let Some(sha256) = &sha256
+            && (sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            return Err(ApiError::bad_request(
+                "invalid_sha256",
+                "sha256 must be 64 hexadecimal characters",
+            )
+            .into());
+        }
 
         parts.sort_by_key(|p| p.part_number);
-        let parts: Vec<s3::serde_types::Part> = parts
+        let expected_parts = expected_part_count(upload.size, upload.part_size);
+        let contiguous = parts
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.part_number as i64 == i as i64 + 1);
+        if parts.len() as i64 != expected_parts || !contiguous {
+            let registered: Vec<String> = parts.iter().map(|p| p.part_number.to_string()).collect();
+            return Err(ApiError::bad_request(
+                "parts_incomplete",
+                format!(
+                    "Expected parts 1 to {} for {} bytes, registered [{}]",
+                    expected_parts,
+                    upload.size,
+                    registered.join(", ")
+                ),
+            )
+            .into());
+        }
+        let parts: Vec<UploadedPart> = parts
             .into_iter()
-            .map(|p| s3::serde_types::Part {
-                part_number: p.part_number.max(1) as u32,
+            .map(|p| UploadedPart {
+                part_number: p.part_number,
                 etag: p.etag,
             })
             .collect();
 
-        let bucket = make_bucket().await?;
-        let response = bucket
-            .complete_multipart_upload(&upload.key, &upload.upload_id, parts)
+        self.store
+            .complete_multipart(&upload.key, &upload.upload_id, &parts)
             .await?;
-        let code = response.status_code();
-
-        if code != 200 {
-            return Err(anyhow::anyhow!(
-                "R2 multipart completion failed (status code {})",
-                code
-            ));
-        }
 
         let metadata = FileMetadata {
             id: upload.file_id.clone(),
@@ -157,69 +623,476 @@ impl<R: FileRepository + Send + Sync> FileUsecase for FileUsecaseImpl<R> {
             filename: upload.filename.clone(),
             content_type: upload.content_type.clone(),
             size: upload.size,
+            key: upload.key.clone(),
+            sha256: None,
+            purpose: upload.purpose,
+            visibility: upload.visibility,
+            folder: String::new(),
+            tags: Vec::new(),
             uploaded_at: Utc::now().naive_utc(),
-            url: Some(format!(
-                "https://cdn.alcaris.net/files/{}/{}/{}",
-                upload.user_id, upload.file_id, upload.filename
-            )),
+            url: None,
+            width: None,
+            height: None,
+            frames: None,
+            has_previews: false,
+            thumbnail_url: None,
+            preview_url: None,
             uploader_username,
             uploader_global_name,
             uploader_avatar_url,
         };
 
-        self.repo.insert_metadata(&metadata).await?;
+        // The parts are used up once assembled, so the session cannot be
+        // completed again: whatever goes wrong from here, the unverified
+        // object must leave the prefix it would be served from.
+        let metadata = match self.verify_assembled(&upload, metadata, sha256).await {
+            Ok(Ok(metadata)) => metadata,
+            Ok(Err((code, reason))) => return Err(self.quarantine(&upload, code, reason).await?),
+            Err(err) => {
+                self.discard_assembled(&upload, &err).await;
+                return Err(err);
+            }
+        };
         self.repo.delete_upload(upload_id).await?;
-        Ok(metadata)
+        self.with_urls(metadata).await
+    }
+
+    async fn upload_file(
+        &self,
+        file: NewFile,
+        body: BoxStream<'_, io::Result<Bytes>>,
+    ) -> AppResult<FileMetadata> {
+        ensure_type_allowed(file.purpose, &file.content_type)?;
+        let folder = match &file.folder {
+            Some(folder) => normalize_folder(folder)
+                .map_err(|reason| ApiError::bad_request("invalid_folder", reason))?,
+            None => String::new(),
+        };
+        let tags = normalize_tags(&file.tags)
+            .map_err(|reason| ApiError::bad_request("invalid_tags", reason))?;
+
+        let filename = sanitize_filename(&file.filename);
+        let file_id = self.ids.generate_id(EntityType::File);
+        let visibility = file
+            .visibility
+            .unwrap_or_else(|| file.purpose.default_visibility());
+        let key = visibility.object_key(&format!(
+            "{}{}/{}/{}",
+            FILES_PREFIX, file.user_id, file_id, filename
+        ));
+
+        // The size is only known once the body has been read, so the stream
+        // is cut off as soon as it outgrows the quota left.
+        let quota_left = self.ensure_quota(&file.user_id, 0).await?;
+        let max_size = file.purpose.policy().max_size.min(DIRECT_UPLOAD_MAX_SIZE);
+        let mut meter = BodyMeter::new(quota_left.map_or(max_size, |left| left.min(max_size)));
+        let stored = {
+            let mut reader = StreamReader::new(body.map(|chunk| meter.observe(chunk)));
+            self.store
+                .put_stream(&key, &mut reader, &file.content_type)
+                .await
+        };
+        if meter.exceeded {
+            if meter.size > max_size {
+                return Err(file_too_large(file.purpose, max_size));
+            }
+            return Err(quota_exceeded(format!(
+                "Upload is larger than the {} bytes of storage left",
+                quota_left.unwrap_or_default()
+            )));
+        }
+        stored?;
+
+        let metadata = FileMetadata {
+            id: file_id,
+            user_id: file.user_id,
+            filename,
+            content_type: file.content_type,
+            size: meter.size,
+            key,
+            sha256: None,
+            purpose: file.purpose,
+            visibility,
+            folder,
+            tags,
+            uploaded_at: Utc::now().naive_utc(),
+            url: None,
+            width: None,
+            height: None,
+            frames: None,
+            has_previews: false,
+            thumbnail_url: None,
+            preview_url: None,
+            uploader_username: file.uploader_username,
+            uploader_global_name: file.uploader_global_name,
+            uploader_avatar_url: file.uploader_avatar_url,
+        };
+
+        let key = metadata.key.clone();
+        match self.record_file(metadata, Some(meter.sha256())).await? {
+            Ok(metadata) => self.with_urls(metadata).await,
+            Err((code, reason)) => {
+                // Nothing refers to the object yet, so there is no session to
+                // quarantine; the client gets the reason straight away.
+                self.store.delete_object(&key).await?;
+                Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, code, reason).into())
+            }
+        }
     }
 
     async fn abort_upload(&self, upload_id: &str) -> AppResult<()> {
         let upload = self.repo.find_upload(upload_id).await?;
-        let bucket = make_bucket().await?;
-        bucket.abort_upload(&upload.key, &upload.upload_id).await?;
+        if upload.status == UPLOAD_QUARANTINED {
+            // Already assembled, so there is no multipart upload left to abort.
+            self.store.delete_object(&upload.key).await?;
+        } else {
+            self.store
+                .abort_multipart(&upload.key, &upload.upload_id)
+                .await?;
+        }
         self.repo.delete_upload(upload_id).await?;
         Ok(())
     }
 
     async fn get_file_by_id(&self, file_id: &str) -> AppResult<FileMetadata> {
-        self.repo.find_metadata(file_id).await
+        let metadata = self.ensure_file(file_id).await?;
+        self.with_urls(metadata).await
     }
 
-    async fn find_all_files(&self, user_id: Option<String>) -> AppResult<Vec<FileMetadata>> {
-        self.repo.list_metadata(user_id).await
+    async fn find_files(&self, mut filter: FileFilter) -> AppResult<Paginated<FileMetadata>> {
+        if let (Some(after), Some(before)) = (filter.uploaded_after, filter.uploaded_before)
+            && after >= before
+        {
+            return Err(ApiError::bad_request(
+                "invalid_range",
+                "uploaded_after must be earlier than uploaded_before",
+            )
+            .into());
+        }
+        if let Some(folder) = &filter.folder {
+            filter.folder = Some(
+                normalize_folder(folder)
+                    .map_err(|reason| ApiError::bad_request("invalid_folder", reason))?,
+            );
+        }
+
+        let (page, per_page) = (filter.page(), filter.per_page());
+        let (rows, total) = self
+            .repo
+            .fetch_page(&filter, per_page, (page - 1) * per_page)
+            .await?;
+
+        let mut files = Vec::with_capacity(rows.len());
+        for metadata in rows {
+            files.push(self.with_urls(metadata).await?);
+        }
+        Ok(Paginated::new(files, page, per_page, total))
     }
 
     async fn delete_file(&self, file_id: &str) -> AppResult<()> {
-        let metadata = self.repo.find_metadata(file_id).await?;
-        let key = format!(
-            "files/{}/{}/{}",
-            metadata.user_id, metadata.id, metadata.filename
-        );
+        let metadata = self.ensure_file(file_id).await?;
+
+        let usages = self.repo.list_usages(file_id).await?;
+        if !usages.is_empty() {
+            let users: Vec<String> = usages
+                .iter()
+                .map(|u| format!("{} {}", u.kind, u.entity_id))
+                .collect();
+            return Err(ApiError::conflict(
+                "file_in_use",
+                format!("File is still used by {}", users.join(", ")),
+            )
+            .into());
+        }
+
+        // Other files may still share the object; the repository only hands
+        // back its key once the last of them is gone.
+        if let Some(key) = self.repo.delete_metadata(file_id).await? {
+            self.store.delete_object(&key).await?;
+        }
+        if metadata.has_previews {
+            for name in [THUMBNAIL_NAME, PREVIEW_NAME] {
+                self.store
+                    .delete_object(&derived_key(&metadata.id, name, metadata.visibility))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_file(&self, file_id: &str, patch: FilePatch) -> AppResult<FileMetadata> {
+        let metadata = self.ensure_file(file_id).await?;
+
+        let folder = match &patch.folder {
+            Some(folder) => normalize_folder(folder)
+                .map_err(|reason| ApiError::bad_request("invalid_folder", reason))?,
+            None => metadata.folder.clone(),
+        };
+        let tags = match &patch.tags {
+            Some(tags) => normalize_tags(tags)
+                .map_err(|reason| ApiError::bad_request("invalid_tags", reason))?,
+            None => metadata.tags.clone(),
+        };
+
+        if let Some(visibility) = patch.visibility {
+            self.change_visibility(&metadata, visibility).await?;
+        }
+        if folder != metadata.folder || tags != metadata.tags {
+            self.repo
+                .update_organization(file_id, &folder, &tags)
+                .await?;
+        }
+
+        self.get_file_by_id(file_id).await
+    }
+
+    async fn list_folders(&self, user_id: Option<&str>) -> AppResult<Vec<FileFolder>> {
+        self.repo.list_folders(user_id).await
+    }
+
+    async fn list_usages(&self, file_id: &str) -> AppResult<Vec<FileUsage>> {
+        self.ensure_file(file_id).await?;
+        self.repo.list_usages(file_id).await
+    }
+
+    async fn add_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<Vec<FileUsage>> {
+        self.ensure_file(file_id).await?;
+        let entity_id = self.resolve_entity(kind, entity_id).await?;
+        self.repo
+            .insert_reference(file_id, kind, &entity_id)
+            .await?;
+        self.repo.list_usages(file_id).await
+    }
 
-        let bucket_name = env::var("R2_BUCKET_NAME")?;
-        let endpoint = env::var("R2_ENDPOINT")?;
-        let access_key = env::var("R2_ACCESS_KEY_ID")?;
-        let secret_key = env::var("R2_ACCESS_KEY_SECRET")?;
+    async fn remove_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<()> {
+        // Items that were deleted can no longer be looked up by slug.
+        let entity_id = match kind {
+            FileUsageKind::Item => self
+                .repo
+                .find_item_id(entity_id)
+                .await?
+                .unwrap_or_else(|| entity_id.to_string()),
+            _ => entity_id.to_string(),
+        };
 
-        let region = Region::Custom {
-            region: "auto".into(),
-            endpoint,
+        if !self
+            .repo
+            .delete_reference(file_id, kind, &entity_id)
+            .await?
+        {
+            return Err(ApiError::not_found(
+                "reference_not_found",
+                format!("File '{}' is not used by {} {}", file_id, kind, entity_id),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn reap_stale_uploads(&self, ttl: Duration) -> AppResult<UploadReaperRun> {
+        let now = Utc::now();
+        let cutoff = now - chrono::Duration::from_std(ttl)?;
+        let mut run = UploadReaperRun {
+            ran_at: now.naive_utc(),
+            ttl_secs: ttl.as_secs() as i64,
+            ..Default::default()
         };
-        let credentials = Credentials::new(Some(&access_key), Some(&secret_key), None, None, None)?;
-        let bucket = Bucket::new(&bucket_name, region, credentials)?.with_path_style();
 
-        let response = bucket.delete_object(&key).await?;
-        let code = response.status_code();
-        if code != 204 && code != 200 {
-            return Err(anyhow::anyhow!(
-                "Failed to delete file from R2 (status code {})",
-                code
-            ));
+        for (upload, last_activity_at) in self.repo.find_stale_uploads(cutoff.naive_utc()).await? {
+            // A failed abort still drops the session: the bucket copy is then
+            // picked up as an orphan on a later run.
+            if let Err(err) = self
+                .store
+                .abort_multipart(&upload.key, &upload.upload_id)
+                .await
+            {
+                run.failures.push(format!("{}: {}", upload.upload_id, err));
+            }
+            self.repo.delete_upload(&upload.upload_id).await?;
+            run.expired.push(ReapedUpload {
+                upload_id: upload.upload_id,
+                key: upload.key,
+                file_id: Some(upload.file_id),
+                user_id: Some(upload.user_id),
+                last_activity_at: Some(last_activity_at),
+            });
+        }
+
+        let mut pending = self.store.list_multipart(FILES_PREFIX).await?;
+        pending.extend(
+            self.store
+                .list_multipart(&format!("{}{}", PRIVATE_PREFIX, FILES_PREFIX))
+                .await?,
+        );
+        let upload_ids: Vec<String> = pending.iter().map(|p| p.upload_id.clone()).collect();
+        let known: HashSet<String> = self
+            .repo
+            .existing_upload_ids(&upload_ids)
+            .await?
+            .into_iter()
+            .collect();
+
+        for pending in pending {
+            // Uploads without a start time may have been created moments ago.
+            let Some(initiated_at) = pending.initiated_at else {
+                continue;
+            };
+            if known.contains(&pending.upload_id) || initiated_at >= cutoff {
+                continue;
+            }
+
+            match self
+                .store
+                .abort_multipart(&pending.key, &pending.upload_id)
+                .await
+            {
+                Ok(()) => run.orphaned.push(ReapedUpload {
+                    upload_id: pending.upload_id,
+                    key: pending.key,
+                    file_id: None,
+                    user_id: None,
+                    last_activity_at: Some(initiated_at.naive_utc()),
+                }),
+                Err(err) => run.failures.push(format!("{}: {}", pending.upload_id, err)),
+            }
+        }
+
+        self.repo.insert_reaper_run(&run).await
+    }
+
+    async fn list_reaper_runs(&self, limit: i64) -> AppResult<Vec<UploadReaperRun>> {
+        self.repo.list_reaper_runs(limit).await
+    }
+
+    async fn apply_pending_visibility_changes(&self) -> AppResult<usize> {
+        let mut moved = 0;
+        for (file_id, visibility) in self.repo.pending_visibility_changes().await? {
+            let result = match self.repo.find_metadata(&file_id).await {
+                Ok(metadata) => self.change_visibility(&metadata, visibility).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => {
+                    self.repo.finish_visibility_change(&file_id).await?;
+                    moved += 1;
+                }
+                Err(err) => tracing::warn!(
+                    "Failed to make file {} {}: {}",
+                    file_id,
+                    visibility.as_str(),
+                    err
+                ),
+            }
+        }
+        Ok(moved)
+    }
+
+    async fn storage_usage(&self, top_users: i64) -> AppResult<StorageUsageReport> {
+        let usage = self.repo.storage_usage(None).await?;
+        Ok(StorageUsageReport {
+            quota_bytes: self.quota.total_bytes,
+            remaining_bytes: remaining_bytes(self.quota.total_bytes, usage.used_bytes),
+            per_user_quota_bytes: self.quota.per_user_bytes,
+            stored_bytes: self.repo.stored_bytes().await?,
+            by_purpose: self.repo.usage_by_purpose(None).await?,
+            top_users: self.repo.top_users(top_users).await?,
+            usage,
+        })
+    }
+
+    async fn user_storage_usage(&self, user_id: &str) -> AppResult<UserUsageReport> {
+        let usage = self.repo.storage_usage(Some(user_id)).await?;
+        Ok(UserUsageReport {
+            user_id: user_id.to_string(),
+            quota_bytes: self.quota.per_user_bytes,
+            remaining_bytes: remaining_bytes(self.quota.per_user_bytes, usage.used_bytes),
+            by_purpose: self.repo.usage_by_purpose(Some(user_id)).await?,
+            usage,
+        })
+    }
+}
+
+/// Running size and SHA-256 of a streamed upload. Chunks past `max_size`
+/// are refused, which aborts the write.
+struct BodyMeter {
+    max_size: i64,
+    size: i64,
+    hasher: Sha256,
+    exceeded: bool,
+}
+
+impl BodyMeter {
+    fn new(max_size: i64) -> Self {
+        Self {
+            max_size,
+            size: 0,
+            hasher: Sha256::new(),
+            exceeded: false,
+        }
+    }
+
+    fn observe(&mut self, chunk: io::Result<Bytes>) -> io::Result<Bytes> {
+        let chunk = chunk?;
+        self.size += chunk.len() as i64;
+        if self.size > self.max_size {
+            self.exceeded = true;
+            return Err(io::Error::other("upload exceeds the size limit"));
         }
+        self.hasher.update(&chunk);
+        Ok(chunk)
+    }
 
-        self.repo.delete_metadata(file_id).await
+    fn sha256(&self) -> String {
+        hex::encode(self.hasher.clone().finalize())
     }
 }
 
+fn quota_exceeded(message: String) -> anyhow::Error {
+    ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "quota_exceeded", message).into()
+}
+
+fn file_too_large(purpose: UploadPurpose, max_size: i64) -> anyhow::Error {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "file_too_large",
+        format!("{} uploads are limited to {} bytes", purpose, max_size),
+    )
+    .into()
+}
+
+fn ensure_type_allowed(purpose: UploadPurpose, content_type: &str) -> AppResult<()> {
+    let policy = purpose.policy();
+    if policy.allows_type(&content_type_essence(content_type)) {
+        return Ok(());
+    }
+    Err(ApiError::new(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "content_type_not_allowed",
+        format!(
+            "{} uploads accept {}",
+            purpose,
+            policy.allowed_types.unwrap_or_default().join(", ")
+        ),
+    )
+    .into())
+}
+
+/// Number of parts an upload of `size` bytes is split into. Even an empty
+/// upload sends one part.
+fn expected_part_count(size: i64, part_size: i64) -> i64 {
+    ((size + part_size - 1) / part_size).max(1)
+}
+
 fn sanitize_filename(filename: &str) -> String {
     let filename = filename.trim();
     if filename.is_empty() {
@@ -236,17 +1109,23 @@ fn sanitize_filename(filename: &str) -> String {
         .collect()
 }
 
-async fn make_bucket() -> AppResult<Box<Bucket>> {
-    let bucket_name = env::var("R2_BUCKET_NAME")?;
-    let endpoint = env::var("R2_ENDPOINT")?;
-    let access_key = env::var("R2_ACCESS_KEY_ID")?;
-    let secret_key = env::var("R2_ACCESS_KEY_SECRET")?;
+#[cfg(test)]
+mod tests {
+    use super::expected_part_count;
 
-    let region = Region::Custom {
-        region: "auto".into(),
-        endpoint,
-    };
-
-    let credentials = Credentials::new(Some(&access_key), Some(&secret_key), None, None, None)?;
-    Ok(Bucket::new(&bucket_name, region, credentials)?.with_path_style())
+    #[test]
+    fn counts_upload_parts() {
+        let cases = [
+            (0, 1),
+            (1, 1),
+            (99, 1),
+            (100, 1),
+            (101, 2),
+            (200, 2),
+            (201, 3),
+        ];
+        for (size, parts) in cases {
+            assert_eq!(expected_part_count(size, 100), parts, "size {}", size);
+        }
+    }
 }
@@ -0,0 +1,43 @@
+use infrastructure::repositorys::unique_violation;
+use shared::error::{ApiError, AppResult};
+use shared::is_valid_slug;
+
+/// Slug for a new resource: the explicit `slug` if given, otherwise the id the
+/// client supplied, which is no longer used as the id itself.
+pub(crate) fn slug_alias(client_id: &str, slug: Option<String>) -> AppResult<Option<String>> {
+    let slug = slug
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| Some(client_id.to_string()).filter(|s| !s.trim().is_empty()));
+
+    let Some(slug) = slug else {
+        return Ok(None);
+    };
+
+    let slug = slug.trim().to_string();
+    if !is_valid_slug(&slug) {
+        return Err(ApiError::bad_request(
+            "invalid_slug",
+            format!(
+                "'{}' is not a valid slug: use up to 64 lowercase letters, digits, '-' or '_', not only digits",
+                slug
+            ),
+        )
+        .into());
+    }
+
+    Ok(Some(slug))
+}
+
+pub(crate) fn slug_taken(slug: &str) -> anyhow::Error {
+    ApiError::conflict("slug_taken", format!("Slug '{}' is already in use", slug)).into()
+}
+
+/// `err` as [`slug_taken`] when a concurrent insert claimed `slug` first and
+/// tripped the unique `index`; the lookup before inserting cannot rule that
+/// out.
+pub(crate) fn slug_conflict(err: anyhow::Error, index: &str, slug: Option<&str>) -> anyhow::Error {
+    match (unique_violation(&err), slug) {
+        (Some(violated), Some(slug)) if violated == index => slug_taken(slug),
+        _ => err,
+    }
+}
@@ -1,27 +1,74 @@
 use async_trait::async_trait;
 use serde_json::Value;
+use std::sync::Arc;
 
-use domain::items::Item;
-use infrastructure::repositorys::item::ItemRepository;
-use shared::error::AppResult;
+use domain::items::{DEFAULT_ITEM_NAMESPACE, Item, ItemKeyAlias, parse_item_key};
+use infrastructure::repositorys::{is_not_found, item::ItemRepository, unique_violation};
+use shared::error::{ApiError, AppResult};
+use shared::{EntityType, IdGenerator};
+
+use crate::ids::{slug_alias, slug_conflict, slug_taken};
 
 pub struct ItemUsecaseImpl<R: ItemRepository + Send + Sync> {
     pub repo: R,
+    pub ids: Arc<IdGenerator>,
 }
 
 impl<R: ItemRepository + Send + Sync> ItemUsecaseImpl<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(repo: R, ids: Arc<IdGenerator>) -> Self {
+        Self { repo, ids }
+    }
+
+    async fn ensure_item(&self, id: &str) -> AppResult<Item> {
+        self.repo.find_by_id(id).await.map_err(|err| {
+            if is_not_found(&err) {
+                item_not_found(id)
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Fails unless `key` is free or already belongs to `item_id`, either as
+    /// its key or as one of its former keys.
+    async fn ensure_key_available(&self, key: &str, item_id: Option<&str>) -> AppResult<()> {
+        match self.repo.find_by_id(key).await {
+            Ok(holder) if Some(holder.id.as_str()) != item_id => Err(ApiError::conflict(
+                "key_taken",
+                format!("Key '{}' is already used by item '{}'", key, holder.id),
+            )
+            .into()),
+            _ => Ok(()),
+        }
     }
 }
 
+fn validate_key(raw: &str) -> AppResult<String> {
+    parse_item_key(raw).map_err(|message| ApiError::bad_request("invalid_key", message).into())
+}
+
+fn item_not_found(id: &str) -> anyhow::Error {
+    ApiError::not_found("item_not_found", format!("Item '{}' not found", id)).into()
+}
+
+/// Key claimed by a concurrent create or rename after it was checked.
+fn key_taken(key: &str) -> anyhow::Error {
+    ApiError::conflict("key_taken", format!("Key '{}' is already in use", key)).into()
+}
+
 #[async_trait]
 pub trait ItemUsecase: Send + Sync {
     async fn find_all(&self, category: Option<String>) -> AppResult<Vec<Item>>;
     async fn find_by_id(&self, id: &str) -> AppResult<Item>;
-    async fn create(&self, item: Item) -> AppResult<()>;
+    /// Assigns a generated id; a client-supplied id is kept as the slug. The
+    /// key defaults to the slug in the default namespace.
+    async fn create(&self, item: Item) -> AppResult<Item>;
+    /// A `key` in the patch is applied as a [`Self::rename`].
     async fn patch(&self, id: &str, patch: Value) -> AppResult<()>;
     async fn delete(&self, id: &str) -> AppResult<()>;
+    /// Gives the item a new key. The old one remains resolvable as an alias.
+    async fn rename(&self, id: &str, key: &str) -> AppResult<Item>;
+    async fn key_aliases(&self, id: &str) -> AppResult<Vec<ItemKeyAlias>>;
 }
 
 #[async_trait]
@@ -34,15 +81,84 @@ impl<R: ItemRepository + Send + Sync> ItemUsecase for ItemUsecaseImpl<R> {
         self.repo.find_by_id(id).await
     }
 
-    async fn create(&self, item: Item) -> AppResult<()> {
-        self.repo.insert(item).await
+    async fn create(&self, mut item: Item) -> AppResult<Item> {
+        item.slug = slug_alias(&item.id, item.slug.take())?;
+        if let Some(slug) = &item.slug
+            && self.repo.find_by_id(slug).await.is_ok()
+        {
+            return Err(slug_taken(slug));
+        }
+
+        let key = match (item.key.take(), &item.slug) {
+            (Some(key), _) => validate_key(&key)?,
+            (None, Some(slug)) => validate_key(&format!("{}:{}", DEFAULT_ITEM_NAMESPACE, slug))?,
+            (None, None) => {
+                return Err(ApiError::bad_request(
+                    "missing_key",
+                    "Items need a namespaced key or a slug to derive it from",
+                )
+                .into());
+            }
+        };
+        self.ensure_key_available(&key, None).await?;
+        item.key = Some(key.clone());
+
+        item.id = self.ids.generate_id(EntityType::Item);
+        self.repo.insert(item.clone()).await.map_err(|err| {
+            if unique_violation(&err) == Some("idx_items_key") {
+                return key_taken(&key);
+            }
+            slug_conflict(err, "idx_items_slug", item.slug.as_deref())
+        })?;
+        Ok(item)
     }
 
-    async fn patch(&self, id: &str, patch: Value) -> AppResult<()> {
-        self.repo.patch(id, patch).await
+    async fn patch(&self, id: &str, mut patch: Value) -> AppResult<()> {
+        // Resolved up front: `id` may be a key that a rename below retires.
+        let item = self.ensure_item(id).await?;
+        if let Some(fields) = patch.as_object_mut()
+            && let Some(key) = fields.remove("key")
+        {
+            let Some(key) = key.as_str() else {
+                return Err(ApiError::bad_request("invalid_key", "key must be a string").into());
+            };
+            self.rename(&item.id, key).await?;
+            if fields.is_empty() {
+                return Ok(());
+            }
+        }
+
+        if !self.repo.patch(&item.id, patch).await? {
+            return Err(item_not_found(id));
+        }
+        Ok(())
     }
 
     async fn delete(&self, id: &str) -> AppResult<()> {
-        self.repo.delete(id).await
+        let item = self.ensure_item(id).await?;
+        if !self.repo.delete(&item.id).await? {
+            return Err(item_not_found(id));
+        }
+        Ok(())
+    }
+
+    async fn rename(&self, id: &str, key: &str) -> AppResult<Item> {
+        let item = self.ensure_item(id).await?;
+        let key = validate_key(key)?;
+        self.ensure_key_available(&key, Some(&item.id)).await?;
+
+        self.repo
+            .rename_key(&item.id, &key)
+            .await
+            .map_err(|err| match unique_violation(&err) {
+                Some("idx_items_key") => key_taken(&key),
+                _ => err,
+            })?;
+        self.repo.find_by_id(&item.id).await
+    }
+
+    async fn key_aliases(&self, id: &str) -> AppResult<Vec<ItemKeyAlias>> {
+        let item = self.ensure_item(id).await?;
+        self.repo.list_key_aliases(&item.id).await
     }
 }
@@ -1,4 +1,6 @@
+pub mod alerts;
 pub mod files;
+mod ids;
 pub mod items;
 pub mod recipes;
 pub mod status;
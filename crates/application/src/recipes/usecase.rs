@@ -1,17 +1,22 @@
 use async_trait::async_trait;
 use serde_json::Value;
+use std::sync::Arc;
 
 use domain::recipes::Recipe;
 use infrastructure::repositorys::recipe::RecipeRepository;
 use shared::error::AppResult;
+use shared::{EntityType, IdGenerator};
+
+use crate::ids::{slug_alias, slug_conflict, slug_taken};
 
 pub struct RecipeUsecaseImpl<R: RecipeRepository + Send + Sync> {
     pub repo: R,
+    pub ids: Arc<IdGenerator>,
 }
 
 impl<R: RecipeRepository + Send + Sync> RecipeUsecaseImpl<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(repo: R, ids: Arc<IdGenerator>) -> Self {
+        Self { repo, ids }
     }
 }
 
@@ -19,7 +24,8 @@ impl<R: RecipeRepository + Send + Sync> RecipeUsecaseImpl<R> {
 pub trait RecipeUsecase: Send + Sync {
     async fn find_all(&self, category: Option<String>) -> AppResult<Vec<Recipe>>;
     async fn find_by_id(&self, id: &str) -> AppResult<Recipe>;
-    async fn create(&self, recipe: Recipe) -> AppResult<()>;
+    /// Assigns a generated id; a client-supplied id is kept as the slug.
+    async fn create(&self, recipe: Recipe) -> AppResult<Recipe>;
     async fn patch(&self, id: &str, patch: Value) -> AppResult<()>;
     async fn delete(&self, id: &str) -> AppResult<()>;
 }
@@ -34,8 +40,20 @@ impl<R: RecipeRepository + Send + Sync> RecipeUsecase for RecipeUsecaseImpl<R> {
         self.repo.find_by_id(id).await
     }
 
-    async fn create(&self, recipe: Recipe) -> AppResult<()> {
-        self.repo.insert(recipe).await
+    async fn create(&self, mut recipe: Recipe) -> AppResult<Recipe> {
+        recipe.slug = slug_alias(&recipe.id, recipe.slug.take())?;
+        if let Some(slug) = &recipe.slug
+            && self.repo.find_by_id(slug).await.is_ok()
+        {
+            return Err(slug_taken(slug));
+        }
+
+        recipe.id = self.ids.generate_id(EntityType::Recipe);
+        self.repo
+            .insert(recipe.clone())
+            .await
+            .map_err(|err| slug_conflict(err, "idx_recipes_slug", recipe.slug.as_deref()))?;
+        Ok(recipe)
     }
 
     async fn patch(&self, id: &str, patch: Value) -> AppResult<()> {
@@ -1,15 +1,64 @@
 use async_trait::async_trait;
-use domain::status::{StatusResponse, StatusSummary};
-use infrastructure::repositorys::status::StatusRepository;
+use chrono::Utc;
+use domain::status::{
+    HistoryResolution, NetworkHistory, NetworkServer, NetworkStatus, Players, StatusHistory,
+    StatusIncident, StatusResponse, StatusSummary, UptimeSummary, UptimeWindow,
+};
+use infrastructure::{
+    repositorys::{incident::IncidentRepository, status::StatusRepository},
+    status_watcher::ServerConfig,
+};
 use shared::error::AppResult;
 
-pub struct StatusUsecaseImpl<R: StatusRepository + Send + Sync + 'static> {
+const AUTO_RAW_MAX_SPAN_SECS: i64 = 2 * 24 * 60 * 60;
+const AUTO_HOUR_MAX_SPAN_SECS: i64 = 62 * 24 * 60 * 60;
+const UPTIME_WINDOWS: [(&str, i64); 4] = [
+    ("24h", 24 * 60 * 60),
+    ("7d", 7 * 24 * 60 * 60),
+    ("30d", 30 * 24 * 60 * 60),
+    ("90d", 90 * 24 * 60 * 60),
+];
+
+pub struct StatusUsecaseImpl<R, I>
+where
+    R: StatusRepository + Send + Sync + 'static,
+    I: IncidentRepository + Send + Sync + 'static,
+{
     pub repo: R,
+    pub incidents: I,
+    pub config: ServerConfig,
 }
 
-impl<R: StatusRepository + Send + Sync + 'static> StatusUsecaseImpl<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+impl<R, I> StatusUsecaseImpl<R, I>
+where
+    R: StatusRepository + Send + Sync + 'static,
+    I: IncidentRepository + Send + Sync + 'static,
+{
+    pub fn new(repo: R, incidents: I, config: ServerConfig) -> Self {
+        Self {
+            repo,
+            incidents,
+            config,
+        }
+    }
+
+    fn server_ids(&self) -> Vec<String> {
+        self.config.servers.iter().map(|s| s.id.clone()).collect()
+    }
+
+    /// Picks the finest granularity that still has data for the whole range
+    /// without returning an unreasonable number of points.
+    fn auto_resolution(&self, from: i64, to: i64) -> HistoryResolution {
+        let span = to - from;
+        let raw_available_since = Utc::now().timestamp() - self.config.rollup.raw_retention_secs();
+
+        if span <= AUTO_RAW_MAX_SPAN_SECS && from >= raw_available_since {
+            HistoryResolution::Raw
+        } else if span <= AUTO_HOUR_MAX_SPAN_SECS {
+            HistoryResolution::Hour
+        } else {
+            HistoryResolution::Day
+        }
     }
 }
 
@@ -17,29 +66,56 @@ impl<R: StatusRepository + Send + Sync + 'static> StatusUsecaseImpl<R> {
 pub trait StatusUsecase: Send + Sync {
     async fn find_all(&self) -> AppResult<Vec<StatusSummary>>;
     async fn find_by_id(&self, id: &str) -> AppResult<StatusResponse>;
+    async fn history(
+        &self,
+        id: &str,
+        from: i64,
+        to: i64,
+        resolution: Option<HistoryResolution>,
+    ) -> AppResult<StatusHistory>;
+    async fn list_incidents(&self, id: &str, limit: i64) -> AppResult<Vec<StatusIncident>>;
+    async fn update_incident_notes(
+        &self,
+        incident_id: i64,
+        notes: Option<String>,
+        actor: &str,
+    ) -> AppResult<StatusIncident>;
+    async fn find_incident(&self, incident_id: i64) -> AppResult<StatusIncident>;
+    async fn uptime(&self, id: &str) -> AppResult<UptimeSummary>;
+    async fn network(&self) -> AppResult<NetworkStatus>;
+    async fn network_history(
+        &self,
+        from: i64,
+        to: i64,
+        bucket_secs: Option<i64>,
+    ) -> AppResult<NetworkHistory>;
 }
 
 #[async_trait]
-impl<R: StatusRepository + Send + Sync + 'static> StatusUsecase for StatusUsecaseImpl<R> {
+impl<R, I> StatusUsecase for StatusUsecaseImpl<R, I>
+where
+    R: StatusRepository + Send + Sync + 'static,
+    I: IncidentRepository + Send + Sync + 'static,
+{
     async fn find_all(&self) -> AppResult<Vec<StatusSummary>> {
         let records = self.repo.list_latest().await?;
+        let ids: Vec<String> = records.iter().map(|(id, _)| id.clone()).collect();
+        let mut history = self.repo.list_history(&ids).await?;
 
-        let mut summaries = Vec::new();
-
-        for (id, record) in records {
-            let history = self.repo.get_history(&id).await?;
-
-            summaries.push(StatusSummary {
-                id,
-                online: record.online,
-                latency: record.latency,
-                players: record.players,
-                timestamp: record.timestamp,
-                history,
-            });
-        }
-
-        Ok(summaries)
+        Ok(records
+            .into_iter()
+            .map(|(id, record)| {
+                let history = history.remove(&id).unwrap_or_default();
+                StatusSummary {
+                    id,
+                    online: record.online,
+                    latency: record.latency,
+                    players: record.players,
+                    timestamp: record.timestamp,
+                    history,
+                }
+            })
+            .collect())
     }
 
     async fn find_by_id(&self, id: &str) -> AppResult<StatusResponse> {
@@ -58,4 +134,158 @@ impl<R: StatusRepository + Send + Sync + 'static> StatusUsecase for StatusUsecas
             None => Err(anyhow::anyhow!("Server not found")),
         }
     }
+
+    async fn history(
+        &self,
+        id: &str,
+        from: i64,
+        to: i64,
+        resolution: Option<HistoryResolution>,
+    ) -> AppResult<StatusHistory> {
+        let resolution = resolution.unwrap_or_else(|| self.auto_resolution(from, to));
+        let points = self
+            .repo
+            .get_history_range(id, resolution, from, to)
+            .await?;
+
+        Ok(StatusHistory {
+            id: id.to_string(),
+            resolution,
+            from,
+            to,
+            points,
+        })
+    }
+
+    async fn list_incidents(&self, id: &str, limit: i64) -> AppResult<Vec<StatusIncident>> {
+        self.incidents.list(id, limit).await
+    }
+
+    async fn update_incident_notes(
+        &self,
+        incident_id: i64,
+        notes: Option<String>,
+        actor: &str,
+    ) -> AppResult<StatusIncident> {
+        let notes = notes
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+        self.incidents
+            .update_notes(incident_id, notes, actor)
+            .await?;
+        self.incidents.find_by_id(incident_id).await
+    }
+
+    async fn find_incident(&self, incident_id: i64) -> AppResult<StatusIncident> {
+        self.incidents.find_by_id(incident_id).await
+    }
+
+    async fn uptime(&self, id: &str) -> AppResult<UptimeSummary> {
+        let now = Utc::now().timestamp();
+        let latest = self.repo.get_latest(id).await?;
+        let open_incident = self.incidents.find_open(id).await?;
+
+        let mut windows = Vec::with_capacity(UPTIME_WINDOWS.len());
+        for (label, span) in UPTIME_WINDOWS {
+            let from = now - span;
+            let (samples, online_samples) = self.repo.sample_counts(id, from, now).await?;
+            let (incidents, downtime_secs) = self.incidents.summarize(id, from, now).await?;
+
+            windows.push(UptimeWindow {
+                window: label.to_string(),
+                from,
+                to: now,
+                samples,
+                uptime_pct: (samples > 0).then(|| online_samples as f64 * 100.0 / samples as f64),
+                incidents,
+                downtime_secs,
+            });
+        }
+
+        Ok(UptimeSummary {
+            id: id.to_string(),
+            online: latest.map(|record| record.online),
+            open_incident,
+            windows,
+        })
+    }
+
+    async fn network(&self) -> AppResult<NetworkStatus> {
+        let mut latest: std::collections::HashMap<_, _> =
+            self.repo.list_latest().await?.into_iter().collect();
+
+        let servers: Vec<NetworkServer> = self
+            .server_ids()
+            .into_iter()
+            .map(|id| match latest.remove(&id) {
+                Some(record) => NetworkServer {
+                    id,
+                    online: record.online,
+                    players: record.players,
+                    timestamp: Some(record.timestamp),
+                },
+                None => NetworkServer {
+                    id,
+                    online: false,
+                    players: None,
+                    timestamp: None,
+                },
+            })
+            .collect();
+
+        let players = servers
+            .iter()
+            .filter(|s| s.online)
+            .filter_map(|s| s.players.as_ref())
+            .fold(Players { online: 0, max: 0 }, |acc, p| Players {
+                online: acc.online + p.online,
+                max: acc.max + p.max,
+            });
+
+        let now = Utc::now().timestamp();
+        let today_peak = self.repo.network_peaks(now, now + 1).await?.pop();
+
+        Ok(NetworkStatus {
+            servers_total: servers.len(),
+            servers_online: servers.iter().filter(|s| s.online).count(),
+            players,
+            today_peak,
+            servers,
+        })
+    }
+
+    async fn network_history(
+        &self,
+        from: i64,
+        to: i64,
+        bucket_secs: Option<i64>,
+    ) -> AppResult<NetworkHistory> {
+        let bucket_secs = bucket_secs.unwrap_or(match to - from {
+            span if span <= AUTO_RAW_MAX_SPAN_SECS => 5 * 60,
+            span if span <= AUTO_HOUR_MAX_SPAN_SECS => 60 * 60,
+            _ => 24 * 60 * 60,
+        });
+        let resolution = if bucket_secs < HistoryResolution::Hour.bucket_secs() {
+            HistoryResolution::Raw
+        } else if bucket_secs < HistoryResolution::Day.bucket_secs() {
+            HistoryResolution::Hour
+        } else {
+            HistoryResolution::Day
+        };
+
+        let server_ids = self.server_ids();
+        let points = self
+            .repo
+            .network_history(&server_ids, resolution, from, to, bucket_secs)
+            .await?;
+        let daily_peaks = self.repo.network_peaks(from, to).await?;
+
+        Ok(NetworkHistory {
+            from,
+            to,
+            bucket_secs,
+            points,
+            daily_peaks,
+        })
+    }
 }
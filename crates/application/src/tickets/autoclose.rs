@@ -0,0 +1,32 @@
+use shared::error::AppResult;
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+use crate::tickets::TicketUsecase;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Closes tickets whose player has not answered for `inactive_days`. A value
+/// of `0` disables the job.
+pub async fn start_ticket_autoclose(
+    usecase: Arc<dyn TicketUsecase>,
+    inactive_days: u32,
+) -> AppResult<()> {
+    if inactive_days == 0 {
+        tracing::info!("Ticket auto-close is disabled");
+        return Ok(());
+    }
+
+    tokio::spawn(async move {
+        loop {
+            match usecase.close_inactive(inactive_days).await {
+                Ok(0) => {}
+                Ok(closed) => tracing::info!("Auto-closed {} inactive tickets", closed),
+                Err(err) => tracing::error!("Failed to auto-close inactive tickets: {}", err),
+            }
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+
+    Ok(())
+}
@@ -1,3 +1,7 @@
+pub mod autoclose;
+pub mod notifier;
 pub mod usecase;
 
+pub use autoclose::*;
+pub use notifier::*;
 pub use usecase::*;
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use domain::tickets::{Ticket, TicketMessage, TicketStatusTransition};
+use infrastructure::discord_tickets::DiscordTicketWebhook;
+use shared::error::AppResult;
+use std::{env, sync::Arc, time::Duration};
+
+/// Mirrors ticket activity to an external channel. Failures are logged by the
+/// caller and never fail the ticket operation itself.
+#[async_trait]
+pub trait TicketNotifier: Send + Sync {
+    /// Returns the id of the thread created for the ticket, if any.
+    async fn ticket_created(&self, ticket: &Ticket) -> AppResult<Option<String>>;
+    async fn staff_replied(&self, ticket: &Ticket, message: &TicketMessage) -> AppResult<()>;
+    async fn status_changed(
+        &self,
+        ticket: &Ticket,
+        transition: &TicketStatusTransition,
+    ) -> AppResult<()>;
+}
+
+pub struct NoopTicketNotifier;
+
+#[async_trait]
+impl TicketNotifier for NoopTicketNotifier {
+    async fn ticket_created(&self, _ticket: &Ticket) -> AppResult<Option<String>> {
+        Ok(None)
+    }
+
+    async fn staff_replied(&self, _ticket: &Ticket, _message: &TicketMessage) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn status_changed(
+        &self,
+        _ticket: &Ticket,
+        _transition: &TicketStatusTransition,
+    ) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TicketNotifier for DiscordTicketWebhook {
+    async fn ticket_created(&self, ticket: &Ticket) -> AppResult<Option<String>> {
+        DiscordTicketWebhook::ticket_created(self, ticket).await
+    }
+
+    async fn staff_replied(&self, ticket: &Ticket, message: &TicketMessage) -> AppResult<()> {
+        DiscordTicketWebhook::staff_replied(self, ticket, message).await
+    }
+
+    async fn status_changed(
+        &self,
+        ticket: &Ticket,
+        transition: &TicketStatusTransition,
+    ) -> AppResult<()> {
+        DiscordTicketWebhook::status_changed(self, ticket, transition).await
+    }
+}
+
+/// Discord notifier when `DISCORD_TICKET_WEBHOOK_URL` is set, otherwise a
+/// no-op. `DISCORD_TICKET_USE_THREADS=false` posts everything to the channel.
+pub fn ticket_notifier_from_env() -> Arc<dyn TicketNotifier> {
+    let Some(webhook_url) = env::var("DISCORD_TICKET_WEBHOOK_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+    else {
+        return Arc::new(NoopTicketNotifier);
+    };
+
+    let use_threads = env::var("DISCORD_TICKET_USE_THREADS")
+        .map(|v| v != "false" && v != "0")
+        .unwrap_or(true);
+
+    Arc::new(DiscordTicketWebhook::new(
+        webhook_url,
+        use_threads,
+        Duration::from_secs(5),
+    ))
+}
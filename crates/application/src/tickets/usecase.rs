@@ -1,47 +1,1056 @@
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::{collections::BTreeMap, sync::Arc};
 
-use domain::tickets::Ticket;
-use infrastructure::repositorys::ticket::TicketRepository;
-use shared::error::AppResult;
+use domain::files::{FileMetadata, UploadPurpose};
+use domain::response::Paginated;
+use domain::tickets::{
+    BacklogAge, CannedResponse, CannedResponsePatch, NewTicketMessage, SlaBreach, SlaMetrics,
+    SlaReport, SlaTargets, Ticket, TicketAttachment, TicketCategory, TicketFilter, TicketMessage,
+    TicketMessageRevision, TicketSlaSample, TicketStaff, TicketStatus, TicketStatusTransition,
+};
+use infrastructure::repositorys::{is_not_found, ticket::TicketRepository};
+use shared::error::{ApiError, AppResult};
+use shared::{EntityType, IdGenerator};
+
+use crate::files::FileUsecase;
+use crate::ids::{slug_alias, slug_conflict, slug_taken};
+use crate::tickets::TicketNotifier;
+
+const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
+const MAX_SLA_RANGE_SECS: i64 = 366 * 24 * 60 * 60;
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Statuses in which the ticket is waiting on the player.
+const WAITING_ON_PLAYER: [TicketStatus; 2] = [TicketStatus::AwaitingPlayer, TicketStatus::Resolved];
 
 pub struct TicketUsecaseImpl<R: TicketRepository + Send + Sync> {
     pub repo: R,
+    pub files: Arc<dyn FileUsecase>,
+    pub sla: SlaTargets,
+    pub notifier: Arc<dyn TicketNotifier>,
+    pub ids: Arc<IdGenerator>,
 }
 
 impl<R: TicketRepository + Send + Sync> TicketUsecaseImpl<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+    pub fn new(
+        repo: R,
+        files: Arc<dyn FileUsecase>,
+        sla: SlaTargets,
+        notifier: Arc<dyn TicketNotifier>,
+        ids: Arc<IdGenerator>,
+    ) -> Self {
+        Self {
+            repo,
+            files,
+            sla,
+            notifier,
+            ids,
+        }
+    }
+
+    /// Mirrors a status change; a notifier outage never fails the change.
+    async fn notify_status(&self, ticket_id: &str, transition: &TicketStatusTransition) {
+        let result = match self.repo.find_by_id(ticket_id).await {
+            Ok(ticket) => self.notifier.status_changed(&ticket, transition).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            tracing::warn!(
+                "Failed to notify status change of ticket {}: {}",
+                ticket_id,
+                err
+            );
+        }
+    }
+
+    /// Resolves `file_ids` to attachments, accepting only completed uploads
+    /// made by `sender`.
+    async fn resolve_attachments(
+        &self,
+        sender: &str,
+        file_ids: &[String],
+    ) -> AppResult<Vec<TicketAttachment>> {
+        if file_ids.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+            return Err(ApiError::bad_request(
+                "too_many_attachments",
+                format!(
+                    "A message can carry at most {} attachments",
+                    MAX_ATTACHMENTS_PER_MESSAGE
+                ),
+            )
+            .into());
+        }
+
+        let mut attachments = Vec::with_capacity(file_ids.len());
+        for file_id in file_ids {
+            let file = self.files.get_file_by_id(file_id).await.map_err(|_| {
+                ApiError::bad_request(
+                    "attachment_not_found",
+                    format!("File '{}' does not exist or is not uploaded yet", file_id),
+                )
+            })?;
+
+            if file.purpose != UploadPurpose::TicketAttachment {
+                return Err(ApiError::bad_request(
+                    "attachment_wrong_purpose",
+                    format!("File '{}' was not uploaded as a ticket_attachment", file_id),
+                )
+                .into());
+            }
+            if file.user_id != sender {
+                return Err(ApiError::forbidden(
+                    "attachment_not_owned",
+                    format!("File '{}' was not uploaded by '{}'", file_id, sender),
+                )
+                .into());
+            }
+
+            attachments.push(TicketAttachment {
+                file_id: file.id,
+                filename: file.filename,
+                content_type: file.content_type,
+                size: file.size,
+            });
+        }
+
+        Ok(attachments)
+    }
+
+    /// Looks the ticket up by id or slug. Callers use the returned ticket's id
+    /// from then on, since `ticket_id` may be the slug.
+    async fn ensure_ticket(&self, ticket_id: &str) -> AppResult<Ticket> {
+        self.repo.find_by_id(ticket_id).await.map_err(|err| {
+            if is_not_found(&err) {
+                ApiError::not_found(
+                    "ticket_not_found",
+                    format!("Ticket '{}' not found", ticket_id),
+                )
+                .into()
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Only the author and staff may change a message.
+    async fn ensure_can_modify(
+        &self,
+        ticket: &Ticket,
+        message: &TicketMessage,
+        actor: &str,
+    ) -> AppResult<()> {
+        if message.sender == actor || self.is_staff_for(ticket, Some(actor)).await? {
+            return Ok(());
+        }
+        Err(ApiError::forbidden(
+            "message_forbidden",
+            "Only the author and staff can change this message",
+        )
+        .into())
+    }
+
+    async fn ensure_message(&self, ticket_id: &str, message_id: i64) -> AppResult<TicketMessage> {
+        self.repo
+            .find_message(ticket_id, message_id)
+            .await?
+            .ok_or_else(|| {
+                ApiError::not_found(
+                    "message_not_found",
+                    format!("Message {} not found on ticket '{}'", message_id, ticket_id),
+                )
+                .into()
+            })
+    }
+
+    async fn apply_transition(
+        &self,
+        ticket: &Ticket,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<TicketStatusTransition> {
+        if !ticket.status.can_transition_to(to) {
+            return Err(ApiError::conflict(
+                "invalid_transition",
+                format!("Ticket cannot move from '{}' to '{}'", ticket.status, to),
+            )
+            .into());
+        }
+
+        let transition = self
+            .repo
+            .transition(&ticket.id, ticket.status, to, actor, reason)
+            .await?
+            .ok_or_else(|| {
+                ApiError::conflict(
+                    "status_changed",
+                    format!("Ticket '{}' changed status concurrently", ticket.id),
+                )
+            })?;
+
+        self.notify_status(&ticket.id, &transition).await;
+        Ok(transition)
+    }
+
+    /// Roster staff and the ticket's assignee count as staff for that ticket.
+    async fn is_staff_for(&self, ticket: &Ticket, viewer: Option<&str>) -> AppResult<bool> {
+        let Some(viewer) = viewer else {
+            return Ok(false);
+        };
+        if ticket.assignee_id.as_deref() == Some(viewer) {
+            return Ok(true);
+        }
+        self.repo.is_staff(viewer).await
+    }
+
+    /// Drops internal notes unless `viewer` is staff for the ticket.
+    async fn visible_to(&self, mut ticket: Ticket, viewer: Option<&str>) -> AppResult<Ticket> {
+        if !self.is_staff_for(&ticket, viewer).await? {
+            ticket.messages.retain(|m| !m.internal);
+        }
+        Ok(ticket)
+    }
+
+    async fn ensure_canned(&self, id: i64) -> AppResult<CannedResponse> {
+        self.repo.find_canned(id).await?.ok_or_else(|| {
+            ApiError::not_found(
+                "canned_response_not_found",
+                format!("Canned response {} not found", id),
+            )
+            .into()
+        })
+    }
+
+    /// Status a new message moves the ticket to: player replies hand the
+    /// ticket to staff, staff replies hand it back to the player.
+    fn status_after_message(ticket: &Ticket, sender: &str) -> Option<TicketStatus> {
+        let from_player = sender == ticket.user_id;
+        let to = match (ticket.status, from_player) {
+            (TicketStatus::Resolved, true) => TicketStatus::Reopened,
+            (_, true) => TicketStatus::AwaitingStaff,
+            (_, false) => TicketStatus::AwaitingPlayer,
+        };
+
+        (ticket.status != to && ticket.status.can_transition_to(to)).then_some(to)
     }
 }
 
 #[async_trait]
 pub trait TicketUsecase: Send + Sync {
-    async fn find_all(&self, user_id: Option<String>) -> AppResult<Vec<Ticket>>;
+    /// One page of tickets as seen by `viewer`; internal notes are left out
+    /// for non-staff, both in the results and in what search matches.
+    async fn find_all(
+        &self,
+        filter: TicketFilter,
+        viewer: Option<&str>,
+    ) -> AppResult<Paginated<Ticket>>;
+    /// Active tickets assigned to `assignee_id`.
+    async fn queue(&self, assignee_id: &str) -> AppResult<Vec<Ticket>>;
+    /// Full ticket, internal notes included.
     async fn find_by_id(&self, id: &str) -> AppResult<Ticket>;
-    async fn create(&self, ticket: Ticket) -> AppResult<()>;
-    async fn update(&self, id: &str, ticket: Ticket) -> AppResult<()>;
+    async fn view(&self, id: &str, viewer: Option<&str>) -> AppResult<Ticket>;
+    /// Returns the ticket with its generated id; a client-supplied id is kept
+    /// as the slug.
+    async fn create(&self, ticket: Ticket, actor: &str) -> AppResult<Ticket>;
+    async fn update(&self, id: &str, ticket: Ticket, actor: &str) -> AppResult<()>;
+    async fn transition(
+        &self,
+        id: &str,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<TicketStatusTransition>;
+    async fn list_transitions(&self, id: &str) -> AppResult<Vec<TicketStatusTransition>>;
+    async fn list_staff(&self) -> AppResult<Vec<TicketStaff>>;
+    async fn upsert_staff(&self, staff: TicketStaff) -> AppResult<TicketStaff>;
+    async fn remove_staff(&self, discord_id: &str, category: TicketCategory) -> AppResult<()>;
     async fn delete(&self, id: &str) -> AppResult<()>;
+    async fn add_message(
+        &self,
+        ticket_id: &str,
+        message: NewTicketMessage,
+    ) -> AppResult<TicketMessage>;
+    /// File metadata of an attachment, for the ticket's participants and staff.
+    async fn get_attachment(
+        &self,
+        ticket_id: &str,
+        file_id: &str,
+        viewer: Option<&str>,
+    ) -> AppResult<FileMetadata>;
+    async fn edit_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        content: &str,
+        actor: &str,
+    ) -> AppResult<(TicketMessage, TicketMessage)>;
+    async fn delete_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        actor: &str,
+    ) -> AppResult<TicketMessage>;
+    async fn message_history(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        viewer: Option<&str>,
+    ) -> AppResult<Vec<TicketMessageRevision>>;
+    async fn list_canned(&self, category: Option<TicketCategory>)
+    -> AppResult<Vec<CannedResponse>>;
+    async fn find_canned(&self, id: i64) -> AppResult<CannedResponse>;
+    async fn create_canned(
+        &self,
+        response: CannedResponse,
+        actor: &str,
+    ) -> AppResult<CannedResponse>;
+    async fn update_canned(&self, id: i64, patch: CannedResponsePatch)
+    -> AppResult<CannedResponse>;
+    /// Returns the deleted response.
+    async fn delete_canned(&self, id: i64) -> AppResult<CannedResponse>;
+    /// SLA figures for tickets created between the unix timestamps `from`
+    /// and `to`.
+    async fn sla_report(&self, from: i64, to: i64) -> AppResult<SlaReport>;
+    /// Reply posted by `author_id` in the Discord thread `thread_id`.
+    async fn add_discord_message(
+        &self,
+        thread_id: &str,
+        author_id: &str,
+        content: &str,
+    ) -> AppResult<(String, TicketMessage)>;
+    /// Closes tickets waiting on the player for more than `inactive_days`.
+    /// Returns how many were closed.
+    async fn close_inactive(&self, inactive_days: u32) -> AppResult<usize>;
 }
 
 #[async_trait]
 impl<R: TicketRepository + Send + Sync> TicketUsecase for TicketUsecaseImpl<R> {
-    async fn find_all(&self, user_id: Option<String>) -> AppResult<Vec<Ticket>> {
-        self.repo.fetch_all(user_id).await
+    async fn find_all(
+        &self,
+        mut filter: TicketFilter,
+        viewer: Option<&str>,
+    ) -> AppResult<Paginated<Ticket>> {
+        if let (Some(after), Some(before)) = (filter.created_after, filter.created_before)
+            && after >= before
+        {
+            return Err(ApiError::bad_request(
+                "invalid_range",
+                "created_after must be earlier than created_before",
+            )
+            .into());
+        }
+
+        let roster_staff = match viewer {
+            Some(viewer) => self.repo.is_staff(viewer).await?,
+            None => false,
+        };
+        filter.include_internal = roster_staff;
+
+        let (page, per_page) = (filter.page(), filter.per_page());
+        let (tickets, total) = self
+            .repo
+            .fetch_page(&filter, per_page, (page - 1) * per_page)
+            .await?;
+
+        let tickets = tickets
+            .into_iter()
+            .map(|mut ticket| {
+                if !roster_staff && (viewer.is_none() || ticket.assignee_id.as_deref() != viewer) {
+                    ticket.messages.retain(|m| !m.internal);
+                }
+                ticket
+            })
+            .collect();
+
+        Ok(Paginated::new(tickets, page, per_page, total))
+    }
+
+    async fn queue(&self, assignee_id: &str) -> AppResult<Vec<Ticket>> {
+        let filter = TicketFilter {
+            assignee_id: Some(assignee_id.to_string()),
+            active_only: true,
+            ..Default::default()
+        };
+        self.repo.fetch_all(&filter).await
     }
 
     async fn find_by_id(&self, id: &str) -> AppResult<Ticket> {
         self.repo.find_by_id(id).await
     }
 
-    async fn create(&self, ticket: Ticket) -> AppResult<()> {
-        self.repo.insert(ticket).await
+    async fn view(&self, id: &str, viewer: Option<&str>) -> AppResult<Ticket> {
+        let ticket = self.ensure_ticket(id).await?;
+        self.visible_to(ticket, viewer).await
+    }
+
+    /// New tickets always start out `open`, whatever the payload says. Tickets
+    /// without an assignee go to the next staff member of their category.
+    /// Initial messages are sent by `actor` and go through the same checks as
+    /// replies; they can never be internal notes or canned responses.
+    async fn create(&self, mut ticket: Ticket, actor: &str) -> AppResult<Ticket> {
+        ticket.slug = slug_alias(&ticket.id, ticket.slug.take())?;
+        if let Some(slug) = &ticket.slug
+            && self.repo.find_by_id(slug).await.is_ok()
+        {
+            return Err(slug_taken(slug));
+        }
+
+        for message in &mut ticket.messages {
+            let file_ids: Vec<String> = message
+                .attachments
+                .iter()
+                .map(|a| a.file_id.clone())
+                .collect();
+            message.attachments = self.resolve_attachments(actor, &file_ids).await?;
+            if message.attachments.is_empty() {
+                message.content = validate_content(&message.content)?;
+            }
+            message.sender = actor.to_string();
+            message.internal = false;
+            message.canned_response_id = None;
+        }
+
+        ticket.id = self.ids.generate_id(EntityType::Ticket);
+        ticket.status = TicketStatus::Open;
+        ticket.labels = normalize_labels(ticket.labels);
+        if ticket.assignee_id.is_none()
+            && let Some(category) = ticket.category
+        {
+            ticket.assignee_id = self.repo.claim_next_staff(category).await?;
+        }
+        self.repo
+            .insert(ticket.clone(), actor)
+            .await
+            .map_err(|err| slug_conflict(err, "idx_tickets_slug", ticket.slug.as_deref()))?;
+
+        match self.notifier.ticket_created(&ticket).await {
+            Ok(Some(thread_id)) => {
+                self.repo.set_discord_thread(&ticket.id, &thread_id).await?;
+                ticket.discord_thread_id = Some(thread_id);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!("Failed to notify creation of ticket {}: {}", ticket.id, err)
+            }
+        }
+
+        Ok(ticket)
+    }
+
+    /// A changed `status` goes through the same rules as [`Self::transition`].
+    async fn update(&self, id: &str, mut ticket: Ticket, actor: &str) -> AppResult<()> {
+        ticket.labels = normalize_labels(ticket.labels);
+        let current = self.ensure_ticket(id).await?;
+        if current.status != ticket.status {
+            self.apply_transition(&current, ticket.status, actor, None)
+                .await?;
+        }
+        self.repo.update(&current.id, ticket).await
+    }
+
+    async fn transition(
+        &self,
+        id: &str,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<TicketStatusTransition> {
+        let ticket = self.ensure_ticket(id).await?;
+        self.apply_transition(&ticket, to, actor, reason).await
     }
 
-    async fn update(&self, id: &str, ticket: Ticket) -> AppResult<()> {
-        self.repo.update(id, ticket).await
+    async fn list_transitions(&self, id: &str) -> AppResult<Vec<TicketStatusTransition>> {
+        let ticket = self.ensure_ticket(id).await?;
+        self.repo.list_transitions(&ticket.id).await
     }
 
+    /// Deletes the ticket and removes its attachments from storage.
     async fn delete(&self, id: &str) -> AppResult<()> {
-        self.repo.delete(id).await
+        let id = &self.ensure_ticket(id).await?.id;
+        let file_ids = self.repo.attachment_file_ids(id).await?;
+        self.repo.delete(id).await?;
+
+        for file_id in file_ids {
+            if let Err(err) = self.files.delete_file(&file_id).await {
+                tracing::error!(
+                    "Failed to delete attachment {} of ticket {}: {}",
+                    file_id,
+                    id,
+                    err
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list_staff(&self) -> AppResult<Vec<TicketStaff>> {
+        self.repo.list_staff().await
+    }
+
+    async fn upsert_staff(&self, staff: TicketStaff) -> AppResult<TicketStaff> {
+        if staff.discord_id.trim().is_empty() {
+            return Err(
+                ApiError::bad_request("invalid_staff", "discord_id must not be empty").into(),
+            );
+        }
+        self.repo.upsert_staff(&staff).await
+    }
+
+    async fn remove_staff(&self, discord_id: &str, category: TicketCategory) -> AppResult<()> {
+        if !self.repo.delete_staff(discord_id, category).await? {
+            return Err(ApiError::not_found(
+                "staff_not_found",
+                format!("'{}' is not on the {} roster", discord_id, category),
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Only the ticket's owner and staff may post. Internal notes and canned
+    /// responses are reserved to staff. A canned response is rendered ahead
+    /// of any text typed alongside it.
+    async fn add_message(
+        &self,
+        ticket_id: &str,
+        message: NewTicketMessage,
+    ) -> AppResult<TicketMessage> {
+        let ticket = self.ensure_ticket(ticket_id).await?;
+        let ticket_id = &ticket.id.clone();
+        if ticket.status.is_closed() {
+            return Err(ApiError::conflict(
+                "ticket_closed",
+                format!("Ticket '{}' is closed; reopen it first", ticket_id),
+            )
+            .into());
+        }
+
+        let sender = message.sender.as_str();
+        let staff = self.is_staff_for(&ticket, Some(sender)).await?;
+        if !staff && sender != ticket.user_id {
+            return Err(ApiError::forbidden(
+                "ticket_forbidden",
+                "Only the ticket's owner and staff can post on it",
+            )
+            .into());
+        }
+        if (message.internal || message.canned_response_id.is_some()) && !staff {
+            return Err(ApiError::forbidden(
+                "staff_only",
+                "Only staff can post internal notes or canned responses",
+            )
+            .into());
+        }
+
+        let mut parts = Vec::new();
+        if let Some(canned_id) = message.canned_response_id {
+            let canned = self.ensure_canned(canned_id).await?;
+            parts.push(canned.render(
+                &ticket,
+                message.player_name.as_deref().unwrap_or(&ticket.user_id),
+                message.staff_name.as_deref().unwrap_or(sender),
+            ));
+        }
+        parts.push(message.content.trim().to_string());
+        let content = parts
+            .into_iter()
+            .filter(|part| !part.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let content = if message.attachments.is_empty() {
+            validate_content(&content)?
+        } else {
+            content
+        };
+
+        let attachments = self
+            .resolve_attachments(sender, &message.attachments)
+            .await?;
+
+        let new_message = TicketMessage {
+            id: 0,
+            sender: sender.to_string(),
+            content,
+            sent_at: Utc::now().naive_utc(),
+            edited_at: None,
+            attachments,
+            internal: message.internal,
+            canned_response_id: message.canned_response_id,
+        };
+
+        let inserted = self.repo.insert_message(ticket_id, &new_message).await?;
+
+        if !message.internal && staff && !message.via_discord {
+            let ticket = self.repo.find_by_id(ticket_id).await?;
+            if let Err(err) = self.notifier.staff_replied(&ticket, &inserted).await {
+                tracing::warn!("Failed to mirror reply on ticket {}: {}", ticket_id, err);
+            }
+        }
+
+        if !message.internal
+            && let Some(to) = Self::status_after_message(&ticket, sender)
+            && let Some(transition) = self
+                .repo
+                .transition(ticket_id, ticket.status, to, sender, Some("new message"))
+                .await?
+        {
+            self.notify_status(ticket_id, &transition).await;
+        }
+
+        Ok(inserted)
     }
+
+    async fn get_attachment(
+        &self,
+        ticket_id: &str,
+        file_id: &str,
+        viewer: Option<&str>,
+    ) -> AppResult<FileMetadata> {
+        let ticket = self.ensure_ticket(ticket_id).await?;
+        let ticket_id = ticket.id.as_str();
+        let staff = self.is_staff_for(&ticket, viewer).await?;
+
+        // Checked first so outsiders cannot probe which files are attached.
+        let allowed = match viewer {
+            Some(viewer) => staff || self.repo.is_participant(ticket_id, viewer).await?,
+            None => false,
+        };
+        if !allowed {
+            return Err(ApiError::forbidden(
+                "attachment_forbidden",
+                "Only the ticket's participants and staff can access its attachments",
+            )
+            .into());
+        }
+
+        if !self.repo.has_attachment(ticket_id, file_id, staff).await? {
+            return Err(ApiError::not_found(
+                "attachment_not_found",
+                format!(
+                    "File '{}' is not attached to ticket '{}'",
+                    file_id, ticket_id
+                ),
+            )
+            .into());
+        }
+
+        self.files.get_file_by_id(file_id).await
+    }
+
+    /// Returns the message before and after the edit.
+    async fn edit_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        content: &str,
+        actor: &str,
+    ) -> AppResult<(TicketMessage, TicketMessage)> {
+        let content = validate_content(content)?;
+        let ticket = self.ensure_ticket(ticket_id).await?;
+        let before = self.ensure_message(&ticket.id, message_id).await?;
+        self.ensure_can_modify(&ticket, &before, actor).await?;
+        let after = self
+            .repo
+            .update_message(message_id, &content, actor)
+            .await?;
+        Ok((before, after))
+    }
+
+    async fn delete_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        actor: &str,
+    ) -> AppResult<TicketMessage> {
+        let ticket = self.ensure_ticket(ticket_id).await?;
+        let message = self.ensure_message(&ticket.id, message_id).await?;
+        self.ensure_can_modify(&ticket, &message, actor).await?;
+        self.repo.delete_message(message_id, actor).await?;
+        Ok(message)
+    }
+
+    /// Staff see the history of every message; others only that of live,
+    /// non-internal messages.
+    async fn message_history(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+        viewer: Option<&str>,
+    ) -> AppResult<Vec<TicketMessageRevision>> {
+        let ticket = self.ensure_ticket(ticket_id).await?;
+        let ticket_id = ticket.id.as_str();
+        if !self.is_staff_for(&ticket, viewer).await? {
+            let message = self.ensure_message(ticket_id, message_id).await?;
+            if message.internal {
+                return Err(ApiError::not_found(
+                    "message_not_found",
+                    format!("Message {} not found on ticket '{}'", message_id, ticket_id),
+                )
+                .into());
+            }
+        }
+
+        self.repo
+            .list_message_revisions(ticket_id, message_id)
+            .await
+    }
+
+    async fn list_canned(
+        &self,
+        category: Option<TicketCategory>,
+    ) -> AppResult<Vec<CannedResponse>> {
+        self.repo.list_canned(category).await
+    }
+
+    async fn find_canned(&self, id: i64) -> AppResult<CannedResponse> {
+        self.ensure_canned(id).await
+    }
+
+    async fn create_canned(
+        &self,
+        mut response: CannedResponse,
+        actor: &str,
+    ) -> AppResult<CannedResponse> {
+        validate_canned(&response)?;
+        response.created_by = Some(actor.to_string());
+        self.repo.insert_canned(&response).await
+    }
+
+    async fn update_canned(
+        &self,
+        id: i64,
+        patch: CannedResponsePatch,
+    ) -> AppResult<CannedResponse> {
+        let mut response = self.ensure_canned(id).await?;
+        response.apply(patch);
+        validate_canned(&response)?;
+        self.repo.update_canned(&response).await
+    }
+
+    async fn delete_canned(&self, id: i64) -> AppResult<CannedResponse> {
+        let response = self.ensure_canned(id).await?;
+        self.repo.delete_canned(id).await?;
+        Ok(response)
+    }
+
+    async fn add_discord_message(
+        &self,
+        thread_id: &str,
+        author_id: &str,
+        content: &str,
+    ) -> AppResult<(String, TicketMessage)> {
+        let ticket = self
+            .repo
+            .find_by_discord_thread(thread_id)
+            .await?
+            .ok_or_else(|| {
+                ApiError::not_found(
+                    "ticket_not_found",
+                    format!("No ticket is linked to thread '{}'", thread_id),
+                )
+            })?;
+
+        let message = self
+            .add_message(
+                &ticket.id,
+                NewTicketMessage {
+                    sender: author_id.to_string(),
+                    content: content.to_string(),
+                    via_discord: true,
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        Ok((ticket.id, message))
+    }
+
+    async fn close_inactive(&self, inactive_days: u32) -> AppResult<usize> {
+        let cutoff = Utc::now().naive_utc() - chrono::Duration::days(i64::from(inactive_days));
+        let reason = format!("No player activity for {} days", inactive_days);
+
+        let mut closed = 0;
+        for (ticket_id, status) in self.repo.find_stale(&WAITING_ON_PLAYER, cutoff).await? {
+            if let Some(transition) = self
+                .repo
+                .transition(
+                    &ticket_id,
+                    status,
+                    TicketStatus::Closed,
+                    "system",
+                    Some(&reason),
+                )
+                .await?
+            {
+                self.notify_status(&ticket_id, &transition).await;
+                closed += 1;
+            }
+        }
+
+        Ok(closed)
+    }
+
+    async fn sla_report(&self, from: i64, to: i64) -> AppResult<SlaReport> {
+        if from >= to {
+            return Err(
+                ApiError::bad_request("invalid_range", "from must be earlier than to").into(),
+            );
+        }
+        if to - from > MAX_SLA_RANGE_SECS {
+            return Err(ApiError::bad_request(
+                "range_too_large",
+                "SLA reports are limited to 366 days",
+            )
+            .into());
+        }
+
+        let (Some(from_at), Some(to_at)) = (naive_from_unix(from), naive_from_unix(to)) else {
+            return Err(ApiError::bad_request("invalid_range", "from/to are out of range").into());
+        };
+
+        let now = Utc::now().naive_utc();
+        let samples = self.repo.sla_samples(from_at, to_at).await?;
+        let backlog = self.repo.backlog_samples().await?;
+        let targets = self.sla;
+
+        let by_category = group_by(&samples, category_key)
+            .into_iter()
+            .map(|(key, group)| sla_metrics(key, &group, &targets, now))
+            .collect();
+        let by_staff = group_by(&samples, staff_key)
+            .into_iter()
+            .map(|(key, group)| sla_metrics(key, &group, &targets, now))
+            .collect();
+        let backlog_by_category = group_by(&backlog, category_key)
+            .into_iter()
+            .map(|(key, group)| backlog_age(key, &group, now))
+            .collect();
+        let backlog_by_staff = group_by(&backlog, staff_key)
+            .into_iter()
+            .map(|(key, group)| backlog_age(key, &group, now))
+            .collect();
+
+        Ok(SlaReport {
+            from,
+            to,
+            targets,
+            overall: sla_metrics(None, &samples.iter().collect::<Vec<_>>(), &targets, now),
+            by_category,
+            by_staff,
+            backlog_by_category,
+            backlog_by_staff,
+            breaches: sla_breaches(&samples, &targets, now),
+        })
+    }
+}
+
+fn validate_content(content: &str) -> AppResult<String> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err(ApiError::bad_request("empty_message", "content must not be empty").into());
+    }
+    Ok(content.to_string())
+}
+
+fn validate_canned(response: &CannedResponse) -> AppResult<()> {
+    if response.name.trim().is_empty() || response.content.trim().is_empty() {
+        return Err(ApiError::bad_request(
+            "invalid_canned_response",
+            "name and content must not be empty",
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Trims, lowercases and de-duplicates labels, dropping empty ones.
+fn normalize_labels(labels: Vec<String>) -> Vec<String> {
+    let mut labels: Vec<String> = labels
+        .into_iter()
+        .map(|label| label.trim().to_lowercase())
+        .filter(|label| !label.is_empty())
+        .collect();
+    labels.sort();
+    labels.dedup();
+    labels
+}
+
+fn naive_from_unix(secs: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(secs, 0).map(|dt| dt.naive_utc())
+}
+
+fn secs_between(from: NaiveDateTime, to: NaiveDateTime) -> i64 {
+    (to - from).num_seconds().max(0)
+}
+
+fn is_active(sample: &TicketSlaSample) -> bool {
+    !matches!(sample.status, TicketStatus::Resolved | TicketStatus::Closed)
+}
+
+fn category_key(sample: &TicketSlaSample) -> Option<String> {
+    sample.category.map(|c| c.to_string())
+}
+
+fn staff_key(sample: &TicketSlaSample) -> Option<String> {
+    sample.assignee_id.clone()
+}
+
+fn group_by(
+    samples: &[TicketSlaSample],
+    key: fn(&TicketSlaSample) -> Option<String>,
+) -> BTreeMap<Option<String>, Vec<&TicketSlaSample>> {
+    let mut groups: BTreeMap<Option<String>, Vec<&TicketSlaSample>> = BTreeMap::new();
+    for sample in samples {
+        groups.entry(key(sample)).or_default().push(sample);
+    }
+    groups
+}
+
+/// Seconds until first response, counting unanswered active tickets up to
+/// `now`. `None` when the ticket ended without a staff reply.
+fn first_response_secs(sample: &TicketSlaSample, now: NaiveDateTime) -> Option<(i64, bool)> {
+    match sample.first_response_at {
+        Some(at) => Some((secs_between(sample.created_at, at), false)),
+        None if is_active(sample) => Some((secs_between(sample.created_at, now), true)),
+        None => None,
+    }
+}
+
+fn resolution_secs(sample: &TicketSlaSample, now: NaiveDateTime) -> Option<(i64, bool)> {
+    match sample.resolved_at {
+        Some(at) => Some((secs_between(sample.created_at, at), false)),
+        None if is_active(sample) => Some((secs_between(sample.created_at, now), true)),
+        None => None,
+    }
+}
+
+fn average(values: &[i64]) -> Option<i64> {
+    (!values.is_empty()).then(|| values.iter().sum::<i64>() / values.len() as i64)
+}
+
+fn p90(values: &mut [i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    let rank = (values.len() * 9).div_ceil(10);
+    Some(values[rank.saturating_sub(1)])
+}
+
+fn sla_metrics(
+    key: Option<String>,
+    samples: &[&TicketSlaSample],
+    targets: &SlaTargets,
+    now: NaiveDateTime,
+) -> SlaMetrics {
+    let mut first_responses = Vec::new();
+    let mut resolutions = Vec::new();
+    let mut metrics = SlaMetrics {
+        key,
+        tickets: samples.len() as i64,
+        ..Default::default()
+    };
+
+    for sample in samples {
+        if let Some((secs, ongoing)) = first_response_secs(sample, now) {
+            if !ongoing {
+                first_responses.push(secs);
+            }
+            if secs > targets.first_response_secs {
+                metrics.first_response_breaches += 1;
+            }
+        }
+        if let Some((secs, ongoing)) = resolution_secs(sample, now) {
+            if !ongoing {
+                resolutions.push(secs);
+            }
+            if secs > targets.resolution_secs {
+                metrics.resolution_breaches += 1;
+            }
+        }
+    }
+
+    metrics.responded = first_responses.len() as i64;
+    metrics.first_response_avg_secs = average(&first_responses);
+    metrics.first_response_p90_secs = p90(&mut first_responses);
+    metrics.first_response_breached = metrics
+        .first_response_avg_secs
+        .is_some_and(|avg| avg > targets.first_response_secs);
+
+    metrics.resolved = resolutions.len() as i64;
+    metrics.resolution_avg_secs = average(&resolutions);
+    metrics.resolution_p90_secs = p90(&mut resolutions);
+    metrics.resolution_breached = metrics
+        .resolution_avg_secs
+        .is_some_and(|avg| avg > targets.resolution_secs);
+
+    metrics
+}
+
+fn backlog_age(
+    key: Option<String>,
+    samples: &[&TicketSlaSample],
+    now: NaiveDateTime,
+) -> BacklogAge {
+    let mut backlog = BacklogAge {
+        key,
+        ..Default::default()
+    };
+
+    for sample in samples {
+        let age = secs_between(sample.created_at, now);
+        match age {
+            a if a < DAY_SECS => backlog.under_1d += 1,
+            a if a < 3 * DAY_SECS => backlog.from_1d_to_3d += 1,
+            a if a < 7 * DAY_SECS => backlog.from_3d_to_7d += 1,
+            _ => backlog.over_7d += 1,
+        }
+        backlog.oldest_secs = Some(backlog.oldest_secs.map_or(age, |oldest| oldest.max(age)));
+    }
+
+    backlog
+}
+
+fn sla_breaches(
+    samples: &[TicketSlaSample],
+    targets: &SlaTargets,
+    now: NaiveDateTime,
+) -> Vec<SlaBreach> {
+    let mut breaches = Vec::new();
+
+    for sample in samples {
+        let checks = [
+            (
+                "first_response",
+                first_response_secs(sample, now),
+                targets.first_response_secs,
+            ),
+            (
+                "resolution",
+                resolution_secs(sample, now),
+                targets.resolution_secs,
+            ),
+        ];
+
+        for (kind, elapsed, target_secs) in checks {
+            if let Some((elapsed_secs, ongoing)) = elapsed
+                && elapsed_secs > target_secs
+            {
+                breaches.push(SlaBreach {
+                    ticket_id: sample.ticket_id.clone(),
+                    category: sample.category,
+                    assignee_id: sample.assignee_id.clone(),
+                    kind: kind.to_string(),
+                    elapsed_secs,
+                    target_secs,
+                    ongoing,
+                });
+            }
+        }
+    }
+
+    breaches
 }
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Offline,
+    PlayersAbove,
+    LatencyAbove,
+}
+
+impl AlertKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "offline" => Some(AlertKind::Offline),
+            "players_above" => Some(AlertKind::PlayersAbove),
+            "latency_above" => Some(AlertKind::LatencyAbove),
+            _ => None,
+        }
+    }
+
+    pub fn requires_threshold(&self) -> bool {
+        !matches!(self, AlertKind::Offline)
+    }
+}
+
+impl std::fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AlertKind::Offline => "offline",
+                AlertKind::PlayersAbove => "players_above",
+                AlertKind::LatencyAbove => "latency_above",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    /// `None` applies the rule to every server in `config/status.toml`.
+    #[serde(default)]
+    pub server_id: Option<String>,
+    pub kind: AlertKind,
+    #[serde(default)]
+    pub threshold: Option<i32>,
+    pub duration_secs: i32,
+    pub webhook_url: String,
+    /// HMAC key for the `X-Natsume-Signature` header; never returned by the API.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub notify_recovery: bool,
+}
+
+impl AlertRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name is required".to_string());
+        }
+        if !(self.webhook_url.starts_with("https://") || self.webhook_url.starts_with("http://")) {
+            return Err("webhook_url must be an http(s) URL".to_string());
+        }
+        if self.duration_secs < 0 {
+            return Err("duration_secs must not be negative".to_string());
+        }
+        if self.kind.requires_threshold() && self.threshold.is_none() {
+            return Err(format!("threshold is required for {} rules", self.kind));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&mut self, patch: AlertRulePatch) {
+        if let Some(name) = patch.name {
+            self.name = name;
+        }
+        if let Some(server_id) = patch.server_id {
+            self.server_id = server_id;
+        }
+        if let Some(kind) = patch.kind {
+            self.kind = kind;
+        }
+        if let Some(threshold) = patch.threshold {
+            self.threshold = threshold;
+        }
+        if let Some(duration_secs) = patch.duration_secs {
+            self.duration_secs = duration_secs;
+        }
+        if let Some(webhook_url) = patch.webhook_url {
+            self.webhook_url = webhook_url;
+        }
+        if let Some(secret) = patch.secret {
+            self.secret = secret;
+        }
+        if let Some(enabled) = patch.enabled {
+            self.enabled = enabled;
+        }
+        if let Some(notify_recovery) = patch.notify_recovery {
+            self.notify_recovery = notify_recovery;
+        }
+    }
+}
+
+/// Partial update of an [`AlertRule`]; nullable fields use `Some(None)` to clear.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AlertRulePatch {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub server_id: Option<Option<String>>,
+    pub kind: Option<AlertKind>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub threshold: Option<Option<i32>>,
+    pub duration_secs: Option<i32>,
+    pub webhook_url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_nullable")]
+    pub secret: Option<Option<String>>,
+    pub enabled: Option<bool>,
+    pub notify_recovery: Option<bool>,
+}
+
+pub(crate) fn deserialize_nullable<'de, D, T>(
+    deserializer: D,
+) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertEvent {
+    Firing,
+    Resolved,
+    Test,
+}
+
+impl std::fmt::Display for AlertEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AlertEvent::Firing => "firing",
+                AlertEvent::Resolved => "resolved",
+                AlertEvent::Test => "test",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertDelivery {
+    pub id: i64,
+    pub rule_id: i64,
+    pub server_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub created_at: i64,
+}
+
+/// Last notified state of a rule for one server, along with the failed
+/// passes of a notification still waiting to be delivered.
+#[derive(Debug, Clone, Default)]
+pub struct AlertState {
+    pub firing: bool,
+    /// Passes the pending notification failed on.
+    pub delivery_failures: i32,
+    /// Webhook requests made for the pending notification so far.
+    pub delivery_attempts: i32,
+    /// Unix timestamp before which the pending notification is not retried.
+    pub retry_at: Option<i64>,
+}
+
+/// Aggregated probe results for one server over an alert rule's window.
+#[derive(Debug, Clone, Default)]
+pub struct AlertWindowStats {
+    pub samples: i64,
+    pub online_samples: i64,
+    pub min_players: Option<i32>,
+    pub avg_latency: Option<f64>,
+    pub latest_online: Option<bool>,
+    pub last_online_at: Option<i64>,
+    pub first_seen_at: Option<i64>,
+}
@@ -0,0 +1,217 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::{FileVisibility, UploadPurpose};
+
+const MAX_FOLDER_LEN: usize = 256;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 32;
+
+/// Query filters for listing files; every field is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileFilter {
+    pub user_id: Option<String>,
+    /// Files in this folder or any folder below it.
+    pub folder: Option<String>,
+    pub tag: Option<String>,
+    /// Case-insensitive part of the filename.
+    pub q: Option<String>,
+    /// Full media type, or only its type such as `image`.
+    pub content_type: Option<String>,
+    pub purpose: Option<UploadPurpose>,
+    pub visibility: Option<FileVisibility>,
+    /// Unix timestamps bounding `uploaded_at`, `uploaded_before` exclusive.
+    pub uploaded_after: Option<i64>,
+    pub uploaded_before: Option<i64>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl FileFilter {
+    pub const DEFAULT_PER_PAGE: i64 = 50;
+    pub const MAX_PER_PAGE: i64 = 200;
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page
+            .unwrap_or(Self::DEFAULT_PER_PAGE)
+            .clamp(1, Self::MAX_PER_PAGE)
+    }
+}
+
+/// Changes to a file's visibility and organisation; unset fields are kept.
+#[derive(Debug, Clone, Default)]
+pub struct FilePatch {
+    pub visibility: Option<FileVisibility>,
+    pub folder: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+/// Folder holding at least one file, with what it directly contains.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileFolder {
+    pub folder: String,
+    pub file_count: i64,
+    pub total_size: i64,
+}
+
+/// What kind of entity uses a file. Ticket usage follows from attachments;
+/// the others are registered explicitly.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileUsageKind {
+    Item,
+    Ticket,
+    ResourcePack,
+}
+
+impl FileUsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileUsageKind::Item => "item",
+            FileUsageKind::Ticket => "ticket",
+            FileUsageKind::ResourcePack => "resource_pack",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "item" => Some(FileUsageKind::Item),
+            "ticket" => Some(FileUsageKind::Ticket),
+            "resource_pack" => Some(FileUsageKind::ResourcePack),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FileUsageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One place a file is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileUsage {
+    pub kind: FileUsageKind,
+    pub entity_id: String,
+    /// Item name or ticket title, when the entity is known to us.
+    pub label: Option<String>,
+    pub since: NaiveDateTime,
+}
+
+/// Folder path with empty segments and surrounding whitespace removed:
+/// ` /Textures//swords/ ` becomes `Textures/swords`.
+pub fn normalize_folder(raw: &str) -> Result<String, String> {
+    let mut segments = Vec::new();
+    for segment in raw.split('/').map(str::trim).filter(|s| !s.is_empty()) {
+        if segment == "." || segment == ".." {
+            return Err(format!("Folder segment '{}' is not allowed", segment));
+        }
+        if segment.chars().any(char::is_control) {
+            return Err("Folders must not contain control characters".to_string());
+        }
+        segments.push(segment);
+    }
+
+    let folder = segments.join("/");
+    if folder.len() > MAX_FOLDER_LEN {
+        return Err(format!("Folders are limited to {} bytes", MAX_FOLDER_LEN));
+    }
+    Ok(folder)
+}
+
+/// Tags trimmed, lower-cased and deduplicated in their original order.
+pub fn normalize_tags(raw: &[String]) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.iter().map(|t| t.trim().to_lowercase()) {
+        if tag.is_empty() || tags.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_LEN {
+            return Err(format!("Tags are limited to {} characters", MAX_TAG_LEN));
+        }
+        if tag
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || c == ',')
+        {
+            return Err(format!(
+                "Tag '{}' must not contain spaces, commas or control characters",
+                tag
+            ));
+        }
+        tags.push(tag);
+    }
+
+    if tags.len() > MAX_TAGS {
+        return Err(format!("A file can have at most {} tags", MAX_TAGS));
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn normalizes_folders() {
+        let cases = [
+            ("", ""),
+            ("/", ""),
+            (" /Textures//swords/ ", "Textures/swords"),
+            ("a / b", "a/b"),
+            ("..a/b..", "..a/b.."),
+        ];
+        for (raw, folder) in cases {
+            assert_eq!(normalize_folder(raw).unwrap(), folder, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn rejects_relative_and_control_segments() {
+        for raw in ["..", "a/../b", "a/./b", " .. /b", "a/b\nc", "a\u{7f}b"] {
+            assert!(normalize_folder(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn limits_folder_length_after_normalizing() {
+        let folder = "a".repeat(MAX_FOLDER_LEN);
+        assert_eq!(normalize_folder(&format!("/{}/", folder)).unwrap(), folder);
+        assert!(normalize_folder(&format!("{}a", folder)).is_err());
+    }
+
+    #[test]
+    fn normalizes_tags_in_order() {
+        assert_eq!(
+            normalize_tags(&tags(&[" Sword ", "", "fire", "SWORD", "fire", "  "])).unwrap(),
+            tags(&["sword", "fire"])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_tags() {
+        for tag in ["two words", "a,b", "ta\tb", "bell\u{7}s"] {
+            assert!(normalize_tags(&tags(&[tag])).is_err(), "{:?}", tag);
+        }
+        assert!(normalize_tags(&["é".repeat(MAX_TAG_LEN)]).is_ok());
+        assert!(normalize_tags(&["a".repeat(MAX_TAG_LEN + 1)]).is_err());
+    }
+
+    #[test]
+    fn limits_tags_after_deduplicating() {
+        let mut raw: Vec<String> = (0..MAX_TAGS).map(|i| format!("tag{}", i)).collect();
+        raw.push("TAG0".to_string());
+        assert_eq!(normalize_tags(&raw).unwrap().len(), MAX_TAGS);
+
+        raw.push("one-more".to_string());
+        assert!(normalize_tags(&raw).is_err());
+    }
+}
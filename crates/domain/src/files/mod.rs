@@ -1,5 +1,97 @@
+pub mod catalog;
+pub mod policy;
+pub mod quota;
+
+pub use catalog::*;
+pub use policy::*;
+pub use quota::*;
+
 use serde::{Deserialize, Serialize};
 
+/// Prefix of uploaded files.
+pub const FILES_PREFIX: &str = "files/";
+/// Prefix of renditions generated from image uploads.
+const DERIVED_PREFIX: &str = "derived/";
+/// Prefix private objects are stored under. Only [`FILES_PREFIX`] and the
+/// renditions may be served by the CDN.
+pub const PRIVATE_PREFIX: &str = "private/";
+pub const THUMBNAIL_NAME: &str = "thumbnail.png";
+pub const PREVIEW_NAME: &str = "preview.png";
+
+/// Key of the rendition `name` generated from the file `file_id`.
+pub fn derived_key(file_id: &str, name: &str, visibility: FileVisibility) -> String {
+    visibility.object_key(&format!("{}{}/{}", DERIVED_PREFIX, file_id, name))
+}
+
+/// Whether `key` may be downloaded without a signed URL.
+pub fn is_public_key(key: &str) -> bool {
+    key.starts_with(FILES_PREFIX) || key.starts_with(DERIVED_PREFIX)
+}
+
+/// Who can download a file. Public files are linked through the CDN; private
+/// ones only through expiring signed URLs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FileVisibility {
+    #[default]
+    Public,
+    Private,
+}
+
+impl FileVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileVisibility::Public => "public",
+            FileVisibility::Private => "private",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "public" => Some(FileVisibility::Public),
+            "private" => Some(FileVisibility::Private),
+            _ => None,
+        }
+    }
+
+    /// `key` moved under the prefix for this visibility.
+    pub fn object_key(&self, key: &str) -> String {
+        let key = key.strip_prefix(PRIVATE_PREFIX).unwrap_or(key);
+        match self {
+            FileVisibility::Public => key.to_string(),
+            FileVisibility::Private => format!("{}{}", PRIVATE_PREFIX, key),
+        }
+    }
+}
+
+impl std::fmt::Display for FileVisibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Where file download links point.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileUrlConfig {
+    /// Base URL public objects are served from, without a trailing slash.
+    pub cdn_base_url: String,
+    /// How long signed links to private files stay valid.
+    pub private_url_expires_secs: u64,
+}
+
+impl Default for FileUrlConfig {
+    fn default() -> Self {
+        Self {
+            cdn_base_url: "https://cdn.alcaris.net".to_string(),
+            private_url_expires_secs: 15 * 60,
+        }
+    }
+}
+
+pub const UPLOAD_IN_PROGRESS: &str = "in_progress";
+/// The assembled object failed verification and was moved aside.
+pub const UPLOAD_QUARANTINED: &str = "quarantined";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileMetadata {
     pub id: String,
@@ -7,8 +99,54 @@ pub struct FileMetadata {
     pub filename: String,
     pub content_type: String,
     pub size: i64,
+    /// Object holding the content, shared by files with the same `sha256`.
+    #[serde(default)]
+    pub key: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub purpose: UploadPurpose,
+    #[serde(default)]
+    pub visibility: FileVisibility,
+    /// Virtual folder, `/`-separated; empty for the root.
+    #[serde(default)]
+    pub folder: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub uploaded_at: chrono::NaiveDateTime,
+    /// CDN link for public files, an expiring signed link for private ones.
     pub url: Option<String>,
+    /// Set for images, along with the thumbnail and preview.
+    #[serde(default)]
+    pub width: Option<i32>,
+    #[serde(default)]
+    pub height: Option<i32>,
+    /// Animation frames: GIF frames, or the frames of a texture strip.
+    #[serde(default)]
+    pub frames: Option<i32>,
+    /// Whether a thumbnail and preview were generated.
+    #[serde(default)]
+    pub has_previews: bool,
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub preview_url: Option<String>,
+    pub uploader_username: Option<String>,
+    pub uploader_global_name: Option<String>,
+    pub uploader_avatar_url: Option<String>,
+}
+
+/// File sent in a single request rather than as a multipart upload.
+#[derive(Debug, Clone)]
+pub struct NewFile {
+    pub user_id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub purpose: UploadPurpose,
+    /// Defaults by purpose.
+    pub visibility: Option<FileVisibility>,
+    pub folder: Option<String>,
+    pub tags: Vec<String>,
     pub uploader_username: Option<String>,
     pub uploader_global_name: Option<String>,
     pub uploader_avatar_url: Option<String>,
@@ -24,6 +162,10 @@ pub struct FileUploadSession {
     pub content_type: String,
     pub size: i64,
     pub part_size: i64,
+    pub purpose: UploadPurpose,
+    pub visibility: FileVisibility,
+    pub status: String,
+    pub quarantine_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,3 +173,29 @@ pub struct FileUploadPart {
     pub part_number: i32,
     pub etag: String,
 }
+
+/// Multipart upload aborted by the stale upload reaper.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReapedUpload {
+    pub upload_id: String,
+    pub key: String,
+    /// Unset for orphans, which have no session to take these from.
+    pub file_id: Option<String>,
+    pub user_id: Option<String>,
+    /// Last activity on the session, or when the bucket says it started.
+    pub last_activity_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Outcome of one pass of the stale upload reaper.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadReaperRun {
+    pub id: i64,
+    pub ran_at: chrono::NaiveDateTime,
+    pub ttl_secs: i64,
+    /// Sessions idle for longer than the TTL.
+    pub expired: Vec<ReapedUpload>,
+    /// Multipart uploads in the bucket without a session.
+    pub orphaned: Vec<ReapedUpload>,
+    /// Uploads that could not be aborted; retried on the next pass.
+    pub failures: Vec<String>,
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+
+use super::FileVisibility;
+
+const MIB: i64 = 1024 * 1024;
+
+/// Largest file accepted by the single-request upload; anything bigger goes
+/// through a multipart upload.
+pub const DIRECT_UPLOAD_MAX_SIZE: i64 = 32 * MIB;
+
+/// What an upload is for, which decides the [`UploadPolicy`] it must meet.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadPurpose {
+    #[default]
+    General,
+    ItemTexture,
+    Avatar,
+    TicketAttachment,
+}
+
+impl UploadPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadPurpose::General => "general",
+            UploadPurpose::ItemTexture => "item_texture",
+            UploadPurpose::Avatar => "avatar",
+            UploadPurpose::TicketAttachment => "ticket_attachment",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "general" => Some(UploadPurpose::General),
+            "item_texture" => Some(UploadPurpose::ItemTexture),
+            "avatar" => Some(UploadPurpose::Avatar),
+            "ticket_attachment" => Some(UploadPurpose::TicketAttachment),
+            _ => None,
+        }
+    }
+
+    /// Visibility of uploads that do not ask for one.
+    pub fn default_visibility(&self) -> FileVisibility {
+        match self {
+            UploadPurpose::TicketAttachment => FileVisibility::Private,
+            _ => FileVisibility::Public,
+        }
+    }
+
+    pub fn policy(&self) -> UploadPolicy {
+        match self {
+            UploadPurpose::General => UploadPolicy {
+                max_size: 5 * 1024 * MIB,
+                allowed_types: None,
+                minecraft_texture: false,
+            },
+            UploadPurpose::ItemTexture => UploadPolicy {
+                max_size: MIB,
+                allowed_types: Some(&["image/png"]),
+                minecraft_texture: true,
+            },
+            UploadPurpose::Avatar => UploadPolicy {
+                max_size: 8 * MIB,
+                allowed_types: Some(&["image/png", "image/jpeg", "image/gif", "image/webp"]),
+                minecraft_texture: false,
+            },
+            UploadPurpose::TicketAttachment => UploadPolicy {
+                max_size: 20 * MIB,
+                allowed_types: Some(&[
+                    "image/png",
+                    "image/jpeg",
+                    "image/gif",
+                    "image/webp",
+                    "text/plain",
+                ]),
+                minecraft_texture: false,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for UploadPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Limits an upload is checked against when it is created and once more
+/// against the stored bytes when it is completed.
+#[derive(Debug, Clone, Copy)]
+pub struct UploadPolicy {
+    pub max_size: i64,
+    /// Accepted media types; `None` accepts any.
+    pub allowed_types: Option<&'static [&'static str]>,
+    /// Images must be laid out as Minecraft textures; see [`texture_frames`].
+    pub minecraft_texture: bool,
+}
+
+impl UploadPolicy {
+    pub fn allows_type(&self, content_type: &str) -> bool {
+        self.allowed_types
+            .is_none_or(|types| types.contains(&content_type))
+    }
+}
+
+/// Bytes of an object's head needed by [`sniff_content_type`] and
+/// [`png_dimensions`].
+pub const SNIFF_LEN: usize = 512;
+
+/// Media types recognised from their magic bytes.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+];
+
+/// Media type of `head` judged by its content alone. Text is reported as
+/// `text/plain` when it is UTF-8 without control bytes.
+pub fn sniff_content_type(head: &[u8]) -> Option<&'static str> {
+    if let Some((_, content_type)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(content_type);
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if looks_like_text(head) {
+        return Some("text/plain");
+    }
+    None
+}
+
+/// Whether `declared` is a truthful description of `head`. Types we cannot
+/// recognise, and `application/octet-stream`, are taken on trust.
+pub fn content_type_matches(declared: &str, head: &[u8]) -> bool {
+    let sniffed = sniff_content_type(head);
+    if declared.starts_with("text/") {
+        return sniffed == Some("text/plain");
+    }
+    let recognisable = MAGIC.iter().any(|(_, t)| *t == declared) || declared == "image/webp";
+    if recognisable {
+        return sniffed == Some(declared);
+    }
+    true
+}
+
+/// Width and height from a PNG's IHDR chunk.
+pub fn png_dimensions(head: &[u8]) -> Option<(u32, u32)> {
+    if !head.starts_with(b"\x89PNG\r\n\x1a\n") || head.len() < 24 || &head[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(head[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(head[20..24].try_into().ok()?);
+    Some((width, height))
+}
+
+/// Number of animation frames in a Minecraft texture of the given size, or
+/// why it is not one. Textures have a power-of-two width and are either
+/// square or a vertical strip of square frames, as `.mcmeta` animations use.
+pub fn texture_frames(width: u32, height: u32) -> Result<u32, String> {
+    if !width.is_power_of_two() {
+        return Err(format!(
+            "Texture width must be a power of two, got {}",
+            width
+        ));
+    }
+    if height == 0 || !height.is_multiple_of(width) {
+        return Err(format!(
+            "Textures must be square or a strip of square frames, got {}x{}",
+            width, height
+        ));
+    }
+    Ok(height / width)
+}
+
+/// Media type without parameters, lower-cased: `Text/Plain; charset=utf-8`
+/// becomes `text/plain`.
+pub fn content_type_essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn looks_like_text(head: &[u8]) -> bool {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        // The head may cut a multi-byte character short.
+        Err(err) if err.error_len().is_none() => {
+            std::str::from_utf8(&head[..err.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    !text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\x0c'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_SIG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    /// Signature plus an IHDR chunk header for a `width`x`height` PNG.
+    fn png_head(width: u32, height: u32) -> Vec<u8> {
+        let mut head = PNG_SIG.to_vec();
+        head.extend_from_slice(&13u32.to_be_bytes());
+        head.extend_from_slice(b"IHDR");
+        head.extend_from_slice(&width.to_be_bytes());
+        head.extend_from_slice(&height.to_be_bytes());
+        head.extend_from_slice(&[8, 6, 0, 0, 0]);
+        head
+    }
+
+    #[test]
+    fn sniffs_magic_bytes() {
+        let cases: &[(&[u8], Option<&str>)] = &[
+            (&png_head(16, 16), Some("image/png")),
+            (b"\xff\xd8\xff\xe0\x00\x10JFIF", Some("image/jpeg")),
+            (b"GIF87a\x01\x00", Some("image/gif")),
+            (b"GIF89a\x01\x00", Some("image/gif")),
+            (b"%PDF-1.7\n", Some("application/pdf")),
+            (b"RIFF\x24\x00\x00\x00WEBPVP8 ", Some("image/webp")),
+            (b"RIFF\x24\x00\x00\x00WAVEfmt ", None),
+            (b"hello\r\n\tworld", Some("text/plain")),
+            (b"\x00\x01\x02\x03", None),
+            (b"caf\xc3", Some("text/plain")),
+            (b"caf\xc3(", None),
+        ];
+        for (head, expected) in cases {
+            assert_eq!(sniff_content_type(head), *expected, "{:?}", head);
+        }
+    }
+
+    #[test]
+    fn accepts_text_cut_mid_character() {
+        // "日本" cut one byte into its second character.
+        let head = &"日本".as_bytes()[..4];
+        assert_eq!(sniff_content_type(head), Some("text/plain"));
+        assert!(content_type_matches("text/plain", head));
+    }
+
+    #[test]
+    fn checks_declared_types_against_content() {
+        let png = png_head(16, 16);
+        let cases: &[(&str, &[u8], bool)] = &[
+            ("image/png", &png, true),
+            ("image/jpeg", &png, false),
+            ("image/gif", b"GIF89a", true),
+            ("image/webp", b"GIF89a", false),
+            ("text/plain", b"plain words", true),
+            ("text/csv", b"a,b\n1,2", true),
+            ("text/plain", &png, false),
+            ("application/pdf", b"%PDF-1.4", true),
+            ("application/pdf", b"not a pdf", false),
+            ("application/octet-stream", &png, true),
+            ("application/zip", b"PK\x03\x04", true),
+        ];
+        for (declared, head, expected) in cases {
+            assert_eq!(
+                content_type_matches(declared, head),
+                *expected,
+                "{} {:?}",
+                declared,
+                head
+            );
+        }
+    }
+
+    #[test]
+    fn reads_png_dimensions() {
+        assert_eq!(png_dimensions(&png_head(16, 48)), Some((16, 48)));
+        assert_eq!(png_dimensions(&png_head(16, 48)[..23]), None);
+        assert_eq!(png_dimensions(b"GIF89a\x10\x00\x10\x00"), None);
+
+        let mut wrong_chunk = png_head(16, 16);
+        wrong_chunk[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(png_dimensions(&wrong_chunk), None);
+    }
+
+    #[test]
+    fn counts_texture_frames() {
+        let cases = [
+            (16, 16, Ok(1)),
+            (16, 64, Ok(4)),
+            (1, 1, Ok(1)),
+            (12, 12, Err(())),
+            (24, 48, Err(())),
+            (0, 0, Err(())),
+            (16, 0, Err(())),
+            (16, 24, Err(())),
+            (32, 16, Err(())),
+        ];
+        for (width, height, expected) in cases {
+            assert_eq!(
+                texture_frames(width, height).map_err(|_| ()),
+                expected,
+                "{}x{}",
+                width,
+                height
+            );
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use super::UploadPurpose;
+
+/// Storage limits in bytes; `None` leaves usage unlimited.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct FileQuota {
+    pub per_user_bytes: Option<i64>,
+    pub total_bytes: Option<i64>,
+}
+
+/// Bytes held by files, and by uploads still in progress at the size they
+/// declared. Quotas are checked against `used_bytes`. Files sharing content
+/// each count in full.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub file_count: i64,
+    pub file_bytes: i64,
+    pub upload_count: i64,
+    pub upload_bytes: i64,
+    pub used_bytes: i64,
+}
+
+impl StorageUsage {
+    pub fn new(file_count: i64, file_bytes: i64, upload_count: i64, upload_bytes: i64) -> Self {
+        Self {
+            file_count,
+            file_bytes,
+            upload_count,
+            upload_bytes,
+            used_bytes: file_bytes + upload_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PurposeUsage {
+    pub purpose: UploadPurpose,
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserUsage {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+}
+
+/// One user's usage against their quota.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserUsageReport {
+    pub user_id: String,
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+    pub quota_bytes: Option<i64>,
+    /// Unset without a quota; never negative.
+    pub remaining_bytes: Option<i64>,
+    pub by_purpose: Vec<PurposeUsage>,
+}
+
+/// Usage across all users against the global quota.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageUsageReport {
+    #[serde(flatten)]
+    pub usage: StorageUsage,
+    pub quota_bytes: Option<i64>,
+    pub remaining_bytes: Option<i64>,
+    pub per_user_quota_bytes: Option<i64>,
+    /// Bytes of stored objects, counting shared content once.
+    pub stored_bytes: i64,
+    pub by_purpose: Vec<PurposeUsage>,
+    /// Users using the most storage, largest first.
+    pub top_users: Vec<UserUsage>,
+}
+
+/// Bytes left under `quota` after `used`.
+pub fn remaining_bytes(quota: Option<i64>, used: i64) -> Option<i64> {
+    quota.map(|quota| (quota - used).max(0))
+}
@@ -3,7 +3,15 @@ use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Item {
+    /// Assigned by the server on creation.
+    #[serde(default)]
     pub id: String,
+    /// Human alias usable wherever the id is.
+    #[serde(default)]
+    pub slug: Option<String>,
+    /// Namespaced `namespace:path` key, unique across items.
+    #[serde(default)]
+    pub key: Option<String>,
     pub category: ItemCategory,
     pub version: i64,
     pub name: String,
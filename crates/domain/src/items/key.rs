@@ -0,0 +1,94 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Namespace assumed for keys given without one, as Minecraft does with
+/// `minecraft:`.
+pub const DEFAULT_ITEM_NAMESPACE: &str = "natsume";
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Normalizes `raw` into a `namespace:path` resource location, or explains
+/// why it is not one. Namespaces allow `[a-z0-9_.-]`, paths additionally `/`.
+pub fn parse_item_key(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    let (namespace, path) = match raw.split_once(':') {
+        Some((namespace, path)) => (namespace, path),
+        None => (DEFAULT_ITEM_NAMESPACE, raw),
+    };
+
+    if namespace.is_empty() || path.is_empty() {
+        return Err(format!("'{}' must have the form namespace:path", raw));
+    }
+    if let Some(c) = namespace.chars().find(|&c| !is_namespace_char(c)) {
+        return Err(format!(
+            "Invalid character '{}' in namespace '{}'",
+            c, namespace
+        ));
+    }
+    if let Some(c) = path.chars().find(|&c| !is_path_char(c)) {
+        return Err(format!("Invalid character '{}' in path '{}'", c, path));
+    }
+
+    let key = format!("{}:{}", namespace, path);
+    if key.len() > MAX_KEY_LEN {
+        return Err(format!("Keys are limited to {} characters", MAX_KEY_LEN));
+    }
+    Ok(key)
+}
+
+fn is_namespace_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | '0'..='9' | '_' | '.' | '-')
+}
+
+fn is_path_char(c: char) -> bool {
+    is_namespace_char(c) || c == '/'
+}
+
+/// Key an item was known by before a rename. Lookups by it still resolve.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemKeyAlias {
+    pub key: String,
+    pub item_id: String,
+    pub renamed_at: NaiveDateTime,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_KEY_LEN, parse_item_key};
+
+    #[test]
+    fn defaults_the_namespace() {
+        assert_eq!(parse_item_key("iron_sword").unwrap(), "natsume:iron_sword");
+        assert_eq!(parse_item_key("  mod:gem  ").unwrap(), "mod:gem");
+    }
+
+    #[test]
+    fn rejects_empty_namespace_or_path() {
+        for raw in [":gem", "mod:", ":", "", "   "] {
+            assert!(parse_item_key(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn allows_slashes_only_in_the_path() {
+        assert_eq!(
+            parse_item_key("mod:tools/pickaxe").unwrap(),
+            "mod:tools/pickaxe"
+        );
+        assert!(parse_item_key("mod/tools:pickaxe").is_err());
+    }
+
+    #[test]
+    fn rejects_uppercase_and_other_characters() {
+        for raw in ["Mod:gem", "mod:Gem", "mod:gem!", "mod:a b", "mod:a:b"] {
+            assert!(parse_item_key(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn limits_the_key_length() {
+        let path = "a".repeat(MAX_KEY_LEN - "mod:".len());
+        assert!(parse_item_key(&format!("mod:{}", path)).is_ok());
+        assert!(parse_item_key(&format!("mod:{}a", path)).is_err());
+    }
+}
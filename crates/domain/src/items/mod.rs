@@ -1,11 +1,13 @@
 pub mod armor;
 pub mod base;
 pub mod food;
+pub mod key;
 pub mod tool;
 pub mod weapon;
 
 pub use armor::*;
 pub use base::*;
 pub use food::*;
+pub use key::*;
 pub use tool::*;
 pub use weapon::*;
@@ -1,3 +1,4 @@
+pub mod alerts;
 pub mod files;
 pub mod items;
 pub mod recipes;
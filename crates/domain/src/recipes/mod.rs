@@ -2,7 +2,12 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Recipe {
+    /// Assigned by the server on creation.
+    #[serde(default)]
     pub id: String,
+    /// Human alias usable wherever the id is.
+    #[serde(default)]
+    pub slug: Option<String>,
     pub category: String,
     pub inputs: Vec<RecipeInput>,
     pub output: RecipeOutput,
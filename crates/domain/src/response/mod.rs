@@ -12,3 +12,26 @@ pub struct ApiErrorResponse {
     pub code: &'static str,
     pub message: String,
 }
+
+/// One page of a list, returned as `data` of [`ApiResponse`].
+#[derive(Serialize)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl<T> Paginated<T> {
+    pub fn new(items: Vec<T>, page: i64, per_page: i64, total: i64) -> Self {
+        let per_page = per_page.max(1);
+        Self {
+            items,
+            page,
+            per_page,
+            total,
+            total_pages: (total + per_page - 1) / per_page,
+        }
+    }
+}
@@ -12,6 +12,8 @@ pub struct StatusRecord {
     pub latency: Option<i32>,
     pub players: Option<Players>,
     pub timestamp: i64,
+    #[serde(default)]
+    pub probe_duration_ms: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,3 +35,140 @@ pub struct StatusSummary {
     pub timestamp: i64,
     pub history: Vec<StatusRecord>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryResolution {
+    Raw,
+    Hour,
+    Day,
+}
+
+impl HistoryResolution {
+    pub fn bucket_secs(&self) -> i64 {
+        match self {
+            HistoryResolution::Raw => 0,
+            HistoryResolution::Hour => 60 * 60,
+            HistoryResolution::Day => 24 * 60 * 60,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "raw" => Some(HistoryResolution::Raw),
+            "hour" | "hourly" => Some(HistoryResolution::Hour),
+            "day" | "daily" => Some(HistoryResolution::Day),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryResolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                HistoryResolution::Raw => "raw",
+                HistoryResolution::Hour => "hour",
+                HistoryResolution::Day => "day",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusHistoryPoint {
+    pub timestamp: i64,
+    pub samples: i32,
+    pub uptime_pct: f64,
+    pub players_min: Option<i32>,
+    pub players_avg: Option<f64>,
+    pub players_max: Option<i32>,
+    pub latency_min: Option<i32>,
+    pub latency_avg: Option<f64>,
+    pub latency_max: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusHistory {
+    pub id: String,
+    pub resolution: HistoryResolution,
+    pub from: i64,
+    pub to: i64,
+    pub points: Vec<StatusHistoryPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusIncident {
+    pub id: i64,
+    pub server_id: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    pub failed_probes: i32,
+    pub notes: Option<String>,
+    pub notes_updated_by: Option<String>,
+    pub notes_updated_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UptimeWindow {
+    pub window: String,
+    pub from: i64,
+    pub to: i64,
+    pub samples: i64,
+    pub uptime_pct: Option<f64>,
+    pub incidents: i64,
+    pub downtime_secs: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UptimeSummary {
+    pub id: String,
+    pub online: Option<bool>,
+    pub open_incident: Option<StatusIncident>,
+    pub windows: Vec<UptimeWindow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkServer {
+    pub id: String,
+    pub online: bool,
+    pub players: Option<Players>,
+    pub timestamp: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkPeak {
+    pub day: i64,
+    pub players: i32,
+    pub at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkStatus {
+    pub servers_total: usize,
+    pub servers_online: usize,
+    pub players: Players,
+    pub today_peak: Option<NetworkPeak>,
+    pub servers: Vec<NetworkServer>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkHistoryPoint {
+    pub timestamp: i64,
+    /// Sum of each server's average player count within the bucket.
+    pub players_avg: f64,
+    /// Sum of each server's highest player count within the bucket.
+    pub players_max: i64,
+    pub servers_online: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkHistory {
+    pub from: i64,
+    pub to: i64,
+    pub bucket_secs: i64,
+    pub points: Vec<NetworkHistoryPoint>,
+    pub daily_peaks: Vec<NetworkPeak>,
+}
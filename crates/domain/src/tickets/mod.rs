@@ -3,18 +3,506 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TicketMessage {
+    #[serde(default)]
+    pub id: i64,
     pub sender: String,
     pub content: String,
     pub sent_at: NaiveDateTime,
+    #[serde(default)]
+    pub edited_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub attachments: Vec<TicketAttachment>,
+    /// Staff-only note; never shown to the player.
+    #[serde(default)]
+    pub internal: bool,
+    /// Canned response the message was written from, if any.
+    #[serde(default)]
+    pub canned_response_id: Option<i64>,
+}
+
+/// Reply to append to a ticket, as received from a handler.
+#[derive(Debug, Clone, Default)]
+pub struct NewTicketMessage {
+    pub sender: String,
+    pub content: String,
+    pub attachments: Vec<String>,
+    pub internal: bool,
+    pub canned_response_id: Option<i64>,
+    /// Values for the `{player_name}` and `{staff_name}` placeholders.
+    pub player_name: Option<String>,
+    pub staff_name: Option<String>,
+    /// Posted in the ticket's Discord thread, so it is not mirrored back.
+    pub via_discord: bool,
+}
+
+/// File attached to a ticket message. The download URL is not included; it is
+/// served by the attachment endpoint after checking the caller's access.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TicketAttachment {
+    pub file_id: String,
+    #[serde(default)]
+    pub filename: String,
+    #[serde(default)]
+    pub content_type: String,
+    #[serde(default)]
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketStatus {
+    #[default]
+    Open,
+    AwaitingStaff,
+    AwaitingPlayer,
+    Resolved,
+    Closed,
+    Reopened,
+}
+
+impl TicketStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(TicketStatus::Open),
+            "awaiting_staff" => Some(TicketStatus::AwaitingStaff),
+            "awaiting_player" => Some(TicketStatus::AwaitingPlayer),
+            "resolved" => Some(TicketStatus::Resolved),
+            "closed" => Some(TicketStatus::Closed),
+            "reopened" => Some(TicketStatus::Reopened),
+            _ => None,
+        }
+    }
+
+    /// Whether the lifecycle allows moving from `self` to `to`. Closed and
+    /// resolved tickets can only come back through `reopened`.
+    pub fn can_transition_to(&self, to: TicketStatus) -> bool {
+        use TicketStatus::*;
+
+        matches!(
+            (self, to),
+            (
+                Open | Reopened,
+                AwaitingStaff | AwaitingPlayer | Resolved | Closed
+            ) | (AwaitingStaff, AwaitingPlayer | Resolved | Closed)
+                | (AwaitingPlayer, AwaitingStaff | Resolved | Closed)
+                | (Resolved, Closed | Reopened)
+                | (Closed, Reopened)
+        )
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TicketStatus::Closed)
+    }
+}
+
+impl std::fmt::Display for TicketStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TicketStatus::Open => "open",
+                TicketStatus::AwaitingStaff => "awaiting_staff",
+                TicketStatus::AwaitingPlayer => "awaiting_player",
+                TicketStatus::Resolved => "resolved",
+                TicketStatus::Closed => "closed",
+                TicketStatus::Reopened => "reopened",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl TicketPriority {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(TicketPriority::Low),
+            "normal" => Some(TicketPriority::Normal),
+            "high" => Some(TicketPriority::High),
+            "urgent" => Some(TicketPriority::Urgent),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TicketPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TicketPriority::Low => "low",
+                TicketPriority::Normal => "normal",
+                TicketPriority::High => "high",
+                TicketPriority::Urgent => "urgent",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TicketCategory {
+    Bug,
+    Appeal,
+    Purchase,
+    Report,
+}
+
+impl TicketCategory {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "bug" => Some(TicketCategory::Bug),
+            "appeal" => Some(TicketCategory::Appeal),
+            "purchase" => Some(TicketCategory::Purchase),
+            "report" => Some(TicketCategory::Report),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TicketCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TicketCategory::Bug => "bug",
+                TicketCategory::Appeal => "appeal",
+                TicketCategory::Purchase => "purchase",
+                TicketCategory::Report => "report",
+            }
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ticket {
+    /// Assigned by the server on creation.
+    #[serde(default)]
     pub id: String,
+    /// Human alias usable wherever the id is.
+    #[serde(default)]
+    pub slug: Option<String>,
     pub user_id: String,
     pub title: String,
-    pub status: String,
+    #[serde(default)]
+    pub status: TicketStatus,
+    /// Discord id of the staff member handling the ticket.
+    #[serde(default)]
+    pub assignee_id: Option<String>,
+    #[serde(default)]
+    pub priority: TicketPriority,
+    #[serde(default)]
+    pub category: Option<TicketCategory>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Discord thread mirroring the ticket, set by the notifier.
+    #[serde(default)]
+    pub discord_thread_id: Option<String>,
+    #[serde(default)]
     pub messages: Vec<TicketMessage>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TicketMessageRevision {
+    pub id: i64,
+    pub message_id: i64,
+    /// `edit` or `delete`.
+    pub action: String,
+    /// Content of the message before this revision was applied.
+    pub content: String,
+    pub edited_by: Option<String>,
+    pub edited_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TicketStatusTransition {
+    pub id: i64,
+    pub ticket_id: String,
+    /// `None` for the transition recorded when the ticket is created.
+    pub from_status: Option<TicketStatus>,
+    pub to_status: TicketStatus,
+    /// Discord id or username of whoever caused the change, `system` for
+    /// automatic transitions.
+    pub actor: String,
+    pub reason: Option<String>,
+    pub transitioned_at: NaiveDateTime,
+}
+
+/// Query filters for listing tickets; every field is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TicketFilter {
+    pub user_id: Option<String>,
+    pub status: Option<TicketStatus>,
+    pub assignee_id: Option<String>,
+    pub priority: Option<TicketPriority>,
+    pub category: Option<TicketCategory>,
+    pub label: Option<String>,
+    /// Leave out resolved and closed tickets.
+    #[serde(default)]
+    pub active_only: bool,
+    /// Unix timestamps bounding `created_at`, `created_before` exclusive.
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    /// Full-text search over titles and message contents, in web search
+    /// syntax (`"exact phrase"`, `-excluded`, `or`).
+    pub q: Option<String>,
+    /// Also match the text of internal notes. Set by the usecase for staff.
+    #[serde(skip)]
+    pub include_internal: bool,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl TicketFilter {
+    pub const DEFAULT_PER_PAGE: i64 = 50;
+    pub const MAX_PER_PAGE: i64 = 200;
+
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page
+            .unwrap_or(Self::DEFAULT_PER_PAGE)
+            .clamp(1, Self::MAX_PER_PAGE)
+    }
+}
+
+/// Staff member taking part in the round-robin for one category.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TicketStaff {
+    pub discord_id: String,
+    pub category: TicketCategory,
+    #[serde(default = "default_true")]
+    pub active: bool,
+    #[serde(default)]
+    pub last_assigned_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CannedResponse {
+    #[serde(default)]
+    pub id: i64,
+    pub name: String,
+    /// Limits the response to one category in staff tooling; `None` fits all.
+    #[serde(default)]
+    pub category: Option<TicketCategory>,
+    /// Text with `{player_name}`, `{ticket_id}`, `{ticket_title}` and
+    /// `{staff_name}` placeholders.
+    pub content: String,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl CannedResponse {
+    pub fn apply(&mut self, patch: CannedResponsePatch) {
+        if let Some(name) = patch.name {
+            self.name = name;
+        }
+        if let Some(category) = patch.category {
+            self.category = category;
+        }
+        if let Some(content) = patch.content {
+            self.content = content;
+        }
+    }
+
+    pub fn render(&self, ticket: &Ticket, player_name: &str, staff_name: &str) -> String {
+        self.content
+            .replace("{player_name}", player_name)
+            .replace("{ticket_id}", &ticket.id)
+            .replace("{ticket_title}", &ticket.title)
+            .replace("{staff_name}", staff_name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CannedResponsePatch {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "crate::alerts::deserialize_nullable")]
+    pub category: Option<Option<TicketCategory>>,
+    pub content: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Targets a ticket is measured against; both in seconds from creation.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SlaTargets {
+    pub first_response_secs: i64,
+    pub resolution_secs: i64,
+}
+
+impl Default for SlaTargets {
+    fn default() -> Self {
+        Self {
+            first_response_secs: 4 * 60 * 60,
+            resolution_secs: 72 * 60 * 60,
+        }
+    }
+}
+
+/// Timestamps of one ticket that the SLA report is computed from.
+#[derive(Debug, Clone)]
+pub struct TicketSlaSample {
+    pub ticket_id: String,
+    pub category: Option<TicketCategory>,
+    pub assignee_id: Option<String>,
+    pub status: TicketStatus,
+    pub created_at: NaiveDateTime,
+    /// First public message from someone other than the ticket's player.
+    pub first_response_at: Option<NaiveDateTime>,
+    /// Last move into `resolved`/`closed`, if that is where the ticket is now.
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+/// Response and resolution times for a group of tickets. `key` is the
+/// category or staff id the group is for, `None` for the catch-all group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SlaMetrics {
+    pub key: Option<String>,
+    pub tickets: i64,
+    pub responded: i64,
+    pub first_response_avg_secs: Option<i64>,
+    pub first_response_p90_secs: Option<i64>,
+    /// Tickets answered late, or still unanswered past the target.
+    pub first_response_breaches: i64,
+    pub resolved: i64,
+    pub resolution_avg_secs: Option<i64>,
+    pub resolution_p90_secs: Option<i64>,
+    pub resolution_breaches: i64,
+    /// Whether the group's average is over its target.
+    pub first_response_breached: bool,
+    pub resolution_breached: bool,
+}
+
+/// Currently active tickets grouped by how long ago they were opened.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BacklogAge {
+    pub key: Option<String>,
+    pub under_1d: i64,
+    pub from_1d_to_3d: i64,
+    pub from_3d_to_7d: i64,
+    pub over_7d: i64,
+    pub oldest_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaBreach {
+    pub ticket_id: String,
+    pub category: Option<TicketCategory>,
+    pub assignee_id: Option<String>,
+    /// `first_response` or `resolution`.
+    pub kind: String,
+    pub elapsed_secs: i64,
+    pub target_secs: i64,
+    /// The ticket is still waiting, so `elapsed_secs` keeps growing.
+    pub ongoing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaReport {
+    pub from: i64,
+    pub to: i64,
+    pub targets: SlaTargets,
+    pub overall: SlaMetrics,
+    pub by_category: Vec<SlaMetrics>,
+    pub by_staff: Vec<SlaMetrics>,
+    /// Backlog is the current state and ignores `from`/`to`.
+    pub backlog_by_category: Vec<BacklogAge>,
+    pub backlog_by_staff: Vec<BacklogAge>,
+    pub breaches: Vec<SlaBreach>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TicketStatus::{self, *};
+
+    const ALL: [TicketStatus; 6] = [
+        Open,
+        AwaitingStaff,
+        AwaitingPlayer,
+        Resolved,
+        Closed,
+        Reopened,
+    ];
+
+    const ALLOWED: &[(TicketStatus, TicketStatus)] = &[
+        (Open, AwaitingStaff),
+        (Open, AwaitingPlayer),
+        (Open, Resolved),
+        (Open, Closed),
+        (Reopened, AwaitingStaff),
+        (Reopened, AwaitingPlayer),
+        (Reopened, Resolved),
+        (Reopened, Closed),
+        (AwaitingStaff, AwaitingPlayer),
+        (AwaitingStaff, Resolved),
+        (AwaitingStaff, Closed),
+        (AwaitingPlayer, AwaitingStaff),
+        (AwaitingPlayer, Resolved),
+        (AwaitingPlayer, Closed),
+        (Resolved, Closed),
+        (Resolved, Reopened),
+        (Closed, Reopened),
+    ];
+
+    #[test]
+    fn allows_lifecycle_transitions() {
+        for &(from, to) in ALLOWED {
+            assert!(
+                from.can_transition_to(to),
+                "{} -> {} should be allowed",
+                from,
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn forbids_everything_else() {
+        for from in ALL {
+            for to in ALL {
+                if ALLOWED.contains(&(from, to)) {
+                    continue;
+                }
+                assert!(
+                    !from.can_transition_to(to),
+                    "{} -> {} should be forbidden",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn forbids_staying_in_place_and_leaving_closed_directly() {
+        for status in ALL {
+            assert!(!status.can_transition_to(status));
+        }
+        assert!(!Closed.can_transition_to(Open));
+        assert!(!Closed.can_transition_to(AwaitingStaff));
+        assert!(!Resolved.can_transition_to(AwaitingPlayer));
+        assert!(!Open.can_transition_to(Reopened));
+    }
+}
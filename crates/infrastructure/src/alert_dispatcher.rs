@@ -0,0 +1,227 @@
+use crate::{
+    repositorys::alert::{AlertRepository, PostgresAlertRepository},
+    status_watcher::{AlertConfig, ServerConfig},
+    webhook::{WebhookOutcome, WebhookSender},
+};
+use chrono::{DateTime, Utc};
+use domain::alerts::{AlertEvent, AlertKind, AlertRule, AlertWindowStats};
+use serde_json::{Value, json};
+use shared::error::AppResult;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const MIN_WINDOW_SECS: i64 = 60;
+
+pub async fn start_alert_dispatcher(pool: PgPool, config: ServerConfig) -> AppResult<()> {
+    let repo = PostgresAlertRepository::new(pool);
+    let sender = webhook_sender(&config.alerts);
+    let server_ids: Vec<String> = config.servers.iter().map(|s| s.id.clone()).collect();
+    let interval = Duration::from_secs(config.alerts.interval_secs.max(1));
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = evaluate_rules(&repo, &sender, &config.alerts, &server_ids).await {
+                tracing::error!("Failed to evaluate alert rules: {}", err);
+            }
+            sleep(interval).await;
+        }
+    });
+
+    Ok(())
+}
+
+pub fn webhook_sender(config: &AlertConfig) -> WebhookSender {
+    WebhookSender::new(
+        Duration::from_millis(config.timeout_ms.max(1)),
+        config.max_attempts,
+    )
+}
+
+async fn evaluate_rules(
+    repo: &PostgresAlertRepository,
+    sender: &WebhookSender,
+    config: &AlertConfig,
+    server_ids: &[String],
+) -> AppResult<()> {
+    let rules = repo.list_rules(true).await?;
+    let now = Utc::now().timestamp();
+
+    for rule in rules {
+        let targets: Vec<&String> = match &rule.server_id {
+            Some(id) => server_ids.iter().filter(|s| *s == id).collect(),
+            None => server_ids.iter().collect(),
+        };
+
+        for server_id in targets {
+            let since = now - i64::from(rule.duration_secs).max(MIN_WINDOW_SECS);
+            let stats = repo.window_stats(server_id, since).await?;
+
+            let Some(firing) = evaluate(&rule, &stats, now) else {
+                continue;
+            };
+            let state = repo.find_state(rule.id, server_id).await?;
+            if firing == state.firing {
+                // The change went back before its notification got through.
+                if state.delivery_failures > 0 {
+                    repo.clear_failed_deliveries(rule.id, server_id).await?;
+                }
+                continue;
+            }
+            if state.retry_at.is_some_and(|at| now < at) {
+                continue;
+            }
+
+            let event = if firing {
+                AlertEvent::Firing
+            } else {
+                AlertEvent::Resolved
+            };
+            if event == AlertEvent::Resolved && !rule.notify_recovery {
+                repo.set_firing(rule.id, server_id, firing, now).await?;
+                continue;
+            }
+
+            let payload = alert_payload(&rule, server_id, event, &stats, now);
+            let outcome = sender
+                .send(
+                    &rule.webhook_url,
+                    rule.secret.as_deref(),
+                    &event.to_string(),
+                    &payload,
+                )
+                .await;
+
+            // A failed notification is sent again on later passes, backing
+            // off each time, until it is delivered or given up. Only the
+            // final outcome is recorded as a delivery.
+            if let Some(err) = &outcome.error {
+                let failures = state.delivery_failures + 1;
+                if outcome.is_retryable() && failures < config.max_deliveries as i32 {
+                    let delay = config.retry_delay(failures as u32);
+                    tracing::warn!(
+                        "Alert webhook for rule {} ({}) failed, retrying in {}s: {}",
+                        rule.id,
+                        server_id,
+                        delay,
+                        err
+                    );
+                    repo.record_failed_delivery(
+                        rule.id,
+                        server_id,
+                        outcome.attempts,
+                        now + delay as i64,
+                    )
+                    .await?;
+                    continue;
+                }
+                tracing::error!(
+                    "Giving up on {} alert webhook for rule {} ({}) after {} passes: {}",
+                    event,
+                    rule.id,
+                    server_id,
+                    failures,
+                    err
+                );
+            }
+
+            let outcome = WebhookOutcome {
+                attempts: state.delivery_attempts + outcome.attempts,
+                ..outcome
+            };
+            repo.insert_delivery(rule.id, server_id, event, &payload, &outcome)
+                .await?;
+            repo.set_firing(rule.id, server_id, firing, now).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the rule's condition currently holds, or `None` when there
+/// are not enough samples to decide and the previous state should be kept.
+pub fn evaluate(rule: &AlertRule, stats: &AlertWindowStats, now: i64) -> Option<bool> {
+    let threshold = rule.threshold.unwrap_or(0);
+
+    match rule.kind {
+        AlertKind::Offline => match stats.latest_online? {
+            true => Some(false),
+            false => {
+                let down_since = stats.last_online_at.or(stats.first_seen_at)?;
+                Some(now - down_since >= i64::from(rule.duration_secs))
+            }
+        },
+        AlertKind::PlayersAbove => {
+            if stats.samples == 0 {
+                return None;
+            }
+            Some(
+                stats.online_samples == stats.samples
+                    && stats.min_players.is_some_and(|p| p > threshold),
+            )
+        }
+        AlertKind::LatencyAbove => {
+            if stats.samples == 0 {
+                return None;
+            }
+            Some(stats.avg_latency.is_some_and(|l| l > f64::from(threshold)))
+        }
+    }
+}
+
+/// Builds a Discord-webhook-compatible body (`username` + `embeds`).
+pub fn alert_payload(
+    rule: &AlertRule,
+    server_id: &str,
+    event: AlertEvent,
+    stats: &AlertWindowStats,
+    now: i64,
+) -> Value {
+    let (label, color) = match event {
+        AlertEvent::Firing => ("FIRING", 0xE74C3C),
+        AlertEvent::Resolved => ("RESOLVED", 0x2ECC71),
+        AlertEvent::Test => ("TEST", 0x3498DB),
+    };
+
+    let condition = match rule.kind {
+        AlertKind::Offline => format!("offline for at least {}s", rule.duration_secs),
+        AlertKind::PlayersAbove => format!(
+            "more than {} players for {}s",
+            rule.threshold.unwrap_or(0),
+            rule.duration_secs
+        ),
+        AlertKind::LatencyAbove => format!(
+            "average latency above {}ms over {}s",
+            rule.threshold.unwrap_or(0),
+            rule.duration_secs
+        ),
+    };
+
+    let mut fields = vec![
+        json!({ "name": "Server", "value": server_id, "inline": true }),
+        json!({ "name": "Rule", "value": rule.name, "inline": true }),
+        json!({ "name": "Condition", "value": condition, "inline": false }),
+    ];
+    if let Some(players) = stats.min_players {
+        fields
+            .push(json!({ "name": "Players (min)", "value": players.to_string(), "inline": true }));
+    }
+    if let Some(latency) = stats.avg_latency {
+        fields.push(json!({ "name": "Latency (avg)", "value": format!("{:.0}ms", latency), "inline": true }));
+    }
+
+    let timestamp = DateTime::<Utc>::from_timestamp(now, 0)
+        .unwrap_or_else(Utc::now)
+        .to_rfc3339();
+
+    json!({
+        "username": "Natsume Status",
+        "embeds": [{
+            "title": format!("[{}] {}: {}", label, server_id, rule.kind),
+            "description": condition,
+            "color": color,
+            "fields": fields,
+            "timestamp": timestamp,
+        }],
+    })
+}
@@ -0,0 +1,161 @@
+use domain::tickets::{Ticket, TicketMessage, TicketStatusTransition};
+use serde_json::{Value, json};
+use shared::error::AppResult;
+use std::time::Duration;
+
+/// Discord caps embed descriptions at 4096 characters.
+const MAX_DESCRIPTION_CHARS: usize = 4000;
+
+/// Posts ticket activity through a Discord webhook. With `use_threads` the
+/// webhook must point at a forum channel: every ticket opens its own post and
+/// later events go into that thread.
+pub struct DiscordTicketWebhook {
+    client: reqwest::Client,
+    webhook_url: String,
+    use_threads: bool,
+}
+
+impl DiscordTicketWebhook {
+    pub fn new(webhook_url: String, use_threads: bool, timeout: Duration) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            webhook_url,
+            use_threads,
+        }
+    }
+
+    /// Returns the id of the thread created for the ticket, if any. Only the
+    /// first message the player can see is posted; internal notes never are.
+    pub async fn ticket_created(&self, ticket: &Ticket) -> AppResult<Option<String>> {
+        let opening = ticket
+            .messages
+            .iter()
+            .find(|m| !m.internal)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+
+        let mut payload = message_payload(json!({
+            "title": format!("New ticket: {}", ticket.title),
+            "description": truncate(opening),
+            "color": 0x3498DB,
+            "fields": [
+                { "name": "Ticket", "value": ticket.id, "inline": true },
+                { "name": "Player", "value": ticket.user_id, "inline": true },
+                {
+                    "name": "Category",
+                    "value": ticket.category.map(|c| c.to_string()).unwrap_or_else(|| "-".into()),
+                    "inline": true
+                },
+                { "name": "Priority", "value": ticket.priority.to_string(), "inline": true },
+            ],
+        }));
+
+        if self.use_threads {
+            payload["thread_name"] = json!(thread_name(ticket));
+        }
+
+        let response = self.post(None, &payload).await?;
+        if !self.use_threads {
+            return Ok(None);
+        }
+
+        Ok(response["channel_id"].as_str().map(str::to_string))
+    }
+
+    pub async fn staff_replied(&self, ticket: &Ticket, message: &TicketMessage) -> AppResult<()> {
+        let payload = message_payload(json!({
+            "author": { "name": message.sender },
+            "description": truncate(&message.content),
+            "color": 0x2ECC71,
+            "footer": { "text": format!("Ticket {}", ticket.id) },
+        }));
+
+        self.post(ticket.discord_thread_id.as_deref(), &payload)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn status_changed(
+        &self,
+        ticket: &Ticket,
+        transition: &TicketStatusTransition,
+    ) -> AppResult<()> {
+        let from = transition
+            .from_status
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "-".into());
+
+        let mut embed = json!({
+            "title": format!("{} → {}", from, transition.to_status),
+            "color": 0xF1C40F,
+            "fields": [
+                { "name": "By", "value": transition.actor, "inline": true },
+            ],
+            "footer": { "text": format!("Ticket {}", ticket.id) },
+        });
+        if let Some(reason) = &transition.reason {
+            embed["description"] = json!(truncate(reason));
+        }
+
+        self.post(ticket.discord_thread_id.as_deref(), &message_payload(embed))
+            .await?;
+        Ok(())
+    }
+
+    async fn post(&self, thread_id: Option<&str>, payload: &Value) -> AppResult<Value> {
+        let mut query = vec![("wait", "true")];
+        if let Some(thread_id) = thread_id.filter(|_| self.use_threads) {
+            query.push(("thread_id", thread_id));
+        }
+
+        let res = self
+            .client
+            .post(&self.webhook_url)
+            .query(&query)
+            .json(payload)
+            .send()
+            .await?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow::anyhow!(
+                "Discord webhook returned {}: {}",
+                status,
+                body
+            ));
+        }
+
+        Ok(res.json().await?)
+    }
+}
+
+fn message_payload(embed: Value) -> Value {
+    json!({
+        "username": "Natsume Tickets",
+        "allowed_mentions": { "parse": [] },
+        "embeds": [embed],
+    })
+}
+
+/// Forum post titles are limited to 100 characters.
+fn thread_name(ticket: &Ticket) -> String {
+    format!("[{}] {}", ticket.id, ticket.title)
+        .chars()
+        .take(100)
+        .collect()
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_DESCRIPTION_CHARS {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(MAX_DESCRIPTION_CHARS).collect();
+    truncated.push('…');
+    truncated
+}
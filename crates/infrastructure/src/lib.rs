@@ -1,3 +1,8 @@
+pub mod alert_dispatcher;
+pub mod discord_tickets;
 pub mod postgres;
 pub mod repositorys;
+pub mod status_rollup;
 pub mod status_watcher;
+pub mod storage;
+pub mod webhook;
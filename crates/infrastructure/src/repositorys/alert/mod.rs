@@ -0,0 +1,351 @@
+use crate::webhook::WebhookOutcome;
+use async_trait::async_trait;
+use domain::alerts::{
+    AlertDelivery, AlertEvent, AlertKind, AlertRule, AlertState, AlertWindowStats,
+};
+use serde_json::Value;
+use shared::error::AppResult;
+use sqlx::{PgPool, Row, postgres::PgRow};
+
+#[async_trait]
+pub trait AlertRepository {
+    async fn list_rules(&self, enabled_only: bool) -> AppResult<Vec<AlertRule>>;
+    async fn find_rule(&self, id: i64) -> AppResult<AlertRule>;
+    async fn insert_rule(&self, rule: &AlertRule) -> AppResult<i64>;
+    async fn update_rule(&self, id: i64, rule: &AlertRule) -> AppResult<()>;
+    async fn delete_rule(&self, id: i64) -> AppResult<()>;
+
+    async fn find_state(&self, rule_id: i64, server_id: &str) -> AppResult<AlertState>;
+    /// Moves the state on and forgets any failed deliveries.
+    async fn set_firing(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        firing: bool,
+        at: i64,
+    ) -> AppResult<()>;
+    /// Counts a failed pass of the pending notification, which made
+    /// `attempts` requests, and holds further passes off until `retry_at`.
+    async fn record_failed_delivery(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        attempts: i32,
+        retry_at: i64,
+    ) -> AppResult<()>;
+    /// Forgets failed deliveries of a notification that is no longer needed.
+    async fn clear_failed_deliveries(&self, rule_id: i64, server_id: &str) -> AppResult<()>;
+
+    async fn insert_delivery(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        event: AlertEvent,
+        payload: &Value,
+        outcome: &WebhookOutcome,
+    ) -> AppResult<()>;
+    async fn list_deliveries(
+        &self,
+        rule_id: Option<i64>,
+        limit: i64,
+    ) -> AppResult<Vec<AlertDelivery>>;
+
+    async fn window_stats(&self, server_id: &str, since: i64) -> AppResult<AlertWindowStats>;
+}
+
+pub struct PostgresAlertRepository {
+    pub pool: PgPool,
+}
+
+impl PostgresAlertRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    fn rule_from_row(row: &PgRow) -> AppResult<AlertRule> {
+        let kind: String = row.get("kind");
+        Ok(AlertRule {
+            id: row.get("id"),
+            name: row.get("name"),
+            server_id: row.get("server_id"),
+            kind: AlertKind::parse(&kind)
+                .ok_or_else(|| anyhow::anyhow!("Invalid alert kind '{}'", kind))?,
+            threshold: row.get("threshold"),
+            duration_secs: row.get("duration_secs"),
+            webhook_url: row.get("webhook_url"),
+            secret: row.get("secret"),
+            enabled: row.get("enabled"),
+            notify_recovery: row.get("notify_recovery"),
+        })
+    }
+}
+
+#[async_trait]
+impl AlertRepository for PostgresAlertRepository {
+    async fn list_rules(&self, enabled_only: bool) -> AppResult<Vec<AlertRule>> {
+        let rows = sqlx::query("SELECT * FROM alert_rules WHERE enabled OR NOT $1 ORDER BY id")
+            .bind(enabled_only)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(Self::rule_from_row).collect()
+    }
+
+    async fn find_rule(&self, id: i64) -> AppResult<AlertRule> {
+        let row = sqlx::query("SELECT * FROM alert_rules WHERE id = $1")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Self::rule_from_row(&row)
+    }
+
+    async fn insert_rule(&self, rule: &AlertRule) -> AppResult<i64> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO alert_rules (
+                name, server_id, kind, threshold, duration_secs,
+                webhook_url, secret, enabled, notify_recovery
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id
+            "#,
+        )
+        .bind(&rule.name)
+        .bind(&rule.server_id)
+        .bind(rule.kind.to_string())
+        .bind(rule.threshold)
+        .bind(rule.duration_secs)
+        .bind(&rule.webhook_url)
+        .bind(&rule.secret)
+        .bind(rule.enabled)
+        .bind(rule.notify_recovery)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("id"))
+    }
+
+    async fn update_rule(&self, id: i64, rule: &AlertRule) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE alert_rules SET
+                name = $2, server_id = $3, kind = $4, threshold = $5, duration_secs = $6,
+                webhook_url = $7, secret = $8, enabled = $9, notify_recovery = $10,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&rule.name)
+        .bind(&rule.server_id)
+        .bind(rule.kind.to_string())
+        .bind(rule.threshold)
+        .bind(rule.duration_secs)
+        .bind(&rule.webhook_url)
+        .bind(&rule.secret)
+        .bind(rule.enabled)
+        .bind(rule.notify_recovery)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_rule(&self, id: i64) -> AppResult<()> {
+        sqlx::query("DELETE FROM alert_rules WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_state(&self, rule_id: i64, server_id: &str) -> AppResult<AlertState> {
+        let row = sqlx::query(
+            r#"
+            SELECT firing, delivery_failures, delivery_attempts, retry_at
+            FROM alert_states
+            WHERE rule_id = $1 AND server_id = $2
+            "#,
+        )
+        .bind(rule_id)
+        .bind(server_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row
+            .map(|row| AlertState {
+                firing: row.get("firing"),
+                delivery_failures: row.get("delivery_failures"),
+                delivery_attempts: row.get("delivery_attempts"),
+                retry_at: row.get("retry_at"),
+            })
+            .unwrap_or_default())
+    }
+
+    async fn set_firing(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        firing: bool,
+        at: i64,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_states (rule_id, server_id, firing, changed_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (rule_id, server_id) DO UPDATE SET
+                firing = EXCLUDED.firing,
+                changed_at = EXCLUDED.changed_at,
+                delivery_failures = 0,
+                delivery_attempts = 0,
+                retry_at = NULL
+            "#,
+        )
+        .bind(rule_id)
+        .bind(server_id)
+        .bind(firing)
+        .bind(at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_failed_delivery(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        attempts: i32,
+        retry_at: i64,
+    ) -> AppResult<()> {
+        // Rules that never fired have no state yet; they start out resolved.
+        sqlx::query(
+            r#"
+            INSERT INTO alert_states
+                (rule_id, server_id, firing, changed_at, delivery_failures, delivery_attempts, retry_at)
+            VALUES ($1, $2, FALSE, EXTRACT(EPOCH FROM NOW())::BIGINT, 1, $3, $4)
+            ON CONFLICT (rule_id, server_id) DO UPDATE SET
+                delivery_failures = alert_states.delivery_failures + 1,
+                delivery_attempts = alert_states.delivery_attempts + EXCLUDED.delivery_attempts,
+                retry_at = EXCLUDED.retry_at
+            "#,
+        )
+        .bind(rule_id)
+        .bind(server_id)
+        .bind(attempts)
+        .bind(retry_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn clear_failed_deliveries(&self, rule_id: i64, server_id: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE alert_states
+            SET delivery_failures = 0, delivery_attempts = 0, retry_at = NULL
+            WHERE rule_id = $1 AND server_id = $2
+            "#,
+        )
+        .bind(rule_id)
+        .bind(server_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_delivery(
+        &self,
+        rule_id: i64,
+        server_id: &str,
+        event: AlertEvent,
+        payload: &Value,
+        outcome: &WebhookOutcome,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_deliveries (rule_id, server_id, event, payload, status_code, error, attempts)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(rule_id)
+        .bind(server_id)
+        .bind(event.to_string())
+        .bind(payload)
+        .bind(outcome.status_code)
+        .bind(&outcome.error)
+        .bind(outcome.attempts)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        rule_id: Option<i64>,
+        limit: i64,
+    ) -> AppResult<Vec<AlertDelivery>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, rule_id, server_id, event, payload, status_code, error, attempts,
+                   EXTRACT(EPOCH FROM created_at)::bigint AS created_at
+            FROM alert_deliveries
+            WHERE $1::bigint IS NULL OR rule_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(rule_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AlertDelivery {
+                id: row.get("id"),
+                rule_id: row.get("rule_id"),
+                server_id: row.get("server_id"),
+                event: row.get("event"),
+                payload: row.get("payload"),
+                status_code: row.get("status_code"),
+                error: row.get("error"),
+                attempts: row.get("attempts"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    async fn window_stats(&self, server_id: &str, since: i64) -> AppResult<AlertWindowStats> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                w.samples, w.online_samples, w.min_players, w.avg_latency,
+                (SELECT online FROM status WHERE server_id = $1 ORDER BY timestamp DESC LIMIT 1) AS latest_online,
+                (SELECT MAX(timestamp) FROM status WHERE server_id = $1 AND online) AS last_online_at,
+                (SELECT MIN(timestamp) FROM status WHERE server_id = $1) AS first_seen_at
+            FROM (
+                SELECT
+                    COUNT(*) AS samples,
+                    COUNT(*) FILTER (WHERE online) AS online_samples,
+                    MIN(players_online) AS min_players,
+                    AVG(latency)::float8 AS avg_latency
+                FROM status
+                WHERE server_id = $1 AND timestamp >= $2
+            ) w
+            "#,
+        )
+        .bind(server_id)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(AlertWindowStats {
+            samples: row.get("samples"),
+            online_samples: row.get("online_samples"),
+            min_players: row.get("min_players"),
+            avg_latency: row.get("avg_latency"),
+            latest_online: row.get("latest_online"),
+            last_online_at: row.get("last_online_at"),
+            first_seen_at: row.get("first_seen_at"),
+        })
+    }
+}
@@ -1,14 +1,66 @@
 use async_trait::async_trait;
-use domain::files::{FileMetadata, FileUploadPart, FileUploadSession};
+use chrono::NaiveDateTime;
+use domain::files::{
+    FileFilter, FileFolder, FileMetadata, FileUploadPart, FileUploadSession, FileUsage,
+    FileUsageKind, FileVisibility, PurposeUsage, StorageUsage, UploadPurpose, UploadReaperRun,
+    UserUsage,
+};
+use serde_json::Value;
 use shared::error::AppResult;
-use sqlx::{PgPool, Row};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, Row, postgres::PgRow};
 
 #[async_trait]
 pub trait FileRepository {
-    async fn insert_metadata(&self, metadata: &FileMetadata) -> AppResult<()>;
+    /// Stores the file, sharing the object of an earlier file with the same
+    /// hash if there is one. Returns the key of the object the file uses.
+    async fn insert_metadata(&self, metadata: &FileMetadata) -> AppResult<String>;
     async fn find_metadata(&self, id: &str) -> AppResult<FileMetadata>;
-    async fn list_metadata(&self, user_id: Option<String>) -> AppResult<Vec<FileMetadata>>;
-    async fn delete_metadata(&self, id: &str) -> AppResult<()>;
+    /// One page of files matching `filter`, newest first, along with the
+    /// total number of matches.
+    async fn fetch_page(
+        &self,
+        filter: &FileFilter,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<(Vec<FileMetadata>, i64)>;
+    async fn update_organization(&self, id: &str, folder: &str, tags: &[String]) -> AppResult<()>;
+    /// Folders in use, optionally only among the files of `user_id`.
+    async fn list_folders(&self, user_id: Option<&str>) -> AppResult<Vec<FileFolder>>;
+    /// Removes the file and returns the key of its object once no other file
+    /// shares it.
+    async fn delete_metadata(&self, id: &str) -> AppResult<Option<String>>;
+    /// Points the file at a copy of its content stored under `key`. Returns
+    /// the key the file now uses, which is an existing object when identical
+    /// content already has that visibility, and the old key once unshared.
+    async fn set_visibility(
+        &self,
+        id: &str,
+        visibility: FileVisibility,
+        key: &str,
+    ) -> AppResult<(String, Option<String>)>;
+    /// Files queued by a migration to move to another visibility, oldest
+    /// first.
+    async fn pending_visibility_changes(&self) -> AppResult<Vec<(String, FileVisibility)>>;
+    async fn finish_visibility_change(&self, file_id: &str) -> AppResult<()>;
+
+    /// Items, tickets and resource packs using the file, oldest first.
+    async fn list_usages(&self, file_id: &str) -> AppResult<Vec<FileUsage>>;
+    /// Records that the entity uses the file; recording it twice is a no-op.
+    async fn insert_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<()>;
+    /// Returns whether the reference existed.
+    async fn delete_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<bool>;
+    /// Id of the item with this id or slug.
+    async fn find_item_id(&self, id_or_slug: &str) -> AppResult<Option<String>>;
 
     async fn create_upload(&self, upload: &FileUploadSession) -> AppResult<()>;
     async fn find_upload(&self, upload_id: &str) -> AppResult<FileUploadSession>;
@@ -20,8 +72,48 @@ pub trait FileRepository {
         etag: &str,
     ) -> AppResult<()>;
     async fn delete_upload(&self, upload_id: &str) -> AppResult<()>;
+    /// Marks the session quarantined; its object now lives under `key`.
+    async fn quarantine_upload(&self, upload_id: &str, key: &str, reason: &str) -> AppResult<()>;
+    /// In-progress sessions with no activity since `before`, with the time of
+    /// their last activity.
+    async fn find_stale_uploads(
+        &self,
+        before: NaiveDateTime,
+    ) -> AppResult<Vec<(FileUploadSession, NaiveDateTime)>>;
+    /// The subset of `upload_ids` that still have a session.
+    async fn existing_upload_ids(&self, upload_ids: &[String]) -> AppResult<Vec<String>>;
+    async fn insert_reaper_run(&self, run: &UploadReaperRun) -> AppResult<UploadReaperRun>;
+    async fn list_reaper_runs(&self, limit: i64) -> AppResult<Vec<UploadReaperRun>>;
+
+    /// Storage used by `user_id`, or by everyone.
+    async fn storage_usage(&self, user_id: Option<&str>) -> AppResult<StorageUsage>;
+    async fn usage_by_purpose(&self, user_id: Option<&str>) -> AppResult<Vec<PurposeUsage>>;
+    /// The `limit` users using the most storage, largest first.
+    async fn top_users(&self, limit: i64) -> AppResult<Vec<UserUsage>>;
+    /// Bytes of stored objects, counting shared content once.
+    async fn stored_bytes(&self) -> AppResult<i64>;
 }
 
+/// Files and in-progress uploads, one row each, for summing storage usage.
+const HELD_STORAGE: &str = r#"
+    WITH held AS (
+        SELECT user_id, purpose, 1 AS files, size AS file_bytes, 0 AS uploads, 0 AS upload_bytes
+        FROM files
+        UNION ALL
+        SELECT user_id, purpose, 0, 0, 1, size
+        FROM file_uploads
+        WHERE status = 'in_progress'
+    )
+"#;
+
+/// Columns of a [`StorageUsage`] summed over `held`.
+const USAGE_COLUMNS: &str = r#"
+    COALESCE(SUM(files), 0)::BIGINT AS file_count,
+    COALESCE(SUM(file_bytes), 0)::BIGINT AS file_bytes,
+    COALESCE(SUM(uploads), 0)::BIGINT AS upload_count,
+    COALESCE(SUM(upload_bytes), 0)::BIGINT AS upload_bytes
+"#;
+
 pub struct PostgresFileRepository {
     pub pool: PgPool,
 }
@@ -31,19 +123,224 @@ impl PostgresFileRepository {
         Self { pool }
     }
 
-    fn generate_cdn_url(user_id: &str, file_id: &str, filename: &str) -> String {
-        format!(
-            "https://cdn.alcaris.net/files/{}/{}/{}",
-            user_id, file_id, filename
+    fn usage_from_row(row: &PgRow) -> StorageUsage {
+        StorageUsage::new(
+            row.get("file_count"),
+            row.get("file_bytes"),
+            row.get("upload_count"),
+            row.get("upload_bytes"),
         )
     }
+
+    fn upload_from_row(row: &PgRow) -> FileUploadSession {
+        FileUploadSession {
+            upload_id: row.get("upload_id"),
+            file_id: row.get("file_id"),
+            user_id: row.get("user_id"),
+            key: row.get("key"),
+            filename: row.get("filename"),
+            content_type: row.get("content_type"),
+            size: row.get("size"),
+            part_size: row.get("part_size"),
+            purpose: Self::purpose_from_row(row),
+            visibility: Self::visibility_from_row(row),
+            status: row.get("status"),
+            quarantine_reason: row.get("quarantine_reason"),
+        }
+    }
+
+    /// Download links depend on storage configuration and are filled in by
+    /// the usecase.
+    fn metadata_from_row(row: &PgRow) -> FileMetadata {
+        FileMetadata {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            filename: row.get("filename"),
+            key: row.get("key"),
+            sha256: row.get("sha256"),
+            content_type: row.get("content_type"),
+            size: row.get("size"),
+            purpose: Self::purpose_from_row(row),
+            visibility: Self::visibility_from_row(row),
+            folder: row.get("folder"),
+            tags: row.get("tags"),
+            uploaded_at: row.get("uploaded_at"),
+            url: None,
+            width: row.get("width"),
+            height: row.get("height"),
+            frames: row.get("frames"),
+            has_previews: row.get("has_previews"),
+            thumbnail_url: None,
+            preview_url: None,
+            uploader_username: row.get::<Option<String>, _>("uploader_username"),
+            uploader_global_name: row.get::<Option<String>, _>("uploader_global_name"),
+            uploader_avatar_url: row.get::<Option<String>, _>("uploader_avatar_url"),
+        }
+    }
+
+    fn purpose_from_row(row: &PgRow) -> UploadPurpose {
+        UploadPurpose::parse(row.get("purpose")).unwrap_or_default()
+    }
+
+    fn visibility_from_row(row: &PgRow) -> FileVisibility {
+        FileVisibility::parse(row.get("visibility")).unwrap_or_default()
+    }
+
+    fn push_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &FileFilter) {
+        query.push(" WHERE TRUE");
+
+        if let Some(user_id) = &filter.user_id {
+            query.push(" AND f.user_id = ").push_bind(user_id.clone());
+        }
+        if let Some(folder) = filter.folder.as_deref().filter(|f| !f.is_empty()) {
+            query
+                .push(" AND (f.folder = ")
+                .push_bind(folder.to_string())
+                .push(" OR f.folder LIKE ")
+                .push_bind(format!("{}/%", escape_like(folder)))
+                .push(")");
+        }
+        if let Some(tag) = &filter.tag {
+            query
+                .push(" AND ")
+                .push_bind(tag.trim().to_lowercase())
+                .push(" = ANY(f.tags)");
+        }
+        if let Some(q) = filter.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            query
+                .push(" AND f.filename ILIKE ")
+                .push_bind(format!("%{}%", escape_like(q)));
+        }
+        if let Some(content_type) = &filter.content_type {
+            let content_type = content_type.trim().to_ascii_lowercase();
+            if content_type.contains('/') {
+                query
+                    .push(" AND lower(split_part(f.content_type, ';', 1)) = ")
+                    .push_bind(content_type);
+            } else {
+                query
+                    .push(" AND lower(f.content_type) LIKE ")
+                    .push_bind(format!("{}/%", escape_like(&content_type)));
+            }
+        }
+        if let Some(purpose) = filter.purpose {
+            query.push(" AND f.purpose = ").push_bind(purpose.as_str());
+        }
+        if let Some(visibility) = filter.visibility {
+            query
+                .push(" AND f.visibility = ")
+                .push_bind(visibility.as_str());
+        }
+        if let Some(after) = filter.uploaded_after {
+            query
+                .push(" AND f.uploaded_at >= to_timestamp(")
+                .push_bind(after)
+                .push(") AT TIME ZONE 'UTC'");
+        }
+        if let Some(before) = filter.uploaded_before {
+            query
+                .push(" AND f.uploaded_at < to_timestamp(")
+                .push_bind(before)
+                .push(") AT TIME ZONE 'UTC'");
+        }
+    }
+
+    async fn count(&self, filter: &FileFilter) -> AppResult<i64> {
+        let mut query = QueryBuilder::new("SELECT COUNT(*) AS total_count FROM files f");
+        Self::push_filter(&mut query, filter);
+        let row = query.build().fetch_one(&self.pool).await?;
+        Ok(row.get("total_count"))
+    }
+
+    /// Takes a reference on the blob for `sha256`, registering `key` as its
+    /// object if it is new. Returns the key of the blob's object.
+    async fn claim_blob(
+        conn: &mut PgConnection,
+        sha256: &str,
+        visibility: FileVisibility,
+        key: &str,
+        size: i64,
+    ) -> AppResult<String> {
+        Ok(sqlx::query_scalar(
+            r#"
+            INSERT INTO file_blobs (sha256, visibility, key, size, ref_count)
+            VALUES ($1, $2, $3, $4, 1)
+            ON CONFLICT (sha256, visibility) DO UPDATE SET ref_count = file_blobs.ref_count + 1
+            RETURNING key
+            "#,
+        )
+        .bind(sha256)
+        .bind(visibility.as_str())
+        .bind(key)
+        .bind(size)
+        .fetch_one(conn)
+        .await?)
+    }
+
+    /// Drops a reference on the blob for `sha256`, returning `key` once no
+    /// file uses it any more.
+    async fn release_blob(
+        conn: &mut PgConnection,
+        sha256: &str,
+        visibility: FileVisibility,
+        key: String,
+    ) -> AppResult<Option<String>> {
+        let remaining: i32 = sqlx::query_scalar(
+            r#"
+            UPDATE file_blobs SET ref_count = ref_count - 1
+            WHERE sha256 = $1 AND visibility = $2
+            RETURNING ref_count
+            "#,
+        )
+        .bind(sha256)
+        .bind(visibility.as_str())
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if remaining > 0 {
+            return Ok(None);
+        }
+        sqlx::query("DELETE FROM file_blobs WHERE sha256 = $1 AND visibility = $2")
+            .bind(sha256)
+            .bind(visibility.as_str())
+            .execute(conn)
+            .await?;
+        Ok(Some(key))
+    }
+
+    fn reaper_run_from_row(row: &PgRow) -> AppResult<UploadReaperRun> {
+        Ok(UploadReaperRun {
+            id: row.get("id"),
+            ran_at: row.get("ran_at"),
+            ttl_secs: row.get("ttl_secs"),
+            expired: serde_json::from_value(row.get::<Value, _>("expired"))?,
+            orphaned: serde_json::from_value(row.get::<Value, _>("orphaned"))?,
+            failures: serde_json::from_value(row.get::<Value, _>("failures"))?,
+        })
+    }
 }
 
 #[async_trait]
 impl FileRepository for PostgresFileRepository {
-    async fn insert_metadata(&self, metadata: &FileMetadata) -> AppResult<()> {
+    async fn insert_metadata(&self, metadata: &FileMetadata) -> AppResult<String> {
+        let mut tx = self.pool.begin().await?;
+
+        let key = match &metadata.sha256 {
+            Some(sha256) => {
+                Self::claim_blob(
+                    &mut tx,
+                    sha256,
+                    metadata.visibility,
+                    &metadata.key,
+                    metadata.size,
+                )
+                .await?
+            }
+            None => metadata.key.clone(),
+        };
+
         sqlx::query(
-            "INSERT INTO files (id, user_id, filename, content_type, size, uploaded_at, uploader_username, uploader_global_name, uploader_avatar_url) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            "INSERT INTO files (id, user_id, filename, content_type, size, uploaded_at, uploader_username, uploader_global_name, uploader_avatar_url, purpose, width, height, frames, has_previews, key, sha256, visibility, folder, tags) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)",
         )
         .bind(&metadata.id)
         .bind(&metadata.user_id)
@@ -54,9 +351,21 @@ impl FileRepository for PostgresFileRepository {
         .bind(&metadata.uploader_username)
         .bind(&metadata.uploader_global_name)
         .bind(&metadata.uploader_avatar_url)
-        .execute(&self.pool)
+        .bind(metadata.purpose.as_str())
+        .bind(metadata.width)
+        .bind(metadata.height)
+        .bind(metadata.frames)
+        .bind(metadata.has_previews)
+        .bind(&key)
+        .bind(&metadata.sha256)
+        .bind(metadata.visibility.as_str())
+        .bind(&metadata.folder)
+        .bind(&metadata.tags)
+        .execute(&mut *tx)
         .await?;
-        Ok(())
+
+        tx.commit().await?;
+        Ok(key)
     }
 
     async fn find_metadata(&self, id: &str) -> AppResult<FileMetadata> {
@@ -65,72 +374,230 @@ impl FileRepository for PostgresFileRepository {
             .fetch_one(&self.pool)
             .await?;
 
-        let user_id: String = row.get("user_id");
-        let file_id: String = row.get("id");
-        let filename: String = row.get("filename");
-
-        Ok(FileMetadata {
-            id: file_id.clone(),
-            user_id: user_id.clone(),
-            filename: filename.clone(),
-            content_type: row.get("content_type"),
-            size: row.get("size"),
-            uploaded_at: row.get("uploaded_at"),
-            url: Some(Self::generate_cdn_url(&user_id, &file_id, &filename)),
-            uploader_username: row.get::<Option<String>, _>("uploader_username"),
-            uploader_global_name: row.get::<Option<String>, _>("uploader_global_name"),
-            uploader_avatar_url: row.get::<Option<String>, _>("uploader_avatar_url"),
-        })
+        Ok(Self::metadata_from_row(&row))
     }
 
-    async fn list_metadata(&self, user_id: Option<String>) -> AppResult<Vec<FileMetadata>> {
-        let rows = if let Some(ref user_id) = user_id {
-            sqlx::query("SELECT * FROM files WHERE user_id = $1")
-                .bind(user_id)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query("SELECT * FROM files")
-                .fetch_all(&self.pool)
-                .await?
+    async fn fetch_page(
+        &self,
+        filter: &FileFilter,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<(Vec<FileMetadata>, i64)> {
+        let mut query =
+            QueryBuilder::new("SELECT f.*, COUNT(*) OVER () AS total_count FROM files f");
+        Self::push_filter(&mut query, filter);
+        query.push(" ORDER BY f.uploaded_at DESC, f.id");
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let total = match rows.first() {
+            Some(row) => row.get("total_count"),
+            None => self.count(filter).await?,
         };
 
+        Ok((rows.iter().map(Self::metadata_from_row).collect(), total))
+    }
+
+    async fn update_organization(&self, id: &str, folder: &str, tags: &[String]) -> AppResult<()> {
+        sqlx::query("UPDATE files SET folder = $2, tags = $3 WHERE id = $1")
+            .bind(id)
+            .bind(folder)
+            .bind(tags)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_folders(&self, user_id: Option<&str>) -> AppResult<Vec<FileFolder>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, COUNT(*) AS file_count, COALESCE(SUM(size), 0)::BIGINT AS total_size
+            FROM files
+            WHERE $1::TEXT IS NULL OR user_id = $1
+            GROUP BY folder
+            ORDER BY folder
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
         Ok(rows
-            .into_iter()
-            .map(|row| {
-                let user_id: String = row.get("user_id");
-                let file_id: String = row.get("id");
-                let filename: String = row.get("filename");
-
-                FileMetadata {
-                    id: file_id.clone(),
-                    user_id: user_id.clone(),
-                    filename: filename.clone(),
-                    content_type: row.get("content_type"),
-                    size: row.get("size"),
-                    uploaded_at: row.get("uploaded_at"),
-                    url: Some(PostgresFileRepository::generate_cdn_url(
-                        &user_id, &file_id, &filename,
-                    )),
-                    uploader_username: row.get::<Option<String>, _>("uploader_username"),
-                    uploader_global_name: row.get::<Option<String>, _>("uploader_global_name"),
-                    uploader_avatar_url: row.get::<Option<String>, _>("uploader_avatar_url"),
-                }
+            .iter()
+            .map(|row| FileFolder {
+                folder: row.get("folder"),
+                file_count: row.get("file_count"),
+                total_size: row.get("total_size"),
             })
             .collect())
     }
 
-    async fn delete_metadata(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM files WHERE id = $1")
+    async fn delete_metadata(&self, id: &str) -> AppResult<Option<String>> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query("DELETE FROM files WHERE id = $1 RETURNING key, sha256, visibility")
             .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let key: String = row.get("key");
+        let released = match row.get::<Option<String>, _>("sha256") {
+            Some(sha256) => {
+                Self::release_blob(&mut tx, &sha256, Self::visibility_from_row(&row), key).await?
+            }
+            None => Some(key),
+        };
+
+        tx.commit().await?;
+        Ok(released)
+    }
+
+    async fn set_visibility(
+        &self,
+        id: &str,
+        visibility: FileVisibility,
+        key: &str,
+    ) -> AppResult<(String, Option<String>)> {
+        let mut tx = self.pool.begin().await?;
+
+        let row =
+            sqlx::query("SELECT key, sha256, visibility, size FROM files WHERE id = $1 FOR UPDATE")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        let old_key: String = row.get("key");
+        let sha256: Option<String> = row.get("sha256");
+
+        // The row must point at the new blob before the old one can go.
+        let new_key = match &sha256 {
+            Some(sha256) => {
+                Self::claim_blob(&mut tx, sha256, visibility, key, row.get("size")).await?
+            }
+            None => key.to_string(),
+        };
+
+        sqlx::query("UPDATE files SET visibility = $2, key = $3 WHERE id = $1")
+            .bind(id)
+            .bind(visibility.as_str())
+            .bind(&new_key)
+            .execute(&mut *tx)
+            .await?;
+
+        let released = match &sha256 {
+            Some(sha256) => {
+                Self::release_blob(&mut tx, sha256, Self::visibility_from_row(&row), old_key)
+                    .await?
+            }
+            None => Some(old_key),
+        };
+
+        tx.commit().await?;
+        Ok((new_key, released))
+    }
+
+    async fn pending_visibility_changes(&self) -> AppResult<Vec<(String, FileVisibility)>> {
+        let rows = sqlx::query(
+            "SELECT file_id, visibility FROM file_visibility_backfill ORDER BY created_at, file_id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("file_id"), Self::visibility_from_row(row)))
+            .collect())
+    }
+
+    async fn finish_visibility_change(&self, file_id: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM file_visibility_backfill WHERE file_id = $1")
+            .bind(file_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    async fn list_usages(&self, file_id: &str) -> AppResult<Vec<FileUsage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.entity_type AS kind, r.entity_id, i.name AS label, r.created_at AS since
+            FROM file_references r
+            LEFT JOIN items i ON r.entity_type = 'item' AND i.id = r.entity_id
+            WHERE r.file_id = $1
+            UNION ALL
+            SELECT 'ticket', t.id, t.title, MIN(m.sent_at)
+            FROM ticket_message_attachments a
+            JOIN ticket_messages m ON m.id = a.message_id
+            JOIN tickets t ON t.id = m.ticket_id
+            WHERE a.file_id = $1
+            GROUP BY t.id, t.title
+            ORDER BY since
+            "#,
+        )
+        .bind(file_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let kind: String = row.get("kind");
+                Ok(FileUsage {
+                    kind: FileUsageKind::parse(&kind)
+                        .ok_or_else(|| anyhow::anyhow!("Unknown file usage kind '{}'", kind))?,
+                    entity_id: row.get("entity_id"),
+                    label: row.get("label"),
+                    since: row.get("since"),
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<()> {
+        sqlx::query(
+            "INSERT INTO file_references (file_id, entity_type, entity_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(file_id)
+        .bind(kind.as_str())
+        .bind(entity_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_reference(
+        &self,
+        file_id: &str,
+        kind: FileUsageKind,
+        entity_id: &str,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            "DELETE FROM file_references WHERE file_id = $1 AND entity_type = $2 AND entity_id = $3",
+        )
+        .bind(file_id)
+        .bind(kind.as_str())
+        .bind(entity_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_item_id(&self, id_or_slug: &str) -> AppResult<Option<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT id FROM items WHERE id = $1 OR slug = $1")
+                .bind(id_or_slug)
+                .fetch_optional(&self.pool)
+                .await?,
+        )
+    }
+
     async fn create_upload(&self, upload: &FileUploadSession) -> AppResult<()> {
         sqlx::query(
-             "INSERT INTO file_uploads (upload_id, file_id, user_id, key, filename, content_type, size, part_size, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'in_progress')",
+             "INSERT INTO file_uploads (upload_id, file_id, user_id, key, filename, content_type, size, part_size, status, purpose, visibility) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
          )
          .bind(&upload.upload_id)
          .bind(&upload.file_id)
@@ -140,6 +607,9 @@ impl FileRepository for PostgresFileRepository {
          .bind(&upload.content_type)
          .bind(upload.size)
          .bind(upload.part_size)
+         .bind(&upload.status)
+         .bind(upload.purpose.as_str())
+         .bind(upload.visibility.as_str())
          .execute(&self.pool)
          .await?;
         Ok(())
@@ -151,16 +621,7 @@ impl FileRepository for PostgresFileRepository {
             .fetch_one(&self.pool)
             .await?;
 
-        Ok(FileUploadSession {
-            upload_id: row.get("upload_id"),
-            file_id: row.get("file_id"),
-            user_id: row.get("user_id"),
-            key: row.get("key"),
-            filename: row.get("filename"),
-            content_type: row.get("content_type"),
-            size: row.get("size"),
-            part_size: row.get("part_size"),
-        })
+        Ok(Self::upload_from_row(&row))
     }
 
     async fn list_upload_parts(&self, upload_id: &str) -> AppResult<Vec<FileUploadPart>> {
@@ -210,4 +671,150 @@ impl FileRepository for PostgresFileRepository {
             .await?;
         Ok(())
     }
+
+    async fn quarantine_upload(&self, upload_id: &str, key: &str, reason: &str) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE file_uploads
+            SET status = 'quarantined', key = $2, quarantine_reason = $3, updated_at = NOW()
+            WHERE upload_id = $1
+            "#,
+        )
+        .bind(upload_id)
+        .bind(key)
+        .bind(reason)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_stale_uploads(
+        &self,
+        before: NaiveDateTime,
+    ) -> AppResult<Vec<(FileUploadSession, NaiveDateTime)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT *, (updated_at AT TIME ZONE 'UTC') AS last_activity_at
+            FROM file_uploads
+            WHERE status = 'in_progress' AND updated_at < ($1 AT TIME ZONE 'UTC')
+            ORDER BY updated_at
+            "#,
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (Self::upload_from_row(row), row.get("last_activity_at")))
+            .collect())
+    }
+
+    async fn existing_upload_ids(&self, upload_ids: &[String]) -> AppResult<Vec<String>> {
+        Ok(
+            sqlx::query_scalar("SELECT upload_id FROM file_uploads WHERE upload_id = ANY($1)")
+                .bind(upload_ids)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    async fn insert_reaper_run(&self, run: &UploadReaperRun) -> AppResult<UploadReaperRun> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO file_upload_reaper_runs (ran_at, ttl_secs, expired, orphaned, failures)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(run.ran_at)
+        .bind(run.ttl_secs)
+        .bind(serde_json::to_value(&run.expired)?)
+        .bind(serde_json::to_value(&run.orphaned)?)
+        .bind(serde_json::to_value(&run.failures)?)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Self::reaper_run_from_row(&row)
+    }
+
+    async fn list_reaper_runs(&self, limit: i64) -> AppResult<Vec<UploadReaperRun>> {
+        let rows =
+            sqlx::query("SELECT * FROM file_upload_reaper_runs ORDER BY ran_at DESC LIMIT $1")
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.iter().map(Self::reaper_run_from_row).collect()
+    }
+
+    async fn storage_usage(&self, user_id: Option<&str>) -> AppResult<StorageUsage> {
+        let row = sqlx::query(&format!(
+            "{} SELECT {} FROM held WHERE $1::TEXT IS NULL OR user_id = $1",
+            HELD_STORAGE, USAGE_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Self::usage_from_row(&row))
+    }
+
+    async fn usage_by_purpose(&self, user_id: Option<&str>) -> AppResult<Vec<PurposeUsage>> {
+        let rows = sqlx::query(&format!(
+            "{} SELECT purpose, {} FROM held WHERE $1::TEXT IS NULL OR user_id = $1 \
+             GROUP BY purpose ORDER BY purpose",
+            HELD_STORAGE, USAGE_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PurposeUsage {
+                purpose: Self::purpose_from_row(row),
+                usage: Self::usage_from_row(row),
+            })
+            .collect())
+    }
+
+    async fn top_users(&self, limit: i64) -> AppResult<Vec<UserUsage>> {
+        let rows = sqlx::query(&format!(
+            "{} SELECT user_id, {} FROM held GROUP BY user_id \
+             ORDER BY SUM(file_bytes + upload_bytes) DESC, user_id LIMIT $1",
+            HELD_STORAGE, USAGE_COLUMNS
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| UserUsage {
+                user_id: row.get("user_id"),
+                usage: Self::usage_from_row(row),
+            })
+            .collect())
+    }
+
+    async fn stored_bytes(&self) -> AppResult<i64> {
+        // Files uploaded before hashing have no blob and own their object.
+        let stored = sqlx::query_scalar(
+            r#"
+            SELECT (SELECT COALESCE(SUM(size), 0) FROM file_blobs)::BIGINT
+                 + (SELECT COALESCE(SUM(size), 0) FROM files WHERE sha256 IS NULL)::BIGINT
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(stored)
+    }
+}
+
+/// `raw` with the `LIKE` wildcards escaped.
+fn escape_like(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
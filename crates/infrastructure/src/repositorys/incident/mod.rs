@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use domain::status::StatusIncident;
+use shared::error::AppResult;
+use sqlx::{PgPool, Row, postgres::PgRow};
+
+#[async_trait]
+pub trait IncidentRepository {
+    async fn open(&self, server_id: &str, started_at: i64, failed_probes: i32) -> AppResult<i64>;
+    async fn record_failure(&self, server_id: &str) -> AppResult<()>;
+    async fn close(&self, server_id: &str, ended_at: i64) -> AppResult<bool>;
+    async fn find_open(&self, server_id: &str) -> AppResult<Option<StatusIncident>>;
+    async fn find_by_id(&self, id: i64) -> AppResult<StatusIncident>;
+    async fn list(&self, server_id: &str, limit: i64) -> AppResult<Vec<StatusIncident>>;
+    async fn update_notes(&self, id: i64, notes: Option<String>, actor: &str) -> AppResult<()>;
+    async fn summarize(&self, server_id: &str, from: i64, to: i64) -> AppResult<(i64, i64)>;
+}
+
+pub struct PostgresIncidentRepository {
+    pub pool: PgPool,
+}
+
+impl PostgresIncidentRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    fn from_row(row: &PgRow) -> StatusIncident {
+        StatusIncident {
+            id: row.get("id"),
+            server_id: row.get("server_id"),
+            started_at: row.get("started_at"),
+            ended_at: row.get("ended_at"),
+            failed_probes: row.get("failed_probes"),
+            notes: row.get("notes"),
+            notes_updated_by: row.get("notes_updated_by"),
+            notes_updated_at: row.get("notes_updated_at"),
+        }
+    }
+}
+
+const INCIDENT_COLUMNS: &str = "id, server_id, started_at, ended_at, failed_probes, notes, notes_updated_by, EXTRACT(EPOCH FROM notes_updated_at)::bigint AS notes_updated_at";
+
+#[async_trait]
+impl IncidentRepository for PostgresIncidentRepository {
+    async fn open(&self, server_id: &str, started_at: i64, failed_probes: i32) -> AppResult<i64> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO status_incidents (server_id, started_at, failed_probes)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (server_id) WHERE ended_at IS NULL
+            DO UPDATE SET failed_probes = EXCLUDED.failed_probes
+            RETURNING id
+            "#,
+        )
+        .bind(server_id)
+        .bind(started_at)
+        .bind(failed_probes)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("id"))
+    }
+
+    async fn record_failure(&self, server_id: &str) -> AppResult<()> {
+        sqlx::query(
+            "UPDATE status_incidents SET failed_probes = failed_probes + 1 WHERE server_id = $1 AND ended_at IS NULL",
+        )
+        .bind(server_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn close(&self, server_id: &str, ended_at: i64) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE status_incidents SET ended_at = $2 WHERE server_id = $1 AND ended_at IS NULL",
+        )
+        .bind(server_id)
+        .bind(ended_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_open(&self, server_id: &str) -> AppResult<Option<StatusIncident>> {
+        let row = sqlx::query(&format!(
+            "SELECT {INCIDENT_COLUMNS} FROM status_incidents WHERE server_id = $1 AND ended_at IS NULL"
+        ))
+        .bind(server_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(Self::from_row))
+    }
+
+    async fn find_by_id(&self, id: i64) -> AppResult<StatusIncident> {
+        let row = sqlx::query(&format!(
+            "SELECT {INCIDENT_COLUMNS} FROM status_incidents WHERE id = $1"
+        ))
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Self::from_row(&row))
+    }
+
+    async fn list(&self, server_id: &str, limit: i64) -> AppResult<Vec<StatusIncident>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {INCIDENT_COLUMNS} FROM status_incidents WHERE server_id = $1 ORDER BY started_at DESC LIMIT $2"
+        ))
+        .bind(server_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::from_row).collect())
+    }
+
+    async fn update_notes(&self, id: i64, notes: Option<String>, actor: &str) -> AppResult<()> {
+        let result = sqlx::query(
+            "UPDATE status_incidents SET notes = $2, notes_updated_by = $3, notes_updated_at = NOW() WHERE id = $1",
+        )
+        .bind(id)
+        .bind(notes)
+        .bind(actor)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(anyhow::anyhow!("Incident {} not found", id));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of incidents overlapping `[from, to)` and the
+    /// seconds of downtime they account for inside that range.
+    async fn summarize(&self, server_id: &str, from: i64, to: i64) -> AppResult<(i64, i64)> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS incidents,
+                COALESCE(SUM(LEAST(COALESCE(ended_at, $3), $3) - GREATEST(started_at, $2)), 0)::bigint AS downtime_secs
+            FROM status_incidents
+            WHERE server_id = $1
+              AND started_at < $3
+              AND (ended_at IS NULL OR ended_at > $2)
+            "#,
+        )
+        .bind(server_id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((row.get("incidents"), row.get("downtime_secs")))
+    }
+}
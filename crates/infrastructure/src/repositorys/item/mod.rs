@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use domain::items::{CustomModelData, Item, ItemCategory};
+use domain::items::{CustomModelData, Item, ItemCategory, ItemKeyAlias};
 use serde_json::Value;
 use shared::error::AppResult;
 use sqlx::{PgPool, Row};
@@ -7,10 +7,16 @@ use sqlx::{PgPool, Row};
 #[async_trait]
 pub trait ItemRepository {
     async fn fetch_all(&self, category: Option<String>) -> AppResult<Vec<Item>>;
+    /// Looks the item up by id, slug, key or a former key.
     async fn find_by_id(&self, id: &str) -> AppResult<Item>;
     async fn insert(&self, item: Item) -> AppResult<()>;
-    async fn patch(&self, id: &str, patch: Value) -> AppResult<()>;
-    async fn delete(&self, id: &str) -> AppResult<()>;
+    /// Returns whether an item with the id `id` was updated.
+    async fn patch(&self, id: &str, patch: Value) -> AppResult<bool>;
+    /// Returns whether an item with the id `id` was deleted.
+    async fn delete(&self, id: &str) -> AppResult<bool>;
+    /// Sets the item's key, keeping the previous one as an alias.
+    async fn rename_key(&self, item_id: &str, key: &str) -> AppResult<()>;
+    async fn list_key_aliases(&self, item_id: &str) -> AppResult<Vec<ItemKeyAlias>>;
 }
 
 pub struct PostgresItemRepository {
@@ -60,6 +66,8 @@ impl ItemRepository for PostgresItemRepository {
 
             let item = Item {
                 id: row.try_get("id")?,
+                slug: row.try_get("slug")?,
+                key: row.try_get("key")?,
                 version: row.try_get("version")?,
                 name: row.try_get("name")?,
                 category: item_category,
@@ -81,10 +89,18 @@ impl ItemRepository for PostgresItemRepository {
     }
 
     async fn find_by_id(&self, id: &str) -> AppResult<Item> {
-        let row = sqlx::query("SELECT * FROM items WHERE id = $1")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await?;
+        let row = sqlx::query(
+            r#"
+            SELECT * FROM items
+            WHERE id = $1 OR slug = $1 OR key = $1
+               OR id IN (SELECT item_id FROM item_key_aliases WHERE key = $1)
+            ORDER BY (id = $1) DESC, (key = $1) DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
 
         let category_str: String = row.try_get("category")?;
         let cmd_value: Option<Value> = row.try_get("custom_model_data")?;
@@ -96,6 +112,8 @@ impl ItemRepository for PostgresItemRepository {
 
         Ok(Item {
             id: row.try_get("id")?,
+            slug: row.try_get("slug")?,
+            key: row.try_get("key")?,
             version: row.try_get("version")?,
             name: row.try_get("name")?,
             category: match category_str.to_lowercase().as_str() {
@@ -124,12 +142,12 @@ impl ItemRepository for PostgresItemRepository {
             INSERT INTO items (
                 id, version, name, category,
                 lore, rarity, max_stack, custom_model_data,
-                price, tags, data, item_model, tooltip_style
+                price, tags, data, item_model, tooltip_style, slug, key
             ) VALUES (
                 $1, $2, $3, $4,
                 to_jsonb($5), $6, $7, to_jsonb($8),
                 to_jsonb($9), to_jsonb($10), to_jsonb($11),
-                $12, $13
+                $12, $13, $14, $15
             )
             "#,
         )
@@ -146,13 +164,15 @@ impl ItemRepository for PostgresItemRepository {
         .bind(item.data)
         .bind(&item.item_model)
         .bind(&item.tooltip_style)
+        .bind(&item.slug)
+        .bind(&item.key)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    async fn patch(&self, id: &str, patch: Value) -> AppResult<()> {
+    async fn patch(&self, id: &str, patch: Value) -> AppResult<bool> {
         let patch_obj = patch
             .as_object()
             .ok_or_else(|| anyhow::anyhow!("Invalid JSON patch"))?;
@@ -213,16 +233,89 @@ impl ItemRepository for PostgresItemRepository {
         query_builder.push_bind(id);
 
         let query = query_builder.build();
-        query.execute(&self.pool).await?;
+        let result = query.execute(&self.pool).await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    async fn delete(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM items WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
+    async fn delete(&self, id: &str) -> AppResult<bool> {
+        // The item no longer holds on to the files it used.
+        let deleted: i64 = sqlx::query_scalar(
+            r#"
+            WITH deleted AS (DELETE FROM items WHERE id = $1 RETURNING id),
+            released AS (
+                DELETE FROM file_references
+                WHERE entity_type = 'item' AND entity_id IN (SELECT id FROM deleted)
+            )
+            SELECT COUNT(*) FROM deleted
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn rename_key(&self, item_id: &str, key: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let previous: Option<String> =
+            sqlx::query_scalar("SELECT key FROM items WHERE id = $1 FOR UPDATE")
+                .bind(item_id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+        if let Some(previous) = previous.filter(|previous| previous != key) {
+            sqlx::query(
+                r#"
+                INSERT INTO item_key_aliases (key, item_id, renamed_at)
+                VALUES ($1, $2, NOW())
+                ON CONFLICT (key) DO UPDATE
+                SET item_id = EXCLUDED.item_id, renamed_at = EXCLUDED.renamed_at
+                "#,
+            )
+            .bind(&previous)
+            .bind(item_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Renaming back to a former key turns it into the current key again.
+        sqlx::query("DELETE FROM item_key_aliases WHERE key = $1 AND item_id = $2")
+            .bind(key)
+            .bind(item_id)
+            .execute(&mut *tx)
             .await?;
+
+        sqlx::query("UPDATE items SET key = $1 WHERE id = $2")
+            .bind(key)
+            .bind(item_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
         Ok(())
     }
+
+    async fn list_key_aliases(&self, item_id: &str) -> AppResult<Vec<ItemKeyAlias>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT key, item_id, renamed_at FROM item_key_aliases
+            WHERE item_id = $1
+            ORDER BY renamed_at DESC
+            "#,
+        )
+        .bind(item_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ItemKeyAlias {
+                key: row.get("key"),
+                item_id: row.get("item_id"),
+                renamed_at: row.get("renamed_at"),
+            })
+            .collect())
+    }
 }
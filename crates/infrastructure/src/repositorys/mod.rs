@@ -1,5 +1,25 @@
+pub mod alert;
 pub mod file;
+pub mod incident;
 pub mod item;
 pub mod recipe;
 pub mod status;
 pub mod ticket;
+
+/// Whether `err` reports a query that matched no row, as `fetch_one` does
+/// for a missing id.
+pub fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<sqlx::Error>(),
+        Some(sqlx::Error::RowNotFound)
+    )
+}
+
+/// Name of the unique constraint or index `err` reports a duplicate in, as a
+/// concurrent insert of the same value does.
+pub fn unique_violation(err: &anyhow::Error) -> Option<&str> {
+    match err.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(db)) if db.is_unique_violation() => db.constraint(),
+        _ => None,
+    }
+}
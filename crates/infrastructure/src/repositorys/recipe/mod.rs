@@ -29,7 +29,7 @@ impl RecipeRepository for PostgresRecipeRepository {
         let rows = if let Some(category) = category {
             sqlx::query(
                 r#"
-                SELECT id, category, inputs, output, is_hidden, cooldown, unlock_level
+                SELECT id, slug, category, inputs, output, is_hidden, cooldown, unlock_level
                 FROM recipes
                 WHERE category = $1
                 "#,
@@ -40,7 +40,7 @@ impl RecipeRepository for PostgresRecipeRepository {
         } else {
             sqlx::query(
                 r#"
-                SELECT id, category, inputs, output, is_hidden, cooldown, unlock_level
+                SELECT id, slug, category, inputs, output, is_hidden, cooldown, unlock_level
                 FROM recipes
                 "#,
             )
@@ -52,6 +52,7 @@ impl RecipeRepository for PostgresRecipeRepository {
             .into_iter()
             .map(|row| Recipe {
                 id: row.get("id"),
+                slug: row.get("slug"),
                 category: row.get("category"),
                 inputs: serde_json::from_value(row.get("inputs")).unwrap_or_default(),
                 output: serde_json::from_value(row.get("output")).unwrap(),
@@ -67,8 +68,8 @@ impl RecipeRepository for PostgresRecipeRepository {
     async fn find_by_id(&self, id: &str) -> AppResult<Recipe> {
         let row = sqlx::query(
             r#"
-            SELECT id, category, inputs, output, is_hidden, cooldown, unlock_level
-            FROM recipes WHERE id = $1
+            SELECT id, slug, category, inputs, output, is_hidden, cooldown, unlock_level
+            FROM recipes WHERE id = $1 OR slug = $1
             "#,
         )
         .bind(id)
@@ -77,6 +78,7 @@ impl RecipeRepository for PostgresRecipeRepository {
 
         Ok(Recipe {
             id: row.get("id"),
+            slug: row.get("slug"),
             category: row.get("category"),
             inputs: serde_json::from_value(row.get("inputs")).unwrap_or_default(),
             output: serde_json::from_value(row.get("output")).unwrap(),
@@ -90,9 +92,9 @@ impl RecipeRepository for PostgresRecipeRepository {
         sqlx::query(
             r#"
             INSERT INTO recipes (
-                id, category, inputs, output, is_hidden, cooldown, unlock_level
+                id, category, inputs, output, is_hidden, cooldown, unlock_level, slug
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7
+                $1, $2, $3, $4, $5, $6, $7, $8
             )
             "#,
         )
@@ -103,13 +105,14 @@ impl RecipeRepository for PostgresRecipeRepository {
         .bind(recipe.is_hidden)
         .bind(recipe.cooldown)
         .bind(recipe.unlock_level)
+        .bind(&recipe.slug)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
     async fn patch(&self, id: &str, patch: Value) -> AppResult<()> {
-        let patch_sql = "UPDATE recipes SET data = data || $1 WHERE id = $2";
+        let patch_sql = "UPDATE recipes SET data = data || $1 WHERE id = $2 OR slug = $2";
         sqlx::query(patch_sql)
             .bind(patch)
             .bind(id)
@@ -119,7 +122,7 @@ impl RecipeRepository for PostgresRecipeRepository {
     }
 
     async fn delete(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM recipes WHERE id = $1")
+        sqlx::query("DELETE FROM recipes WHERE id = $1 OR slug = $1")
             .bind(id)
             .execute(&self.pool)
             .await?;
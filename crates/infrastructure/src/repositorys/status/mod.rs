@@ -1,7 +1,10 @@
 use async_trait::async_trait;
-use domain::status::{Players, StatusRecord};
+use domain::status::{
+    HistoryResolution, NetworkHistoryPoint, NetworkPeak, Players, StatusHistoryPoint, StatusRecord,
+};
 use shared::error::AppResult;
 use sqlx::{PgPool, Row};
+use std::collections::HashMap;
 
 #[async_trait]
 pub trait StatusRepository {
@@ -9,6 +12,32 @@ pub trait StatusRepository {
     async fn get_history(&self, id: &str) -> AppResult<Vec<StatusRecord>>;
     async fn insert(&self, id: &str, record: &StatusRecord) -> AppResult<()>;
     async fn list_latest(&self) -> AppResult<Vec<(String, StatusRecord)>>;
+    async fn list_history(&self, ids: &[String]) -> AppResult<HashMap<String, Vec<StatusRecord>>>;
+    async fn get_history_range(
+        &self,
+        id: &str,
+        resolution: HistoryResolution,
+        from: i64,
+        to: i64,
+    ) -> AppResult<Vec<StatusHistoryPoint>>;
+    async fn sample_counts(&self, id: &str, from: i64, to: i64) -> AppResult<(i64, i64)>;
+    async fn rollup(&self, resolution: HistoryResolution) -> AppResult<u64>;
+    async fn purge_raw_before(&self, timestamp: i64) -> AppResult<u64>;
+    async fn update_network_peaks(&self, server_ids: &[String]) -> AppResult<u64>;
+    async fn network_peaks(&self, from: i64, to: i64) -> AppResult<Vec<NetworkPeak>>;
+    async fn network_history(
+        &self,
+        server_ids: &[String],
+        resolution: HistoryResolution,
+        from: i64,
+        to: i64,
+        bucket_secs: i64,
+    ) -> AppResult<Vec<NetworkHistoryPoint>>;
+    async fn purge_rollups_before(
+        &self,
+        resolution: HistoryResolution,
+        timestamp: i64,
+    ) -> AppResult<u64>;
 }
 
 pub struct PostgresStatusRepository {
@@ -25,7 +54,7 @@ impl PostgresStatusRepository {
 impl StatusRepository for PostgresStatusRepository {
     async fn get_latest(&self, id: &str) -> AppResult<Option<StatusRecord>> {
         let row = sqlx::query(
-            "SELECT online, latency, players_online, players_max, timestamp FROM status WHERE server_id = $1 ORDER BY timestamp DESC LIMIT 1"
+            "SELECT online, latency, players_online, players_max, timestamp, probe_duration_ms FROM status WHERE server_id = $1 ORDER BY timestamp DESC LIMIT 1"
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -39,12 +68,13 @@ impl StatusRepository for PostgresStatusRepository {
                 max: row.get("players_max"),
             }),
             timestamp: row.get("timestamp"),
+            probe_duration_ms: row.get("probe_duration_ms"),
         }))
     }
 
     async fn get_history(&self, id: &str) -> AppResult<Vec<StatusRecord>> {
         let rows = sqlx::query(
-            "SELECT online, latency, players_online, players_max, timestamp FROM status WHERE server_id = $1 ORDER BY timestamp DESC OFFSET 1 LIMIT 59"
+            "SELECT online, latency, players_online, players_max, timestamp, probe_duration_ms FROM status WHERE server_id = $1 ORDER BY timestamp DESC OFFSET 1 LIMIT 59"
         )
         .bind(id)
         .fetch_all(&self.pool)
@@ -60,6 +90,7 @@ impl StatusRepository for PostgresStatusRepository {
                     max: row.get("players_max"),
                 }),
                 timestamp: row.get("timestamp"),
+                probe_duration_ms: row.get("probe_duration_ms"),
             })
             .collect())
     }
@@ -71,7 +102,7 @@ impl StatusRepository for PostgresStatusRepository {
         };
 
         sqlx::query(
-            "INSERT INTO status (server_id, online, latency, players_online, players_max, timestamp) VALUES ($1, $2, $3, $4, $5, $6)"
+            "INSERT INTO status (server_id, online, latency, players_online, players_max, timestamp, probe_duration_ms) VALUES ($1, $2, $3, $4, $5, $6, $7)"
         )
         .bind(id)
         .bind(record.online)
@@ -79,6 +110,7 @@ impl StatusRepository for PostgresStatusRepository {
         .bind(players_online)
         .bind(players_max)
         .bind(record.timestamp)
+        .bind(record.probe_duration_ms)
         .execute(&self.pool)
         .await?;
 
@@ -88,7 +120,7 @@ impl StatusRepository for PostgresStatusRepository {
     async fn list_latest(&self) -> AppResult<Vec<(String, StatusRecord)>> {
         let rows = sqlx::query(
             "
-            SELECT DISTINCT ON (server_id) server_id, online, latency, players_online, players_max, timestamp
+            SELECT DISTINCT ON (server_id) server_id, online, latency, players_online, players_max, timestamp, probe_duration_ms
             FROM status
             ORDER BY server_id, timestamp DESC
             "
@@ -109,9 +141,356 @@ impl StatusRepository for PostgresStatusRepository {
                             max: row.get("players_max"),
                         }),
                         timestamp: row.get("timestamp"),
+                        probe_duration_ms: row.get("probe_duration_ms"),
                     },
                 )
             })
             .collect())
     }
+
+    /// Same rows as `get_history` for each of `ids` in one round trip.
+    async fn list_history(&self, ids: &[String]) -> AppResult<HashMap<String, Vec<StatusRecord>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT s.server_id, h.online, h.latency, h.players_online, h.players_max, h.timestamp, h.probe_duration_ms
+            FROM UNNEST($1::text[]) AS s (server_id)
+            CROSS JOIN LATERAL (
+                SELECT * FROM status
+                WHERE server_id = s.server_id
+                ORDER BY timestamp DESC
+                OFFSET 1 LIMIT 59
+            ) h
+            ORDER BY s.server_id, h.timestamp DESC
+            "#,
+        )
+        .bind(ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut history: HashMap<String, Vec<StatusRecord>> = HashMap::new();
+        for row in rows {
+            history
+                .entry(row.get("server_id"))
+                .or_default()
+                .push(StatusRecord {
+                    online: row.get("online"),
+                    latency: row.get("latency"),
+                    players: row.try_get("players_online").ok().map(|online| Players {
+                        online,
+                        max: row.get("players_max"),
+                    }),
+                    timestamp: row.get("timestamp"),
+                    probe_duration_ms: row.get("probe_duration_ms"),
+                });
+        }
+
+        Ok(history)
+    }
+
+    async fn get_history_range(
+        &self,
+        id: &str,
+        resolution: HistoryResolution,
+        from: i64,
+        to: i64,
+    ) -> AppResult<Vec<StatusHistoryPoint>> {
+        if resolution == HistoryResolution::Raw {
+            let rows = sqlx::query(
+                r#"
+                SELECT online, latency, players_online, timestamp
+                FROM status
+                WHERE server_id = $1 AND timestamp >= $2 AND timestamp < $3
+                ORDER BY timestamp
+                "#,
+            )
+            .bind(id)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await?;
+
+            return Ok(rows
+                .into_iter()
+                .map(|row| {
+                    let online: bool = row.get("online");
+                    let players: Option<i32> = row.get("players_online");
+                    let latency: Option<i32> = row.get("latency");
+
+                    StatusHistoryPoint {
+                        timestamp: row.get("timestamp"),
+                        samples: 1,
+                        uptime_pct: if online { 100.0 } else { 0.0 },
+                        players_min: players,
+                        players_avg: players.map(f64::from),
+                        players_max: players,
+                        latency_min: latency,
+                        latency_avg: latency.map(f64::from),
+                        latency_max: latency,
+                    }
+                })
+                .collect());
+        }
+
+        let bucket = resolution.bucket_secs();
+        let rows = sqlx::query(
+            r#"
+            SELECT bucket_start, samples, uptime_pct,
+                   players_min, players_avg, players_max,
+                   latency_min, latency_avg, latency_max
+            FROM status_rollups
+            WHERE server_id = $1 AND resolution = $2 AND bucket_start >= $3 AND bucket_start < $4
+            ORDER BY bucket_start
+            "#,
+        )
+        .bind(id)
+        .bind(resolution.to_string())
+        .bind(from.div_euclid(bucket) * bucket)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| StatusHistoryPoint {
+                timestamp: row.get("bucket_start"),
+                samples: row.get("samples"),
+                uptime_pct: row.get("uptime_pct"),
+                players_min: row.get("players_min"),
+                players_avg: row.get("players_avg"),
+                players_max: row.get("players_max"),
+                latency_min: row.get("latency_min"),
+                latency_avg: row.get("latency_avg"),
+                latency_max: row.get("latency_max"),
+            })
+            .collect())
+    }
+
+    /// Returns `(samples, online_samples)` for `[from, to)` from the hourly
+    /// rollups, which outlive the raw samples.
+    async fn sample_counts(&self, id: &str, from: i64, to: i64) -> AppResult<(i64, i64)> {
+        let bucket = HistoryResolution::Hour.bucket_secs();
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COALESCE(SUM(samples), 0)::bigint AS samples,
+                COALESCE(SUM(online_samples), 0)::bigint AS online_samples
+            FROM status_rollups
+            WHERE server_id = $1 AND resolution = 'hour' AND bucket_start >= $2 AND bucket_start < $3
+            "#,
+        )
+        .bind(id)
+        .bind(from.div_euclid(bucket) * bucket)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((row.get("samples"), row.get("online_samples")))
+    }
+
+    async fn rollup(&self, resolution: HistoryResolution) -> AppResult<u64> {
+        if resolution == HistoryResolution::Raw {
+            return Ok(0);
+        }
+
+        // Buckets are recomputed from the newest existing one onwards, so the
+        // current (partial) bucket is refreshed on every run.
+        let result = sqlx::query(
+            r#"
+            INSERT INTO status_rollups (
+                server_id, resolution, bucket_start, samples, online_samples, uptime_pct,
+                players_min, players_avg, players_max,
+                latency_min, latency_avg, latency_max
+            )
+            SELECT
+                server_id,
+                $1,
+                (timestamp / $2) * $2 AS bucket_start,
+                COUNT(*)::int,
+                (COUNT(*) FILTER (WHERE online))::int,
+                (100.0 * COUNT(*) FILTER (WHERE online) / COUNT(*))::float8,
+                MIN(players_online),
+                AVG(players_online)::float8,
+                MAX(players_online),
+                MIN(latency),
+                AVG(latency)::float8,
+                MAX(latency)
+            FROM status
+            WHERE timestamp >= COALESCE(
+                (SELECT MAX(bucket_start) FROM status_rollups WHERE resolution = $1),
+                0
+            )
+            GROUP BY server_id, bucket_start
+            ON CONFLICT (server_id, resolution, bucket_start) DO UPDATE SET
+                samples = EXCLUDED.samples,
+                online_samples = EXCLUDED.online_samples,
+                uptime_pct = EXCLUDED.uptime_pct,
+                players_min = EXCLUDED.players_min,
+                players_avg = EXCLUDED.players_avg,
+                players_max = EXCLUDED.players_max,
+                latency_min = EXCLUDED.latency_min,
+                latency_avg = EXCLUDED.latency_avg,
+                latency_max = EXCLUDED.latency_max
+            "#,
+        )
+        .bind(resolution.to_string())
+        .bind(resolution.bucket_secs())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn purge_raw_before(&self, timestamp: i64) -> AppResult<u64> {
+        let result = sqlx::query("DELETE FROM status WHERE timestamp < $1")
+            .bind(timestamp)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn purge_rollups_before(
+        &self,
+        resolution: HistoryResolution,
+        timestamp: i64,
+    ) -> AppResult<u64> {
+        let result =
+            sqlx::query("DELETE FROM status_rollups WHERE resolution = $1 AND bucket_start < $2")
+                .bind(resolution.to_string())
+                .bind(timestamp)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Recomputes the daily peak of concurrent players across `server_ids`
+    /// from the newest recorded day onwards. Each server contributes its
+    /// highest count per five-minute slot so probes that do not line up in
+    /// time are still summed together.
+    async fn update_network_peaks(&self, server_ids: &[String]) -> AppResult<u64> {
+        let result = sqlx::query(
+            r#"
+            WITH per_slot AS (
+                SELECT (timestamp / 300) * 300 AS slot, server_id, MAX(players_online) AS players
+                FROM status
+                WHERE server_id = ANY($1)
+                  AND online
+                  AND timestamp >= COALESCE((SELECT MAX(day) FROM network_daily_peaks), 0)
+                GROUP BY slot, server_id
+            ),
+            totals AS (
+                SELECT slot, SUM(players)::int AS players
+                FROM per_slot
+                GROUP BY slot
+            )
+            INSERT INTO network_daily_peaks (day, players, at)
+            SELECT DISTINCT ON ((slot / 86400) * 86400)
+                (slot / 86400) * 86400, players, slot
+            FROM totals
+            ORDER BY (slot / 86400) * 86400, players DESC, slot
+            ON CONFLICT (day) DO UPDATE SET
+                players = EXCLUDED.players,
+                at = EXCLUDED.at
+            "#,
+        )
+        .bind(server_ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn network_peaks(&self, from: i64, to: i64) -> AppResult<Vec<NetworkPeak>> {
+        let rows = sqlx::query(
+            "SELECT day, players, at FROM network_daily_peaks WHERE day >= $1 AND day < $2 ORDER BY day",
+        )
+        .bind(from.div_euclid(86400) * 86400)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| NetworkPeak {
+                day: row.get("day"),
+                players: row.get("players"),
+                at: row.get("at"),
+            })
+            .collect())
+    }
+
+    async fn network_history(
+        &self,
+        server_ids: &[String],
+        resolution: HistoryResolution,
+        from: i64,
+        to: i64,
+        bucket_secs: i64,
+    ) -> AppResult<Vec<NetworkHistoryPoint>> {
+        let query = if resolution == HistoryResolution::Raw {
+            r#"
+            WITH per_server AS (
+                SELECT
+                    (timestamp / $4) * $4 AS bucket,
+                    server_id,
+                    AVG(COALESCE(players_online, 0))::float8 AS players_avg,
+                    COALESCE(MAX(players_online), 0) AS players_max,
+                    BOOL_OR(online) AS online
+                FROM status
+                WHERE server_id = ANY($1) AND timestamp >= $2 AND timestamp < $3
+                GROUP BY bucket, server_id
+            )
+            SELECT
+                bucket,
+                SUM(players_avg)::float8 AS players_avg,
+                SUM(players_max)::bigint AS players_max,
+                COUNT(*) FILTER (WHERE online) AS servers_online
+            FROM per_server
+            GROUP BY bucket
+            ORDER BY bucket
+            "#
+        } else {
+            r#"
+            WITH per_server AS (
+                SELECT
+                    (bucket_start / $4) * $4 AS bucket,
+                    server_id,
+                    (SUM(COALESCE(players_avg, 0) * samples) / NULLIF(SUM(samples), 0))::float8 AS players_avg,
+                    COALESCE(MAX(players_max), 0) AS players_max,
+                    BOOL_OR(online_samples > 0) AS online
+                FROM status_rollups
+                WHERE server_id = ANY($1) AND resolution = $5
+                  AND bucket_start >= $2 AND bucket_start < $3
+                GROUP BY bucket, server_id
+            )
+            SELECT
+                bucket,
+                COALESCE(SUM(players_avg), 0)::float8 AS players_avg,
+                SUM(players_max)::bigint AS players_max,
+                COUNT(*) FILTER (WHERE online) AS servers_online
+            FROM per_server
+            GROUP BY bucket
+            ORDER BY bucket
+            "#
+        };
+
+        let mut query = sqlx::query(query)
+            .bind(server_ids)
+            .bind(from.div_euclid(bucket_secs) * bucket_secs)
+            .bind(to)
+            .bind(bucket_secs);
+        if resolution != HistoryResolution::Raw {
+            query = query.bind(resolution.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| NetworkHistoryPoint {
+                timestamp: row.get("bucket"),
+                players_avg: row.get("players_avg"),
+                players_max: row.get("players_max"),
+                servers_online: row.get("servers_online"),
+            })
+            .collect())
+    }
 }
@@ -1,15 +1,112 @@
 use async_trait::async_trait;
-use domain::tickets::Ticket;
+use chrono::NaiveDateTime;
+use domain::tickets::{
+    CannedResponse, Ticket, TicketAttachment, TicketCategory, TicketFilter, TicketMessage,
+    TicketMessageRevision, TicketPriority, TicketSlaSample, TicketStaff, TicketStatus,
+    TicketStatusTransition,
+};
 use shared::error::AppResult;
-use sqlx::{PgPool, Row};
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder, Row, Transaction, postgres::PgRow};
+use std::collections::HashMap;
 
 #[async_trait]
 pub trait TicketRepository {
-    async fn fetch_all(&self, user_id: Option<String>) -> AppResult<Vec<Ticket>>;
+    async fn fetch_all(&self, filter: &TicketFilter) -> AppResult<Vec<Ticket>>;
+    /// One page of tickets matching `filter`, ranked by search relevance when
+    /// `filter.q` is set, along with the total number of matches.
+    async fn fetch_page(
+        &self,
+        filter: &TicketFilter,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<(Vec<Ticket>, i64)>;
     async fn find_by_id(&self, id: &str) -> AppResult<Ticket>;
-    async fn insert(&self, ticket: Ticket) -> AppResult<()>;
+    async fn insert(&self, ticket: Ticket, actor: &str) -> AppResult<()>;
     async fn update(&self, id: &str, ticket: Ticket) -> AppResult<()>;
     async fn delete(&self, id: &str) -> AppResult<()>;
+
+    async fn insert_message(
+        &self,
+        ticket_id: &str,
+        message: &TicketMessage,
+    ) -> AppResult<TicketMessage>;
+    async fn find_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+    ) -> AppResult<Option<TicketMessage>>;
+    async fn update_message(
+        &self,
+        message_id: i64,
+        content: &str,
+        edited_by: &str,
+    ) -> AppResult<TicketMessage>;
+    async fn delete_message(&self, message_id: i64, deleted_by: &str) -> AppResult<()>;
+    async fn list_message_revisions(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+    ) -> AppResult<Vec<TicketMessageRevision>>;
+
+    /// Moves the ticket from `from` to `to` and records the transition plus an
+    /// audit entry. Returns `None` when the ticket is no longer in `from`.
+    async fn transition(
+        &self,
+        ticket_id: &str,
+        from: TicketStatus,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<Option<TicketStatusTransition>>;
+    async fn list_transitions(&self, ticket_id: &str) -> AppResult<Vec<TicketStatusTransition>>;
+    /// Tickets in one of `statuses` without a player message since before
+    /// `before`. Time spent in an earlier status does not count, so the player
+    /// always gets the full period once the ticket waits on them.
+    async fn find_stale(
+        &self,
+        statuses: &[TicketStatus],
+        before: NaiveDateTime,
+    ) -> AppResult<Vec<(String, TicketStatus)>>;
+
+    async fn list_staff(&self) -> AppResult<Vec<TicketStaff>>;
+    async fn upsert_staff(&self, staff: &TicketStaff) -> AppResult<TicketStaff>;
+    async fn delete_staff(&self, discord_id: &str, category: TicketCategory) -> AppResult<bool>;
+    /// Picks the active staff member of `category` who was assigned least
+    /// recently and marks them as assigned now.
+    async fn claim_next_staff(&self, category: TicketCategory) -> AppResult<Option<String>>;
+
+    async fn is_staff(&self, discord_id: &str) -> AppResult<bool>;
+    /// Whether `discord_id` opened or is assigned to the ticket.
+    async fn is_participant(&self, ticket_id: &str, discord_id: &str) -> AppResult<bool>;
+    /// Whether the file is attached to a live message of the ticket; internal
+    /// notes only count when `include_internal` is set.
+    async fn has_attachment(
+        &self,
+        ticket_id: &str,
+        file_id: &str,
+        include_internal: bool,
+    ) -> AppResult<bool>;
+    /// Files attached to any message of the ticket, deleted messages included.
+    async fn attachment_file_ids(&self, ticket_id: &str) -> AppResult<Vec<String>>;
+
+    async fn list_canned(&self, category: Option<TicketCategory>)
+    -> AppResult<Vec<CannedResponse>>;
+    async fn find_canned(&self, id: i64) -> AppResult<Option<CannedResponse>>;
+    async fn insert_canned(&self, response: &CannedResponse) -> AppResult<CannedResponse>;
+    async fn update_canned(&self, response: &CannedResponse) -> AppResult<CannedResponse>;
+    async fn delete_canned(&self, id: i64) -> AppResult<bool>;
+
+    /// Tickets created in `[from, to)`.
+    async fn sla_samples(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> AppResult<Vec<TicketSlaSample>>;
+    /// Tickets that are neither resolved nor closed.
+    async fn backlog_samples(&self) -> AppResult<Vec<TicketSlaSample>>;
+
+    async fn set_discord_thread(&self, ticket_id: &str, thread_id: &str) -> AppResult<()>;
+    async fn find_by_discord_thread(&self, thread_id: &str) -> AppResult<Option<Ticket>>;
 }
 
 pub struct PostgresTicketRepository {
@@ -20,86 +117,932 @@ impl PostgresTicketRepository {
     pub fn new(pool: PgPool) -> Self {
         Self { pool }
     }
+
+    fn ticket_from_row(row: &PgRow, messages: Vec<TicketMessage>) -> Ticket {
+        Ticket {
+            id: row.get("id"),
+            slug: row.get("slug"),
+            user_id: row.get("user_id"),
+            title: row.get("title"),
+            status: TicketStatus::parse(row.get("status")).unwrap_or_default(),
+            assignee_id: row.get("assignee_id"),
+            priority: TicketPriority::parse(row.get("priority")).unwrap_or_default(),
+            category: row
+                .get::<Option<&str>, _>("category")
+                .and_then(TicketCategory::parse),
+            labels: row.get("labels"),
+            discord_thread_id: row.get("discord_thread_id"),
+            messages,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    fn push_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &TicketFilter) {
+        query.push(" WHERE TRUE");
+
+        if let Some(user_id) = &filter.user_id {
+            query.push(" AND t.user_id = ").push_bind(user_id.clone());
+        }
+        if let Some(status) = filter.status {
+            query.push(" AND t.status = ").push_bind(status.to_string());
+        }
+        if let Some(assignee_id) = &filter.assignee_id {
+            query
+                .push(" AND t.assignee_id = ")
+                .push_bind(assignee_id.clone());
+        }
+        if let Some(priority) = filter.priority {
+            query
+                .push(" AND t.priority = ")
+                .push_bind(priority.to_string());
+        }
+        if let Some(category) = filter.category {
+            query
+                .push(" AND t.category = ")
+                .push_bind(category.to_string());
+        }
+        if let Some(label) = &filter.label {
+            query
+                .push(" AND ")
+                .push_bind(label.clone())
+                .push(" = ANY(t.labels)");
+        }
+        if filter.active_only {
+            query.push(" AND t.status NOT IN ('resolved', 'closed')");
+        }
+        if let Some(after) = filter.created_after {
+            query
+                .push(" AND t.created_at >= to_timestamp(")
+                .push_bind(after)
+                .push(") AT TIME ZONE 'UTC'");
+        }
+        if let Some(before) = filter.created_before {
+            query
+                .push(" AND t.created_at < to_timestamp(")
+                .push_bind(before)
+                .push(") AT TIME ZONE 'UTC'");
+        }
+        if let Some(q) = search_query(filter) {
+            query
+                .push(" AND (t.search_vector @@ websearch_to_tsquery('simple', ")
+                .push_bind(q.to_string())
+                .push(") OR EXISTS (SELECT 1 FROM ticket_messages m WHERE m.ticket_id = t.id AND m.deleted_at IS NULL");
+            if !filter.include_internal {
+                query.push(" AND NOT m.internal");
+            }
+            query
+                .push(" AND m.search_vector @@ websearch_to_tsquery('simple', ")
+                .push_bind(q.to_string())
+                .push(")))");
+        }
+    }
+
+    async fn count(&self, filter: &TicketFilter) -> AppResult<i64> {
+        let mut query = sqlx::QueryBuilder::new("SELECT COUNT(*) AS total_count FROM tickets t");
+        Self::push_filter(&mut query, filter);
+        let row = query.build().fetch_one(&self.pool).await?;
+        Ok(row.get("total_count"))
+    }
+
+    async fn tickets_from_rows(&self, rows: &[PgRow]) -> AppResult<Vec<Ticket>> {
+        let ids: Vec<String> = rows.iter().map(|row| row.get("id")).collect();
+        let mut messages = self.load_messages(&ids).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let id: String = row.get("id");
+                Self::ticket_from_row(row, messages.remove(&id).unwrap_or_default())
+            })
+            .collect())
+    }
+
+    fn message_from_row(row: &PgRow) -> TicketMessage {
+        TicketMessage {
+            id: row.get("id"),
+            sender: row.get("sender"),
+            content: row.get("content"),
+            sent_at: row.get("sent_at"),
+            edited_at: row.get("edited_at"),
+            attachments: Vec::new(),
+            internal: row.get("internal"),
+            canned_response_id: row.get("canned_response_id"),
+        }
+    }
+
+    async fn fetch_sla_samples(
+        &self,
+        range: Option<(NaiveDateTime, NaiveDateTime)>,
+    ) -> AppResult<Vec<TicketSlaSample>> {
+        let (from, to) = range.unzip();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                t.id, t.category, t.assignee_id, t.status, t.created_at,
+                (
+                    SELECT MIN(m.sent_at)
+                    FROM ticket_messages m
+                    WHERE m.ticket_id = t.id AND m.sender <> t.user_id AND NOT m.internal
+                ) AS first_response_at,
+                CASE WHEN t.status IN ('resolved', 'closed') THEN (
+                    SELECT MAX(s.transitioned_at)
+                    FROM ticket_status_transitions s
+                    WHERE s.ticket_id = t.id AND s.to_status IN ('resolved', 'closed')
+                ) END AS resolved_at
+            FROM tickets t
+            WHERE ($1::timestamp IS NULL OR t.created_at >= $1)
+              AND ($2::timestamp IS NULL OR t.created_at < $2)
+              AND ($1::timestamp IS NOT NULL OR t.status NOT IN ('resolved', 'closed'))
+            ORDER BY t.created_at
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TicketSlaSample {
+                ticket_id: row.get("id"),
+                category: row
+                    .get::<Option<&str>, _>("category")
+                    .and_then(TicketCategory::parse),
+                assignee_id: row.get("assignee_id"),
+                status: TicketStatus::parse(row.get("status")).unwrap_or_default(),
+                created_at: row.get("created_at"),
+                first_response_at: row.get("first_response_at"),
+                resolved_at: row.get("resolved_at"),
+            })
+            .collect())
+    }
+
+    fn canned_from_row(row: &PgRow) -> CannedResponse {
+        CannedResponse {
+            id: row.get("id"),
+            name: row.get("name"),
+            category: row
+                .get::<Option<&str>, _>("category")
+                .and_then(TicketCategory::parse),
+            content: row.get("content"),
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    async fn load_attachments<'e>(
+        executor: impl PgExecutor<'e>,
+        message_ids: &[i64],
+    ) -> AppResult<HashMap<i64, Vec<TicketAttachment>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT a.message_id, f.id AS file_id, f.filename, f.content_type, f.size
+            FROM ticket_message_attachments a
+            JOIN files f ON f.id = a.file_id
+            WHERE a.message_id = ANY($1)
+            ORDER BY f.uploaded_at, f.id
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(executor)
+        .await?;
+
+        let mut attachments: HashMap<i64, Vec<TicketAttachment>> = HashMap::new();
+        for row in rows {
+            attachments
+                .entry(row.get("message_id"))
+                .or_default()
+                .push(TicketAttachment {
+                    file_id: row.get("file_id"),
+                    filename: row.get("filename"),
+                    content_type: row.get("content_type"),
+                    size: row.get("size"),
+                });
+        }
+
+        Ok(attachments)
+    }
+
+    async fn load_messages(
+        &self,
+        ticket_ids: &[String],
+    ) -> AppResult<HashMap<String, Vec<TicketMessage>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT ticket_id, id, sender, content, sent_at, edited_at, internal, canned_response_id
+            FROM ticket_messages
+            WHERE ticket_id = ANY($1) AND deleted_at IS NULL
+            ORDER BY sent_at, id
+            "#,
+        )
+        .bind(ticket_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let message_ids: Vec<i64> = rows.iter().map(|row| row.get("id")).collect();
+        let mut attachments = Self::load_attachments(&self.pool, &message_ids).await?;
+
+        let mut messages: HashMap<String, Vec<TicketMessage>> = HashMap::new();
+        for row in rows {
+            let mut message = Self::message_from_row(&row);
+            message.attachments = attachments.remove(&message.id).unwrap_or_default();
+            messages
+                .entry(row.get("ticket_id"))
+                .or_default()
+                .push(message);
+        }
+
+        Ok(messages)
+    }
+
+    async fn insert_message_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        ticket_id: &str,
+        message: &TicketMessage,
+    ) -> AppResult<TicketMessage> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ticket_messages (ticket_id, sender, content, sent_at, internal, canned_response_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, sender, content, sent_at, edited_at, internal, canned_response_id
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(&message.sender)
+        .bind(&message.content)
+        .bind(message.sent_at)
+        .bind(message.internal)
+        .bind(message.canned_response_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        let mut inserted = Self::message_from_row(&row);
+
+        if !message.attachments.is_empty() {
+            let file_ids: Vec<&str> = message
+                .attachments
+                .iter()
+                .map(|a| a.file_id.as_str())
+                .collect();
+
+            sqlx::query(
+                r#"
+                INSERT INTO ticket_message_attachments (message_id, file_id)
+                SELECT $1, UNNEST($2::text[])
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(inserted.id)
+            .bind(&file_ids)
+            .execute(&mut **tx)
+            .await?;
+
+            inserted.attachments = Self::load_attachments(&mut **tx, &[inserted.id])
+                .await?
+                .remove(&inserted.id)
+                .unwrap_or_default();
+        }
+
+        Ok(inserted)
+    }
+
+    fn staff_from_row(row: &PgRow) -> Option<TicketStaff> {
+        Some(TicketStaff {
+            discord_id: row.get("discord_id"),
+            category: TicketCategory::parse(row.get("category"))?,
+            active: row.get("active"),
+            last_assigned_at: row.get("last_assigned_at"),
+        })
+    }
+
+    fn transition_from_row(row: &PgRow) -> TicketStatusTransition {
+        let from_status: Option<String> = row.get("from_status");
+        TicketStatusTransition {
+            id: row.get("id"),
+            ticket_id: row.get("ticket_id"),
+            from_status: from_status.as_deref().and_then(TicketStatus::parse),
+            to_status: TicketStatus::parse(row.get("to_status")).unwrap_or_default(),
+            actor: row.get("actor"),
+            reason: row.get("reason"),
+            transitioned_at: row.get("transitioned_at"),
+        }
+    }
+
+    async fn insert_transition_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        ticket_id: &str,
+        from: Option<TicketStatus>,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<TicketStatusTransition> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ticket_status_transitions (ticket_id, from_status, to_status, actor, reason)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, ticket_id, from_status, to_status, actor, reason, transitioned_at
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(from.map(|s| s.to_string()))
+        .bind(to.to_string())
+        .bind(actor)
+        .bind(reason)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(Self::transition_from_row(&row))
+    }
 }
 
 #[async_trait]
 impl TicketRepository for PostgresTicketRepository {
-    async fn fetch_all(&self, user_id: Option<String>) -> AppResult<Vec<Ticket>> {
-        let rows = if let Some(uid) = user_id {
-            sqlx::query("SELECT * FROM tickets WHERE user_id = $1")
-                .bind(uid)
-                .fetch_all(&self.pool)
-                .await?
+    async fn fetch_all(&self, filter: &TicketFilter) -> AppResult<Vec<Ticket>> {
+        let mut query = sqlx::QueryBuilder::new("SELECT * FROM tickets t");
+        Self::push_filter(&mut query, filter);
+        query.push(" ORDER BY t.created_at DESC, t.id");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        self.tickets_from_rows(&rows).await
+    }
+
+    async fn fetch_page(
+        &self,
+        filter: &TicketFilter,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<(Vec<Ticket>, i64)> {
+        let mut query = sqlx::QueryBuilder::new("SELECT t.*, COUNT(*) OVER () AS total_count");
+        if let Some(q) = search_query(filter) {
+            query
+                .push(", ts_rank(t.search_vector, websearch_to_tsquery('simple', ")
+                .push_bind(q)
+                .push(")) AS rank");
+        }
+        query.push(" FROM tickets t");
+        Self::push_filter(&mut query, filter);
+
+        if search_query(filter).is_some() {
+            query.push(" ORDER BY rank DESC, t.created_at DESC, t.id");
         } else {
-            sqlx::query("SELECT * FROM tickets")
-                .fetch_all(&self.pool)
-                .await?
-        };
+            query.push(" ORDER BY t.created_at DESC, t.id");
+        }
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
 
-        let tickets = rows
-            .into_iter()
-            .map(|row| Ticket {
-                id: row.get("id"),
-                user_id: row.get("user_id"),
-                title: row.get("title"),
-                status: row.get("status"),
-                messages: serde_json::from_value(row.get("messages")).unwrap_or_default(),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
-            .collect();
+        let rows = query.build().fetch_all(&self.pool).await?;
+        let total = match rows.first() {
+            Some(row) => row.get("total_count"),
+            None => self.count(filter).await?,
+        };
 
-        Ok(tickets)
+        Ok((self.tickets_from_rows(&rows).await?, total))
     }
 
     async fn find_by_id(&self, id: &str) -> AppResult<Ticket> {
-        let row = sqlx::query("SELECT * FROM tickets WHERE id = $1")
+        let row = sqlx::query("SELECT * FROM tickets WHERE id = $1 OR slug = $1")
             .bind(id)
             .fetch_one(&self.pool)
             .await?;
 
-        Ok(Ticket {
-            id: row.get("id"),
-            user_id: row.get("user_id"),
-            title: row.get("title"),
-            status: row.get("status"),
-            messages: serde_json::from_value(row.get("messages")).unwrap_or_default(),
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        })
+        let id: String = row.get("id");
+        let mut messages = self.load_messages(std::slice::from_ref(&id)).await?;
+
+        Ok(Self::ticket_from_row(
+            &row,
+            messages.remove(&id).unwrap_or_default(),
+        ))
     }
 
-    async fn insert(&self, ticket: Ticket) -> AppResult<()> {
-        sqlx::query("INSERT INTO tickets (id, user_id, title, status, messages, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)")
-            .bind(&ticket.id)
-            .bind(&ticket.user_id)
-            .bind(&ticket.title)
-            .bind(&ticket.status)
-            .bind(serde_json::to_value(&ticket.messages)?)
-            .bind(ticket.created_at)
-            .bind(ticket.updated_at)
-            .execute(&self.pool)
-            .await?;
+    async fn insert(&self, ticket: Ticket, actor: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO tickets (
+                id, user_id, title, status, assignee_id, priority, category, labels,
+                created_at, updated_at, status_changed_at, slug
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $9, $11)
+            "#,
+        )
+        .bind(&ticket.id)
+        .bind(&ticket.user_id)
+        .bind(&ticket.title)
+        .bind(ticket.status.to_string())
+        .bind(&ticket.assignee_id)
+        .bind(ticket.priority.to_string())
+        .bind(ticket.category.map(|c| c.to_string()))
+        .bind(&ticket.labels)
+        .bind(ticket.created_at)
+        .bind(ticket.updated_at)
+        .bind(&ticket.slug)
+        .execute(&mut *tx)
+        .await?;
+
+        Self::insert_transition_tx(&mut tx, &ticket.id, None, ticket.status, actor, None).await?;
+
+        for message in &ticket.messages {
+            Self::insert_message_tx(&mut tx, &ticket.id, message).await?;
+        }
+
+        tx.commit().await?;
         Ok(())
     }
 
+    /// Updates the ticket's own fields. Messages and status are managed
+    /// through their dedicated methods and are not rewritten here.
     async fn update(&self, id: &str, ticket: Ticket) -> AppResult<()> {
-        sqlx::query("UPDATE tickets SET title = $1, status = $2, messages = $3, updated_at = $4 WHERE id = $5")
-            .bind(ticket.title)
-            .bind(ticket.status)
-            .bind(serde_json::to_value(ticket.messages)?)
-            .bind(ticket.updated_at)
+        sqlx::query(
+            r#"
+            UPDATE tickets
+            SET title = $1, assignee_id = $2, priority = $3, category = $4, labels = $5, updated_at = $6
+            WHERE id = $7
+            "#,
+        )
+        .bind(ticket.title)
+        .bind(ticket.assignee_id)
+        .bind(ticket.priority.to_string())
+        .bind(ticket.category.map(|c| c.to_string()))
+        .bind(ticket.labels)
+        .bind(ticket.updated_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> AppResult<()> {
+        sqlx::query("DELETE FROM tickets WHERE id = $1")
             .bind(id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    async fn delete(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM tickets WHERE id = $1")
+    async fn insert_message(
+        &self,
+        ticket_id: &str,
+        message: &TicketMessage,
+    ) -> AppResult<TicketMessage> {
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = Self::insert_message_tx(&mut tx, ticket_id, message).await?;
+
+        sqlx::query("UPDATE tickets SET updated_at = $1 WHERE id = $2")
+            .bind(message.sent_at)
+            .bind(ticket_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn find_message(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+    ) -> AppResult<Option<TicketMessage>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, sender, content, sent_at, edited_at, internal, canned_response_id
+            FROM ticket_messages
+            WHERE ticket_id = $1 AND id = $2 AND deleted_at IS NULL
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let mut message = Self::message_from_row(&row);
+        message.attachments = Self::load_attachments(&self.pool, &[message.id])
+            .await?
+            .remove(&message.id)
+            .unwrap_or_default();
+
+        Ok(Some(message))
+    }
+
+    async fn update_message(
+        &self,
+        message_id: i64,
+        content: &str,
+        edited_by: &str,
+    ) -> AppResult<TicketMessage> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ticket_message_revisions (message_id, action, content, edited_by)
+            SELECT id, 'edit', content, $2 FROM ticket_messages WHERE id = $1
+            "#,
+        )
+        .bind(message_id)
+        .bind(edited_by)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query(
+            r#"
+            UPDATE ticket_messages SET content = $2, edited_at = NOW()
+            WHERE id = $1
+            RETURNING id, sender, content, sent_at, edited_at, internal, canned_response_id
+            "#,
+        )
+        .bind(message_id)
+        .bind(content)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Self::message_from_row(&row))
+    }
+
+    async fn delete_message(&self, message_id: i64, deleted_by: &str) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO ticket_message_revisions (message_id, action, content, edited_by)
+            SELECT id, 'delete', content, $2 FROM ticket_messages WHERE id = $1
+            "#,
+        )
+        .bind(message_id)
+        .bind(deleted_by)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE ticket_messages SET deleted_at = NOW(), deleted_by = $2 WHERE id = $1")
+            .bind(message_id)
+            .bind(deleted_by)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_message_revisions(
+        &self,
+        ticket_id: &str,
+        message_id: i64,
+    ) -> AppResult<Vec<TicketMessageRevision>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id, r.message_id, r.action, r.content, r.edited_by, r.edited_at
+            FROM ticket_message_revisions r
+            JOIN ticket_messages m ON m.id = r.message_id
+            WHERE m.ticket_id = $1 AND r.message_id = $2
+            ORDER BY r.edited_at, r.id
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(message_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TicketMessageRevision {
+                id: row.get("id"),
+                message_id: row.get("message_id"),
+                action: row.get("action"),
+                content: row.get("content"),
+                edited_by: row.get("edited_by"),
+                edited_at: row.get("edited_at"),
+            })
+            .collect())
+    }
+
+    async fn transition(
+        &self,
+        ticket_id: &str,
+        from: TicketStatus,
+        to: TicketStatus,
+        actor: &str,
+        reason: Option<&str>,
+    ) -> AppResult<Option<TicketStatusTransition>> {
+        let mut tx = self.pool.begin().await?;
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE tickets
+            SET status = $3, status_changed_at = NOW(), updated_at = NOW()
+            WHERE id = $1 AND status = $2
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(from.to_string())
+        .bind(to.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let transition =
+            Self::insert_transition_tx(&mut tx, ticket_id, Some(from), to, actor, reason).await?;
+
+        tx.commit().await?;
+        Ok(Some(transition))
+    }
+
+    async fn list_transitions(&self, ticket_id: &str) -> AppResult<Vec<TicketStatusTransition>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, ticket_id, from_status, to_status, actor, reason, transitioned_at
+            FROM ticket_status_transitions
+            WHERE ticket_id = $1
+            ORDER BY transitioned_at, id
+            "#,
+        )
+        .bind(ticket_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::transition_from_row).collect())
+    }
+
+    async fn find_stale(
+        &self,
+        statuses: &[TicketStatus],
+        before: NaiveDateTime,
+    ) -> AppResult<Vec<(String, TicketStatus)>> {
+        let statuses: Vec<String> = statuses.iter().map(|s| s.to_string()).collect();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT t.id, t.status
+            FROM tickets t
+            LEFT JOIN LATERAL (
+                SELECT MAX(m.sent_at) AS sent_at
+                FROM ticket_messages m
+                WHERE m.ticket_id = t.id AND m.sender = t.user_id AND m.deleted_at IS NULL
+            ) player ON TRUE
+            WHERE t.status = ANY($1)
+              AND GREATEST(player.sent_at, t.status_changed_at) < $2
+            "#,
+        )
+        .bind(&statuses)
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let status: String = row.get("status");
+                TicketStatus::parse(&status).map(|status| (row.get("id"), status))
+            })
+            .collect())
+    }
+
+    async fn list_staff(&self) -> AppResult<Vec<TicketStaff>> {
+        let rows = sqlx::query("SELECT * FROM ticket_staff ORDER BY category, discord_id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().filter_map(Self::staff_from_row).collect())
+    }
+
+    async fn upsert_staff(&self, staff: &TicketStaff) -> AppResult<TicketStaff> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ticket_staff (discord_id, category, active)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (discord_id, category) DO UPDATE SET active = EXCLUDED.active
+            RETURNING *
+            "#,
+        )
+        .bind(&staff.discord_id)
+        .bind(staff.category.to_string())
+        .bind(staff.active)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Self::staff_from_row(&row).ok_or_else(|| anyhow::anyhow!("Invalid staff category"))
+    }
+
+    async fn delete_staff(&self, discord_id: &str, category: TicketCategory) -> AppResult<bool> {
+        let result =
+            sqlx::query("DELETE FROM ticket_staff WHERE discord_id = $1 AND category = $2")
+                .bind(discord_id)
+                .bind(category.to_string())
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn claim_next_staff(&self, category: TicketCategory) -> AppResult<Option<String>> {
+        let row = sqlx::query(
+            r#"
+            UPDATE ticket_staff
+            SET last_assigned_at = NOW()
+            WHERE (discord_id, category) = (
+                SELECT discord_id, category
+                FROM ticket_staff
+                WHERE category = $1 AND active
+                ORDER BY last_assigned_at NULLS FIRST, discord_id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING discord_id
+            "#,
+        )
+        .bind(category.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.get("discord_id")))
+    }
+
+    async fn is_staff(&self, discord_id: &str) -> AppResult<bool> {
+        let row = sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM ticket_staff WHERE discord_id = $1 AND active) AS staff",
+        )
+        .bind(discord_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("staff"))
+    }
+
+    async fn is_participant(&self, ticket_id: &str, discord_id: &str) -> AppResult<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM tickets
+                WHERE id = $1 AND (user_id = $2 OR assignee_id = $2)
+            ) AS participant
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(discord_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("participant"))
+    }
+
+    async fn has_attachment(
+        &self,
+        ticket_id: &str,
+        file_id: &str,
+        include_internal: bool,
+    ) -> AppResult<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM ticket_message_attachments a
+                JOIN ticket_messages m ON m.id = a.message_id
+                WHERE m.ticket_id = $1 AND a.file_id = $2 AND m.deleted_at IS NULL
+                  AND ($3 OR NOT m.internal)
+            ) AS attached
+            "#,
+        )
+        .bind(ticket_id)
+        .bind(file_id)
+        .bind(include_internal)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("attached"))
+    }
+
+    async fn attachment_file_ids(&self, ticket_id: &str) -> AppResult<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT DISTINCT a.file_id
+            FROM ticket_message_attachments a
+            JOIN ticket_messages m ON m.id = a.message_id
+            WHERE m.ticket_id = $1
+            "#,
+        )
+        .bind(ticket_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| row.get("file_id")).collect())
+    }
+
+    async fn list_canned(
+        &self,
+        category: Option<TicketCategory>,
+    ) -> AppResult<Vec<CannedResponse>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM ticket_canned_responses
+            WHERE $1::text IS NULL OR category IS NULL OR category = $1
+            ORDER BY name
+            "#,
+        )
+        .bind(category.map(|c| c.to_string()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(Self::canned_from_row).collect())
+    }
+
+    async fn find_canned(&self, id: i64) -> AppResult<Option<CannedResponse>> {
+        let row = sqlx::query("SELECT * FROM ticket_canned_responses WHERE id = $1")
             .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(Self::canned_from_row))
+    }
+
+    async fn insert_canned(&self, response: &CannedResponse) -> AppResult<CannedResponse> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO ticket_canned_responses (name, category, content, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+        )
+        .bind(&response.name)
+        .bind(response.category.map(|c| c.to_string()))
+        .bind(&response.content)
+        .bind(&response.created_by)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Self::canned_from_row(&row))
+    }
+
+    async fn update_canned(&self, response: &CannedResponse) -> AppResult<CannedResponse> {
+        let row = sqlx::query(
+            r#"
+            UPDATE ticket_canned_responses
+            SET name = $2, category = $3, content = $4, updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(response.id)
+        .bind(&response.name)
+        .bind(response.category.map(|c| c.to_string()))
+        .bind(&response.content)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Self::canned_from_row(&row))
+    }
+
+    async fn delete_canned(&self, id: i64) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM ticket_canned_responses WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn sla_samples(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> AppResult<Vec<TicketSlaSample>> {
+        self.fetch_sla_samples(Some((from, to))).await
+    }
+
+    async fn backlog_samples(&self) -> AppResult<Vec<TicketSlaSample>> {
+        self.fetch_sla_samples(None).await
+    }
+
+    async fn set_discord_thread(&self, ticket_id: &str, thread_id: &str) -> AppResult<()> {
+        sqlx::query("UPDATE tickets SET discord_thread_id = $2 WHERE id = $1")
+            .bind(ticket_id)
+            .bind(thread_id)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
+
+    async fn find_by_discord_thread(&self, thread_id: &str) -> AppResult<Option<Ticket>> {
+        let row = sqlx::query("SELECT * FROM tickets WHERE discord_thread_id = $1")
+            .bind(thread_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(self.tickets_from_rows(&[row]).await?.pop()),
+            None => Ok(None),
+        }
+    }
+}
+
+fn search_query(filter: &TicketFilter) -> Option<&str> {
+    filter.q.as_deref().map(str::trim).filter(|q| !q.is_empty())
 }
@@ -0,0 +1,60 @@
+use crate::{
+    repositorys::status::{PostgresStatusRepository, StatusRepository},
+    status_watcher::{RollupConfig, ServerConfig},
+};
+use chrono::Utc;
+use domain::status::HistoryResolution;
+use shared::error::AppResult;
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::sleep;
+
+pub async fn start_status_rollup(pool: PgPool, config: ServerConfig) -> AppResult<()> {
+    let repo = PostgresStatusRepository::new(pool);
+    let server_ids: Vec<String> = config.servers.iter().map(|s| s.id.clone()).collect();
+    let config = config.rollup;
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = run_rollup(&repo, &config, &server_ids).await {
+                tracing::error!("Failed to roll up status history: {}", err);
+            }
+            sleep(Duration::from_secs(config.interval_secs.max(1))).await;
+        }
+    });
+
+    Ok(())
+}
+
+async fn run_rollup(
+    repo: &PostgresStatusRepository,
+    config: &RollupConfig,
+    server_ids: &[String],
+) -> AppResult<()> {
+    let hourly = repo.rollup(HistoryResolution::Hour).await?;
+    let daily = repo.rollup(HistoryResolution::Day).await?;
+    let peaks = repo.update_network_peaks(server_ids).await?;
+
+    let now = Utc::now().timestamp();
+    let purged_raw = repo
+        .purge_raw_before(now - config.raw_retention_secs())
+        .await?;
+    let purged_hourly = match config.hourly_retention_secs() {
+        Some(secs) => {
+            repo.purge_rollups_before(HistoryResolution::Hour, now - secs)
+                .await?
+        }
+        None => 0,
+    };
+
+    tracing::debug!(
+        "Status rollup: {} hourly and {} daily buckets and {} network peaks written, {} raw samples and {} hourly buckets purged",
+        hourly,
+        daily,
+        peaks,
+        purged_raw,
+        purged_hourly
+    );
+
+    Ok(())
+}
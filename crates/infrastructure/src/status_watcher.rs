@@ -1,69 +1,366 @@
-use crate::repositorys::status::StatusRepository;
+use crate::repositorys::{
+    incident::{IncidentRepository, PostgresIncidentRepository},
+    status::{PostgresStatusRepository, StatusRepository},
+};
 use chrono::Utc;
 use domain::status::{Players, StatusRecord};
 use serde::Deserialize;
 use shared::error::AppResult;
 use sqlx::PgPool;
-use std::{fs, time::Duration};
-use tokio::time::sleep;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::time::{sleep, timeout};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ServerEntry {
     pub id: String,
     pub address: String,
     pub port: u16,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     pub servers: Vec<ServerEntry>,
+    #[serde(default)]
+    pub probe: ProbeConfig,
+    #[serde(default)]
+    pub rollup: RollupConfig,
+    #[serde(default)]
+    pub alerts: AlertConfig,
 }
 
-pub async fn start_status_watcher(pool: PgPool) -> AppResult<()> {
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProbeConfig {
+    /// Seconds between two probes of a healthy server.
+    pub interval_secs: u64,
+    /// Upper bound for a single status query.
+    pub timeout_ms: u64,
+    /// Extra attempts before a server is recorded as offline.
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+    /// Random delay added to every schedule so probes do not line up.
+    pub jitter_ms: u64,
+    /// Cap for the exponential backoff applied to servers that stay down.
+    pub max_backoff_secs: u64,
+    /// Consecutive offline probes after which an incident is opened.
+    pub incident_threshold: u32,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 60,
+            timeout_ms: 5_000,
+            retries: 2,
+            retry_delay_ms: 1_000,
+            jitter_ms: 5_000,
+            max_backoff_secs: 600,
+            incident_threshold: 3,
+        }
+    }
+}
+
+impl ProbeConfig {
+    fn next_delay(&self, consecutive_failures: u32) -> Duration {
+        let base = self.interval_secs.max(1);
+        let secs = if consecutive_failures <= 1 {
+            base
+        } else {
+            let factor = 1u64 << (consecutive_failures - 1).min(16);
+            base.saturating_mul(factor)
+                .min(self.max_backoff_secs.max(base))
+        };
+
+        Duration::from_secs(secs) + self.jitter()
+    }
+
+    fn jitter(&self) -> Duration {
+        if self.jitter_ms == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_millis(rand::random_range(0..=self.jitter_ms))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RollupConfig {
+    /// Seconds between two rollup runs.
+    pub interval_secs: u64,
+    /// Days of raw samples kept before they are deleted. Never below 2 so the
+    /// current day can always be rolled up from raw samples.
+    pub raw_retention_days: u32,
+    /// Days of hourly aggregates kept; `0` keeps them forever.
+    pub hourly_retention_days: u32,
+}
+
+impl Default for RollupConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            raw_retention_days: 14,
+            hourly_retention_days: 365,
+        }
+    }
+}
+
+impl RollupConfig {
+    pub fn raw_retention_secs(&self) -> i64 {
+        i64::from(self.raw_retention_days.max(2)) * 24 * 60 * 60
+    }
+
+    pub fn hourly_retention_secs(&self) -> Option<i64> {
+        (self.hourly_retention_days > 0)
+            .then(|| i64::from(self.hourly_retention_days) * 24 * 60 * 60)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// Seconds between two evaluations of the alert rules.
+    pub interval_secs: u64,
+    /// Timeout for a single webhook request.
+    pub timeout_ms: u64,
+    pub max_attempts: u32,
+    /// Passes a failing notification is sent on before it is given up.
+    pub max_deliveries: u32,
+    /// Upper bound for the wait between two passes of a failing notification.
+    pub max_backoff_secs: u64,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            timeout_ms: 5_000,
+            max_attempts: 3,
+            max_deliveries: 8,
+            max_backoff_secs: 3_600,
+        }
+    }
+}
+
+impl AlertConfig {
+    /// Seconds to wait before the next pass of a notification that failed on
+    /// `failures` passes, doubling from the evaluation interval.
+    pub fn retry_delay(&self, failures: u32) -> u64 {
+        let base = self.interval_secs.max(1);
+        let factor = 1u64 << failures.saturating_sub(1).min(16);
+        base.saturating_mul(factor)
+            .min(self.max_backoff_secs.max(base))
+    }
+}
+
+pub fn load_server_config() -> AppResult<ServerConfig> {
     let config_text = fs::read_to_string("config/status.toml")?;
-    let config: ServerConfig = toml::from_str(&config_text)?;
-
-    let repo = crate::repositorys::status::PostgresStatusRepository::new(pool);
-
-    tokio::spawn(async move {
-        loop {
-            for server in &config.servers {
-                let result = query_minecraft_status(&server.address, server.port).await;
-                let timestamp = Utc::now().timestamp();
-
-                let record = match result {
-                    Ok((latency, online_players, max_players)) => StatusRecord {
-                        online: true,
-                        latency: Some(latency as i32),
-                        players: Some(Players {
-                            online: online_players as i32,
-                            max: max_players as i32,
-                        }),
-                        timestamp,
-                    },
-                    Err(_) => StatusRecord {
-                        online: false,
-                        latency: None,
-                        players: None,
-                        timestamp,
-                    },
-                };
+    Ok(toml::from_str(&config_text)?)
+}
+
+pub async fn start_status_watcher(pool: PgPool) -> AppResult<()> {
+    let config = load_server_config()?;
+
+    for server in config.servers {
+        let repo = PostgresStatusRepository::new(pool.clone());
+        let incidents = PostgresIncidentRepository::new(pool.clone());
+        let probe = config.probe.clone();
+
+        tokio::spawn(async move {
+            watch_server(server, probe, repo, incidents).await;
+        });
+    }
+
+    Ok(())
+}
 
-                if let Err(err) = repo.insert(&server.id, &record).await {
-                    tracing::error!("Failed to insert status for {}: {}", server.id, err);
-                }
+async fn watch_server(
+    server: ServerEntry,
+    probe: ProbeConfig,
+    repo: PostgresStatusRepository,
+    incidents: PostgresIncidentRepository,
+) {
+    let mut consecutive_failures: u32 = 0;
+    let mut first_failure_at: Option<i64> = None;
+    let mut incident_open = match incidents.find_open(&server.id).await {
+        Ok(open) => open.is_some(),
+        Err(err) => {
+            tracing::error!("Failed to load open incident for {}: {}", server.id, err);
+            false
+        }
+    };
+
+    sleep(probe.jitter()).await;
+
+    loop {
+        let record = probe_server(&server, &probe).await;
+
+        if record.online {
+            consecutive_failures = 0;
+            first_failure_at = None;
+        } else {
+            consecutive_failures = consecutive_failures.saturating_add(1);
+            first_failure_at.get_or_insert(record.timestamp);
+            tracing::warn!(
+                "Server {} is offline ({} consecutive failed probes)",
+                server.id,
+                consecutive_failures
+            );
+        }
+
+        if let Err(err) = repo.insert(&server.id, &record).await {
+            tracing::error!("Failed to insert status for {}: {}", server.id, err);
+        }
+
+        incident_open = track_incident(
+            &incidents,
+            &server.id,
+            &record,
+            consecutive_failures,
+            first_failure_at,
+            incident_open,
+            probe.incident_threshold.max(1),
+        )
+        .await;
+
+        sleep(probe.next_delay(consecutive_failures)).await;
+    }
+}
+
+/// Opens, extends or closes the server's incident and returns whether one is
+/// open afterwards. Database errors leave the in-memory state unchanged so the
+/// transition is retried on the next probe.
+async fn track_incident(
+    incidents: &PostgresIncidentRepository,
+    server_id: &str,
+    record: &StatusRecord,
+    consecutive_failures: u32,
+    first_failure_at: Option<i64>,
+    incident_open: bool,
+    threshold: u32,
+) -> bool {
+    if record.online {
+        if !incident_open {
+            return false;
+        }
+        return match incidents.close(server_id, record.timestamp).await {
+            Ok(_) => {
+                tracing::info!("Server {} is back online; incident closed", server_id);
+                false
             }
-            sleep(Duration::from_secs(60)).await;
+            Err(err) => {
+                tracing::error!("Failed to close incident for {}: {}", server_id, err);
+                true
+            }
+        };
+    }
+
+    if incident_open {
+        if let Err(err) = incidents.record_failure(server_id).await {
+            tracing::error!("Failed to update incident for {}: {}", server_id, err);
         }
-    });
+        return true;
+    }
 
-    Ok(())
+    if consecutive_failures < threshold {
+        return false;
+    }
+
+    let started_at = first_failure_at.unwrap_or(record.timestamp);
+    match incidents
+        .open(server_id, started_at, consecutive_failures as i32)
+        .await
+    {
+        Ok(id) => {
+            tracing::warn!("Opened incident {} for server {}", id, server_id);
+            true
+        }
+        Err(err) => {
+            tracing::error!("Failed to open incident for {}: {}", server_id, err);
+            false
+        }
+    }
 }
 
-async fn query_minecraft_status(address: &str, port: u16) -> AppResult<(u32, u32, u32)> {
+async fn probe_server(server: &ServerEntry, probe: &ProbeConfig) -> StatusRecord {
+    let started = Instant::now();
+    let attempt_timeout = Duration::from_millis(probe.timeout_ms.max(1));
+
+    let mut last_error = None;
+
+    for attempt in 0..=probe.retries {
+        if attempt > 0 {
+            sleep(Duration::from_millis(probe.retry_delay_ms)).await;
+        }
+
+        let attempt_started = Instant::now();
+        let result = timeout(
+            attempt_timeout,
+            query_minecraft_status(&server.address, server.port),
+        )
+        .await;
+
+        match result {
+            Ok(Ok((online_players, max_players))) => {
+                return StatusRecord {
+                    online: true,
+                    latency: Some(elapsed_ms(attempt_started)),
+                    players: Some(Players {
+                        online: online_players as i32,
+                        max: max_players as i32,
+                    }),
+                    timestamp: Utc::now().timestamp(),
+                    probe_duration_ms: Some(elapsed_ms(started)),
+                };
+            }
+            Ok(Err(err)) => last_error = Some(err.to_string()),
+            Err(_) => last_error = Some(format!("timed out after {:?}", attempt_timeout)),
+        }
+    }
+
+    tracing::debug!(
+        "Probe of {} failed after {} attempts: {}",
+        server.id,
+        probe.retries + 1,
+        last_error.unwrap_or_default()
+    );
+
+    StatusRecord {
+        online: false,
+        latency: None,
+        players: None,
+        timestamp: Utc::now().timestamp(),
+        probe_duration_ms: Some(elapsed_ms(started)),
+    }
+}
+
+fn elapsed_ms(since: Instant) -> i32 {
+    since.elapsed().as_millis().min(i32::MAX as u128) as i32
+}
+
+async fn query_minecraft_status(address: &str, port: u16) -> AppResult<(u32, u32)> {
     use mc_query::status;
 
     let data = status(address, port).await?;
 
-    Ok((0, data.players.online as u32, data.players.max as u32))
+    Ok((data.players.online as u32, data.players.max as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlertConfig;
+
+    #[test]
+    fn doubles_alert_retry_delay_up_to_the_cap() {
+        let config = AlertConfig {
+            interval_secs: 30,
+            max_backoff_secs: 200,
+            ..Default::default()
+        };
+        let delays: Vec<u64> = (1..=5).map(|f| config.retry_delay(f)).collect();
+        assert_eq!(delays, [30, 60, 120, 200, 200]);
+        assert_eq!(config.retry_delay(u32::MAX), 200);
+    }
 }
@@ -0,0 +1,364 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use shared::error::{ApiError, AppResult};
+use std::{
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+};
+
+use super::{ObjectHead, ObjectStore, PendingMultipart, UploadedPart};
+
+/// Directory under the root holding the parts of unfinished uploads.
+const STAGING_DIR: &str = ".uploads";
+/// File in an upload's staging directory recording its object key.
+const KEY_FILE: &str = "key";
+
+/// Stores objects on local disk so uploads work without cloud credentials.
+/// Part URLs point back at the API, which accepts them through
+/// [`LocalObjectStore::write_part`] after checking their signature. Download
+/// URLs are served the same way and checked by
+/// [`LocalObjectStore::verify_download`].
+pub struct LocalObjectStore {
+    root: PathBuf,
+    public_base_url: String,
+    secret: Vec<u8>,
+}
+
+impl LocalObjectStore {
+    pub fn new(root: PathBuf, public_base_url: &str, secret: Vec<u8>) -> Self {
+        Self {
+            root,
+            public_base_url: public_base_url.trim_end_matches('/').to_string(),
+            secret,
+        }
+    }
+
+    /// Path of the object stored under `key`.
+    pub fn object_path(&self, key: &str) -> AppResult<PathBuf> {
+        let relative = Path::new(key);
+        let safe = !key.is_empty()
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            && !key.starts_with(STAGING_DIR);
+        if !safe {
+            return Err(anyhow::anyhow!("Invalid object key '{}'", key));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// Stores one part sent to a URL from [`ObjectStore::presign_part`] and
+    /// returns its ETag.
+    pub async fn write_part(
+        &self,
+        upload_id: &str,
+        part_number: i32,
+        expires: i64,
+        signature: &str,
+        data: &[u8],
+    ) -> AppResult<String> {
+        if expires < Utc::now().timestamp() {
+            return Err(ApiError::forbidden("url_expired", "The upload URL has expired").into());
+        }
+        if !self.verify(&part_message(upload_id, part_number, expires), signature) {
+            return Err(
+                ApiError::forbidden("invalid_signature", "The upload URL is not valid").into(),
+            );
+        }
+
+        let dir = self.staging_path(upload_id)?;
+        if !fs::try_exists(&dir).await? {
+            return Err(ApiError::not_found(
+                "upload_not_found",
+                format!("Upload '{}' does not exist", upload_id),
+            )
+            .into());
+        }
+
+        fs::write(dir.join(part_file(part_number)), data).await?;
+        Ok(hex::encode(Sha256::digest(data)))
+    }
+
+    fn staging_path(&self, upload_id: &str) -> AppResult<PathBuf> {
+        if upload_id.is_empty() || !upload_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ApiError::not_found(
+                "upload_not_found",
+                format!("Upload '{}' does not exist", upload_id),
+            )
+            .into());
+        }
+        Ok(self.root.join(STAGING_DIR).join(upload_id))
+    }
+
+    /// Checks a URL from [`ObjectStore::presign_get`] for `key`.
+    pub fn verify_download(&self, key: &str, expires: i64, signature: &str) -> AppResult<()> {
+        if expires < Utc::now().timestamp() {
+            return Err(ApiError::forbidden("url_expired", "The download URL has expired").into());
+        }
+        if !self.verify(&download_message(key, expires), signature) {
+            return Err(
+                ApiError::forbidden("invalid_signature", "The download URL is not valid").into(),
+            );
+        }
+        Ok(())
+    }
+
+    fn mac(&self, message: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(message.as_bytes());
+        mac
+    }
+
+    fn sign(&self, message: &str) -> String {
+        hex::encode(self.mac(message).finalize().into_bytes())
+    }
+
+    fn verify(&self, message: &str, signature: &str) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        self.mac(message).verify_slice(&signature).is_ok()
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalObjectStore {
+    async fn create_multipart(&self, key: &str, _content_type: &str) -> AppResult<String> {
+        self.object_path(key)?;
+
+        let upload_id = hex::encode(rand::random::<[u8; 16]>());
+        let dir = self.staging_path(&upload_id)?;
+        fs::create_dir_all(&dir).await?;
+        fs::write(dir.join(KEY_FILE), key).await?;
+        Ok(upload_id)
+    }
+
+    async fn presign_part(
+        &self,
+        _key: &str,
+        upload_id: &str,
+        part_number: i32,
+        expires_in: Duration,
+    ) -> AppResult<String> {
+        let expires = Utc::now().timestamp() + expires_in.as_secs() as i64;
+        let signature = self.sign(&part_message(upload_id, part_number, expires));
+
+        Ok(format!(
+            "{}/v1/storage/uploads/{}/parts/{}?expires={}&signature={}",
+            self.public_base_url, upload_id, part_number, expires, signature
+        ))
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> AppResult<()> {
+        let dir = self.staging_path(upload_id)?;
+        let target = self.object_path(key)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let partial = partial_path(&target);
+        let mut out = fs::File::create(&partial).await?;
+        for part in parts {
+            let Ok(data) = fs::read(dir.join(part_file(part.part_number))).await else {
+                fs::remove_file(&partial).await.ok();
+                return Err(ApiError::bad_request(
+                    "part_missing",
+                    format!("Part {} was never uploaded", part.part_number),
+                )
+                .into());
+            };
+            if hex::encode(Sha256::digest(&data)) != part.etag.trim_matches('"') {
+                fs::remove_file(&partial).await.ok();
+                return Err(ApiError::bad_request(
+                    "etag_mismatch",
+                    format!("ETag of part {} does not match", part.part_number),
+                )
+                .into());
+            }
+            out.write_all(&data).await?;
+        }
+        out.flush().await?;
+        drop(out);
+
+        fs::rename(&partial, &target).await?;
+        fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, _key: &str, upload_id: &str) -> AppResult<()> {
+        match fs::remove_dir_all(self.staging_path(upload_id)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list_multipart(&self, prefix: &str) -> AppResult<Vec<PendingMultipart>> {
+        let mut entries = match fs::read_dir(self.root.join(STAGING_DIR)).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut pending = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Ok(key) = fs::read_to_string(entry.path().join(KEY_FILE)).await else {
+                continue;
+            };
+            if !key.starts_with(prefix) {
+                continue;
+            }
+
+            let initiated_at = entry
+                .metadata()
+                .await
+                .and_then(|m| m.modified())
+                .ok()
+                .map(DateTime::<Utc>::from);
+            pending.push(PendingMultipart {
+                key,
+                upload_id: entry.file_name().to_string_lossy().into_owned(),
+                initiated_at,
+            });
+        }
+
+        Ok(pending)
+    }
+
+    async fn head_object(&self, key: &str) -> AppResult<ObjectHead> {
+        let metadata = fs::metadata(self.object_path(key)?).await?;
+        Ok(ObjectHead {
+            size: metadata.len() as i64,
+        })
+    }
+
+    async fn read_prefix(&self, key: &str, len: usize) -> AppResult<Vec<u8>> {
+        let file = fs::File::open(self.object_path(key)?).await?;
+        let mut data = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn get_object(&self, key: &str) -> AppResult<Vec<u8>> {
+        Ok(fs::read(self.object_path(key)?).await?)
+    }
+
+    async fn put_object(&self, key: &str, data: &[u8], _content_type: &str) -> AppResult<()> {
+        let target = self.object_path(key)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(target, data).await?;
+        Ok(())
+    }
+
+    async fn put_stream(
+        &self,
+        key: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        _content_type: &str,
+    ) -> AppResult<()> {
+        let target = self.object_path(key)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let partial = partial_path(&target);
+        let mut out = fs::File::create(&partial).await?;
+        let copied = tokio::io::copy(reader, &mut out).await;
+        let flushed = match copied {
+            Ok(_) => out.flush().await,
+            Err(err) => Err(err),
+        };
+        drop(out);
+        if let Err(err) = flushed {
+            fs::remove_file(&partial).await.ok();
+            return Err(err.into());
+        }
+
+        fs::rename(&partial, &target).await?;
+        Ok(())
+    }
+
+    async fn object_sha256(&self, key: &str) -> AppResult<String> {
+        let mut file = fs::File::open(self.object_path(key)?).await?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buf[..read]);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        self.object_path(key)?;
+
+        let expires = Utc::now().timestamp() + expires_in.as_secs() as i64;
+        let signature = self.sign(&download_message(key, expires));
+
+        Ok(format!(
+            "{}/v1/storage/objects/{}?expires={}&signature={}",
+            self.public_base_url, key, expires, signature
+        ))
+    }
+
+    async fn copy_object(&self, from: &str, to: &str) -> AppResult<()> {
+        let target = self.object_path(to)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::copy(self.object_path(from)?, target).await?;
+        Ok(())
+    }
+
+    async fn move_object(&self, from: &str, to: &str) -> AppResult<()> {
+        let target = self.object_path(to)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(self.object_path(from)?, target).await?;
+        Ok(())
+    }
+
+    async fn delete_object(&self, key: &str) -> AppResult<()> {
+        match fs::remove_file(self.object_path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn part_file(part_number: i32) -> String {
+    format!("part-{:05}", part_number)
+}
+
+/// Where an object is assembled before being renamed into place.
+fn partial_path(target: &Path) -> PathBuf {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+fn part_message(upload_id: &str, part_number: i32, expires: i64) -> String {
+    format!("{}.{}.{}", upload_id, part_number, expires)
+}
+
+fn download_message(key: &str, expires: i64) -> String {
+    format!("get.{}.{}", key, expires)
+}
@@ -0,0 +1,79 @@
+pub mod local;
+pub mod s3;
+pub mod unconfigured;
+
+pub use local::LocalObjectStore;
+pub use s3::S3ObjectStore;
+pub use unconfigured::UnconfiguredObjectStore;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use shared::error::AppResult;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+
+/// Part of a multipart upload as reported by the client after uploading it.
+#[derive(Debug, Clone)]
+pub struct UploadedPart {
+    pub part_number: i32,
+    pub etag: String,
+}
+
+/// Object attributes read back from the backend.
+#[derive(Debug, Clone)]
+pub struct ObjectHead {
+    pub size: i64,
+}
+
+/// Multipart upload the backend still holds open.
+#[derive(Debug, Clone)]
+pub struct PendingMultipart {
+    pub key: String,
+    pub upload_id: String,
+    pub initiated_at: Option<DateTime<Utc>>,
+}
+
+/// Blob storage behind file uploads. Parts are uploaded by the client
+/// directly to the URLs handed out by [`ObjectStore::presign_part`].
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Starts a multipart upload to `key` and returns its upload id.
+    async fn create_multipart(&self, key: &str, content_type: &str) -> AppResult<String>;
+    async fn presign_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        expires_in: Duration,
+    ) -> AppResult<String>;
+    /// Assembles `parts`, which must be sorted by part number.
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> AppResult<()>;
+    async fn abort_multipart(&self, key: &str, upload_id: &str) -> AppResult<()>;
+    /// Multipart uploads still in progress for keys starting with `prefix`.
+    async fn list_multipart(&self, prefix: &str) -> AppResult<Vec<PendingMultipart>>;
+    async fn head_object(&self, key: &str) -> AppResult<ObjectHead>;
+    /// Up to the first `len` bytes of a non-empty object.
+    async fn read_prefix(&self, key: &str, len: usize) -> AppResult<Vec<u8>>;
+    async fn get_object(&self, key: &str) -> AppResult<Vec<u8>>;
+    async fn put_object(&self, key: &str, data: &[u8], content_type: &str) -> AppResult<()>;
+    /// Stores everything `reader` yields without holding it all in memory.
+    /// Nothing is left under `key` when reading fails.
+    async fn put_stream(
+        &self,
+        key: &str,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        content_type: &str,
+    ) -> AppResult<()>;
+    /// Hex SHA-256 of the stored object, read back in full.
+    async fn object_sha256(&self, key: &str) -> AppResult<String>;
+    /// URL the object can be downloaded from until `expires_in` has passed.
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> AppResult<String>;
+    async fn copy_object(&self, from: &str, to: &str) -> AppResult<()>;
+    async fn move_object(&self, from: &str, to: &str) -> AppResult<()>;
+    async fn delete_object(&self, key: &str) -> AppResult<()>;
+}
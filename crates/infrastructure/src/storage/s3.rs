@@ -0,0 +1,248 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use sha2::{Digest, Sha256};
+use shared::error::AppResult;
+use std::{collections::HashMap, env, time::Duration};
+use tokio::io::AsyncRead;
+
+use super::{ObjectHead, ObjectStore, PendingMultipart, UploadedPart};
+
+/// S3-compatible storage; configured for Cloudflare R2 through the `R2_*`
+/// variables.
+pub struct S3ObjectStore {
+    bucket: Box<Bucket>,
+}
+
+impl S3ObjectStore {
+    pub fn new(bucket: Box<Bucket>) -> Self {
+        Self { bucket }
+    }
+
+    pub fn from_env() -> AppResult<Self> {
+        let bucket_name = env::var("R2_BUCKET_NAME")?;
+        let endpoint = env::var("R2_ENDPOINT")?;
+        let access_key = env::var("R2_ACCESS_KEY_ID")?;
+        let secret_key = env::var("R2_ACCESS_KEY_SECRET")?;
+
+        let region = Region::Custom {
+            region: "auto".into(),
+            endpoint,
+        };
+        let credentials = Credentials::new(Some(&access_key), Some(&secret_key), None, None, None)?;
+
+        Ok(Self::new(
+            Bucket::new(&bucket_name, region, credentials)?.with_path_style(),
+        ))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn create_multipart(&self, key: &str, content_type: &str) -> AppResult<String> {
+        let initiated = self
+            .bucket
+            .initiate_multipart_upload(key, content_type)
+            .await?;
+        Ok(initiated.upload_id)
+    }
+
+    async fn presign_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        expires_in: Duration,
+    ) -> AppResult<String> {
+        let mut queries = HashMap::new();
+        queries.insert("partNumber".to_string(), part_number.to_string());
+        queries.insert("uploadId".to_string(), upload_id.to_string());
+
+        let url = self
+            .bucket
+            .presign_put(key, expires_in.as_secs() as u32, None, Some(queries))
+            .await?;
+        Ok(url)
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[UploadedPart],
+    ) -> AppResult<()> {
+        let parts = parts
+            .iter()
+            .map(|p| s3::serde_types::Part {
+                part_number: p.part_number.max(1) as u32,
+                etag: p.etag.clone(),
+            })
+            .collect();
+
+        let response = self
+            .bucket
+            .complete_multipart_upload(key, upload_id, parts)
+            .await?;
+        let code = response.status_code();
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "R2 multipart completion failed (status code {})",
+                code
+            ));
+        }
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, key: &str, upload_id: &str) -> AppResult<()> {
+        self.bucket.abort_upload(key, upload_id).await?;
+        Ok(())
+    }
+
+    async fn list_multipart(&self, prefix: &str) -> AppResult<Vec<PendingMultipart>> {
+        let pages = self
+            .bucket
+            .list_multiparts_uploads(Some(prefix), None)
+            .await?;
+
+        Ok(pages
+            .into_iter()
+            .flat_map(|page| page.uploads)
+            .map(|upload| PendingMultipart {
+                initiated_at: DateTime::parse_from_rfc3339(&upload.initiated)
+                    .ok()
+                    .map(|dt| dt.with_timezone(&Utc)),
+                key: upload.key,
+                upload_id: upload.id,
+            })
+            .collect())
+    }
+
+    async fn head_object(&self, key: &str) -> AppResult<ObjectHead> {
+        let (head, code) = self.bucket.head_object(key).await?;
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to read object from R2 (status code {})",
+                code
+            ));
+        }
+        let size = head
+            .content_length
+            .ok_or_else(|| anyhow::anyhow!("R2 did not report a size for '{}'", key))?;
+        Ok(ObjectHead { size })
+    }
+
+    async fn read_prefix(&self, key: &str, len: usize) -> AppResult<Vec<u8>> {
+        let response = self
+            .bucket
+            .get_object_range(key, 0, Some(len.max(1) as u64 - 1))
+            .await?;
+        let code = response.status_code();
+        if code != 200 && code != 206 {
+            return Err(anyhow::anyhow!(
+                "Failed to read object from R2 (status code {})",
+                code
+            ));
+        }
+        Ok(response.to_vec())
+    }
+
+    async fn get_object(&self, key: &str) -> AppResult<Vec<u8>> {
+        let response = self.bucket.get_object(key).await?;
+        let code = response.status_code();
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to read object from R2 (status code {})",
+                code
+            ));
+        }
+        Ok(response.to_vec())
+    }
+
+    async fn put_object(&self, key: &str, data: &[u8], content_type: &str) -> AppResult<()> {
+        let response = self
+            .bucket
+            .put_object_with_content_type(key, data, content_type)
+            .await?;
+        let code = response.status_code();
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to write object to R2 (status code {})",
+                code
+            ));
+        }
+        Ok(())
+    }
+
+    async fn put_stream(
+        &self,
+        key: &str,
+        mut reader: &mut (dyn AsyncRead + Send + Unpin),
+        content_type: &str,
+    ) -> AppResult<()> {
+        let response = self
+            .bucket
+            .put_object_stream_with_content_type(&mut reader, key, content_type)
+            .await?;
+        let code = response.status_code();
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to upload file to R2 (status code {})",
+                code
+            ));
+        }
+        Ok(())
+    }
+
+    async fn object_sha256(&self, key: &str) -> AppResult<String> {
+        let mut response = self.bucket.get_object_stream(key).await?;
+        if response.status_code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to read object from R2 (status code {})",
+                response.status_code
+            ));
+        }
+
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = response.bytes().next().await {
+            hasher.update(chunk?);
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        Ok(self
+            .bucket
+            .presign_get(key, expires_in.as_secs() as u32, None)
+            .await?)
+    }
+
+    async fn copy_object(&self, from: &str, to: &str) -> AppResult<()> {
+        let code = self.bucket.copy_object_internal(from, to).await?;
+        if code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to copy object in R2 (status code {})",
+                code
+            ));
+        }
+        Ok(())
+    }
+
+    async fn move_object(&self, from: &str, to: &str) -> AppResult<()> {
+        self.copy_object(from, to).await?;
+        self.delete_object(from).await
+    }
+
+    async fn delete_object(&self, key: &str) -> AppResult<()> {
+        let response = self.bucket.delete_object(key).await?;
+        let code = response.status_code();
+        if code != 204 && code != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to delete file from R2 (status code {})",
+                code
+            ));
+        }
+        Ok(())
+    }
+}
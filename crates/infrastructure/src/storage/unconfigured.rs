@@ -0,0 +1,107 @@
+use async_trait::async_trait;
+use shared::error::{ApiError, AppResult};
+use std::time::Duration;
+use tokio::io::AsyncRead;
+
+use super::{ObjectHead, ObjectStore, PendingMultipart, UploadedPart};
+
+/// Stands in for a backend that could not be set up, so that only the file
+/// endpoints fail. Every call reports why with a 503 `storage_unavailable`.
+pub struct UnconfiguredObjectStore {
+    reason: String,
+}
+
+impl UnconfiguredObjectStore {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+
+    fn unavailable<T>(&self) -> AppResult<T> {
+        Err(ApiError::unavailable(
+            "storage_unavailable",
+            format!("File storage is not configured: {}", self.reason),
+        )
+        .into())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for UnconfiguredObjectStore {
+    async fn create_multipart(&self, _key: &str, _content_type: &str) -> AppResult<String> {
+        self.unavailable()
+    }
+
+    async fn presign_part(
+        &self,
+        _key: &str,
+        _upload_id: &str,
+        _part_number: i32,
+        _expires_in: Duration,
+    ) -> AppResult<String> {
+        self.unavailable()
+    }
+
+    async fn complete_multipart(
+        &self,
+        _key: &str,
+        _upload_id: &str,
+        _parts: &[UploadedPart],
+    ) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn abort_multipart(&self, _key: &str, _upload_id: &str) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn list_multipart(&self, _prefix: &str) -> AppResult<Vec<PendingMultipart>> {
+        self.unavailable()
+    }
+
+    async fn head_object(&self, _key: &str) -> AppResult<ObjectHead> {
+        self.unavailable()
+    }
+
+    async fn read_prefix(&self, _key: &str, _len: usize) -> AppResult<Vec<u8>> {
+        self.unavailable()
+    }
+
+    async fn get_object(&self, _key: &str) -> AppResult<Vec<u8>> {
+        self.unavailable()
+    }
+
+    async fn put_object(&self, _key: &str, _data: &[u8], _content_type: &str) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn put_stream(
+        &self,
+        _key: &str,
+        _reader: &mut (dyn AsyncRead + Send + Unpin),
+        _content_type: &str,
+    ) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn object_sha256(&self, _key: &str) -> AppResult<String> {
+        self.unavailable()
+    }
+
+    async fn presign_get(&self, _key: &str, _expires_in: Duration) -> AppResult<String> {
+        self.unavailable()
+    }
+
+    async fn copy_object(&self, _from: &str, _to: &str) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn move_object(&self, _from: &str, _to: &str) -> AppResult<()> {
+        self.unavailable()
+    }
+
+    async fn delete_object(&self, _key: &str) -> AppResult<()> {
+        self.unavailable()
+    }
+}
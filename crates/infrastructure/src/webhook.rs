@@ -0,0 +1,272 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[derive(Debug, Clone)]
+pub struct WebhookOutcome {
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub attempts: i32,
+}
+
+impl WebhookOutcome {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Whether sending again may succeed: network errors, 5xx, 408 and 429
+    /// are worth retrying, any other client error is not.
+    pub fn is_retryable(&self) -> bool {
+        match self.status_code {
+            None => true,
+            Some(status) => status >= 500 || status == 408 || status == 429,
+        }
+    }
+}
+
+pub struct WebhookSender {
+    client: reqwest::Client,
+    max_attempts: u32,
+}
+
+impl WebhookSender {
+    pub fn new(timeout: Duration, max_attempts: u32) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            max_attempts: max_attempts.max(1),
+        }
+    }
+
+    /// POSTs `payload` as JSON. When a secret is given the body is signed with
+    /// HMAC-SHA256 over `"{timestamp}.{body}"` and sent as
+    /// `X-Natsume-Signature: sha256=<hex>` alongside `X-Natsume-Timestamp`.
+    /// Network errors and 5xx/408/429 responses are retried with a linear
+    /// backoff.
+    pub async fn send(
+        &self,
+        url: &str,
+        secret: Option<&str>,
+        event: &str,
+        payload: &Value,
+    ) -> WebhookOutcome {
+        let body = payload.to_string();
+        let mut outcome = WebhookOutcome {
+            status_code: None,
+            error: None,
+            attempts: 0,
+        };
+
+        for attempt in 1..=self.max_attempts {
+            outcome.attempts = attempt as i32;
+
+            let timestamp = Utc::now().timestamp().to_string();
+            let mut request = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .header("X-Natsume-Event", event)
+                .header("X-Natsume-Timestamp", &timestamp)
+                .body(body.clone());
+
+            if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+                request = request.header("X-Natsume-Signature", sign(secret, &timestamp, &body));
+            }
+
+            match request.send().await {
+                Ok(res) => {
+                    let status = res.status();
+                    outcome.status_code = Some(status.as_u16() as i32);
+
+                    if status.is_success() {
+                        outcome.error = None;
+                        return outcome;
+                    }
+
+                    outcome.error = Some(format!("webhook responded with {}", status));
+                    if !outcome.is_retryable() {
+                        return outcome;
+                    }
+                }
+                Err(err) => {
+                    outcome.status_code = None;
+                    outcome.error = Some(err.to_string());
+                }
+            }
+
+            if attempt < self.max_attempts {
+                sleep(Duration::from_secs(attempt as u64)).await;
+            }
+        }
+
+        outcome
+    }
+}
+
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: String,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    /// Accepts one request on a local port and answers it with `status`.
+    async fn stand_in(status: u16) -> (String, tokio::task::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            let head_end = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+                if let Some(pos) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+
+            let head = String::from_utf8_lossy(&raw[..head_end]).to_string();
+            let headers: Vec<(String, String)> = head
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+                .collect();
+            let length: usize = headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+                .map(|(_, v)| v.parse().unwrap())
+                .unwrap_or(0);
+            while raw.len() < head_end + length {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+            }
+
+            let response = format!(
+                "HTTP/1.1 {} Stand-in\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            Received {
+                headers,
+                body: String::from_utf8_lossy(&raw[head_end..head_end + length]).to_string(),
+            }
+        });
+
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn sends_signed_payload() {
+        let (url, received) = stand_in(204).await;
+        let sender = WebhookSender::new(Duration::from_secs(5), 1);
+        let payload = json!({ "username": "Natsume Status", "embeds": [] });
+
+        let outcome = sender.send(&url, Some("s3cret"), "firing", &payload).await;
+        let received = received.await.unwrap();
+
+        assert!(outcome.is_success());
+        assert_eq!(outcome.status_code, Some(204));
+        assert_eq!(outcome.attempts, 1);
+        assert_eq!(
+            serde_json::from_str::<Value>(&received.body).unwrap(),
+            payload
+        );
+        assert_eq!(received.header("X-Natsume-Event"), Some("firing"));
+
+        let timestamp = received.header("X-Natsume-Timestamp").unwrap();
+        let signature = received.header("X-Natsume-Signature").unwrap();
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature, sign("s3cret", timestamp, &received.body));
+    }
+
+    #[tokio::test]
+    async fn omits_signature_without_secret() {
+        let (url, received) = stand_in(200).await;
+        let sender = WebhookSender::new(Duration::from_secs(5), 1);
+
+        let outcome = sender.send(&url, None, "test", &json!({})).await;
+        let received = received.await.unwrap();
+
+        assert!(outcome.is_success());
+        assert!(received.header("X-Natsume-Signature").is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_client_errors_without_retrying() {
+        let (url, received) = stand_in(404).await;
+        let sender = WebhookSender::new(Duration::from_secs(5), 3);
+
+        let outcome = sender.send(&url, None, "test", &json!({})).await;
+        received.await.unwrap();
+
+        assert!(!outcome.is_success());
+        assert_eq!(outcome.status_code, Some(404));
+        assert_eq!(outcome.attempts, 1);
+    }
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        assert_eq!(
+            sign("key", "1700000000", "{}"),
+            "sha256=9d713ed406bb7076d4123f0dc2c39d2df5c654ed4b0cd56b52c8b4c940bd63ae"
+        );
+    }
+
+    #[test]
+    fn retries_only_transient_failures() {
+        let cases = [
+            (None, true),
+            (Some(500), true),
+            (Some(503), true),
+            (Some(408), true),
+            (Some(429), true),
+            (Some(400), false),
+            (Some(404), false),
+            (Some(410), false),
+        ];
+        for (status_code, retryable) in cases {
+            let outcome = WebhookOutcome {
+                status_code,
+                error: Some("failed".to_string()),
+                attempts: 1,
+            };
+            assert_eq!(outcome.is_retryable(), retryable, "{:?}", status_code);
+        }
+    }
+}
@@ -26,3 +26,79 @@ pub async fn not_found_handler() -> impl IntoResponse {
 
     (StatusCode::NOT_FOUND, Json(body)).into_response()
 }
+
+/// Failure the client can act on, raised from usecases through `AppResult`.
+/// Handlers recover it with [`error_response`] instead of reporting a 500.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    pub fn forbidden(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, message)
+    }
+
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, code, message)
+    }
+
+    pub fn unavailable(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::SERVICE_UNAVAILABLE, code, message)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiErrorResponse {
+            status: self.status.as_u16(),
+            code: self.code,
+            message: self.message,
+        };
+
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Renders an [`ApiError`] carried by `err` as-is, anything else with the
+/// given fallback status and code.
+pub fn error_response(err: anyhow::Error, status: StatusCode, code: &'static str) -> Response {
+    match err.downcast::<ApiError>() {
+        Ok(api_error) => api_error.into_response(),
+        Err(err) => {
+            let body = ApiErrorResponse {
+                status: status.as_u16(),
+                code,
+                message: err.to_string(),
+            };
+
+            (status, Json(body)).into_response()
+        }
+    }
+}
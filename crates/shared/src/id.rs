@@ -4,6 +4,100 @@ use uuid::Uuid;
 
 const EPOCH_MILLIS: u64 = 1735689600000; // 2025-01-01 UTC
 
+const SEQUENCE_BITS: u64 = 6;
+const ENTITY_BITS: u64 = 5;
+const FRAGMENT_BITS: u64 = 9;
+const TIMESTAMP_SHIFT: u64 = FRAGMENT_BITS + ENTITY_BITS + SEQUENCE_BITS;
+
+const MAX_SLUG_LEN: usize = 64;
+
+/// Resources whose ids come from [`IdGenerator`]. The discriminant is the
+/// 5-bit entity-type field of the id, so values must never be reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EntityType {
+    Item = 1,
+    Recipe = 2,
+    Ticket = 3,
+    File = 4,
+}
+
+impl EntityType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::Item),
+            2 => Some(Self::Recipe),
+            3 => Some(Self::Ticket),
+            4 => Some(Self::File),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Item => "item",
+            Self::Recipe => "recipe",
+            Self::Ticket => "ticket",
+            Self::File => "file",
+        }
+    }
+}
+
+impl std::fmt::Display for EntityType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Fields packed into a generated id.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodedId {
+    pub id: u64,
+    /// Unix time in milliseconds at which the id was generated.
+    pub timestamp_ms: u64,
+    /// Raw entity-type field; see [`Self::entity`].
+    pub entity_type: u8,
+    pub sequence: u8,
+}
+
+impl DecodedId {
+    pub fn entity(&self) -> Option<EntityType> {
+        EntityType::from_u8(self.entity_type)
+    }
+}
+
+/// Decodes an id produced by [`IdGenerator`]. Returns `None` for strings that
+/// are not a generated id, such as slugs or legacy ids.
+pub fn decode_id(id: &str) -> Option<DecodedId> {
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let id: u64 = id.parse().ok()?;
+    let elapsed_ms = id >> TIMESTAMP_SHIFT;
+    if elapsed_ms == 0 {
+        return None;
+    }
+
+    Some(DecodedId {
+        id,
+        timestamp_ms: elapsed_ms + EPOCH_MILLIS,
+        entity_type: ((id >> SEQUENCE_BITS) & ((1 << ENTITY_BITS) - 1)) as u8,
+        sequence: (id & ((1 << SEQUENCE_BITS) - 1)) as u8,
+    })
+}
+
+/// Human alias accepted in place of a generated id: lowercase ascii letters,
+/// digits, `-` and `_`, at most 64 characters and not purely numeric so it
+/// can never be mistaken for an id.
+pub fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= MAX_SLUG_LEN
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+        && !slug.bytes().all(|b| b.is_ascii_digit())
+}
+
 pub struct IdGenerator {
     last_timestamp: AtomicU64,
     sequence: AtomicU64,
@@ -17,6 +111,11 @@ impl IdGenerator {
         }
     }
 
+    /// Id for `entity` in the string form stored in the database.
+    pub fn generate_id(&self, entity: EntityType) -> String {
+        self.generate(entity as u8).to_string()
+    }
+
     pub fn generate(&self, entity_type: u8) -> u64 {
         let uuid = Uuid::new_v4();
         self.generate_from_uuid(uuid, entity_type)
@@ -27,7 +126,7 @@ impl IdGenerator {
 
         let last = self.last_timestamp.load(Ordering::Relaxed);
         let seq = if now_ms == last {
-            (self.sequence.fetch_add(1, Ordering::Relaxed) + 1) & 0b111111
+            (self.sequence.fetch_add(1, Ordering::Relaxed) + 1) & ((1 << SEQUENCE_BITS) - 1)
         } else {
             self.sequence.store(0, Ordering::Relaxed);
             self.last_timestamp.store(now_ms, Ordering::Relaxed);
@@ -44,9 +143,12 @@ impl IdGenerator {
             uuid_bytes[5],
             uuid_bytes[6],
             uuid_bytes[7],
-        ]) & 0x1FF;
+        ]) & ((1 << FRAGMENT_BITS) - 1);
 
-        (now_ms << (9 + 5 + 6)) | (uuid_fragment << (5 + 6)) | ((entity_type as u64) << 6) | seq
+        (now_ms << TIMESTAMP_SHIFT)
+            | (uuid_fragment << (ENTITY_BITS + SEQUENCE_BITS))
+            | ((entity_type as u64) << SEQUENCE_BITS)
+            | seq
     }
 }
 
@@ -62,3 +164,106 @@ fn current_unix_ms() -> u64 {
         .expect("Time went backwards")
         .as_millis() as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids_round_trip() {
+        let ids = IdGenerator::new();
+        let before = current_unix_ms();
+        let id = ids.generate_id(EntityType::Ticket);
+        let after = current_unix_ms();
+
+        let decoded = decode_id(&id).expect("generated ids decode");
+        assert_eq!(decoded.id.to_string(), id);
+        assert_eq!(decoded.entity(), Some(EntityType::Ticket));
+        assert!((before..=after).contains(&decoded.timestamp_ms));
+    }
+
+    #[test]
+    fn decodes_each_field_of_the_layout() {
+        let elapsed_ms = 123_456_789u64;
+        let id = (elapsed_ms << TIMESTAMP_SHIFT)
+            | (0x1FF << (ENTITY_BITS + SEQUENCE_BITS))
+            | ((EntityType::File as u64) << SEQUENCE_BITS)
+            | 0b10_1010;
+
+        let decoded = decode_id(&id.to_string()).unwrap();
+        assert_eq!(decoded.timestamp_ms, EPOCH_MILLIS + elapsed_ms);
+        assert_eq!(decoded.entity_type, EntityType::File as u8);
+        assert_eq!(decoded.entity(), Some(EntityType::File));
+        assert_eq!(decoded.sequence, 0b10_1010);
+    }
+
+    #[test]
+    fn fragment_comes_from_the_uuid_and_leaves_other_fields_alone() {
+        let ids = IdGenerator::new();
+        let id = ids.generate_from_uuid(Uuid::from_u128(u128::MAX), EntityType::Item as u8);
+
+        let fragment = (id >> (ENTITY_BITS + SEQUENCE_BITS)) & ((1 << FRAGMENT_BITS) - 1);
+        assert_eq!(fragment, (1 << FRAGMENT_BITS) - 1);
+        assert_eq!(
+            decode_id(&id.to_string()).unwrap().entity(),
+            Some(EntityType::Item)
+        );
+    }
+
+    #[test]
+    fn entity_types_survive_the_five_bit_field() {
+        for entity in [
+            EntityType::Item,
+            EntityType::Recipe,
+            EntityType::Ticket,
+            EntityType::File,
+        ] {
+            assert!((entity as u64) < (1 << ENTITY_BITS));
+            assert_eq!(EntityType::from_u8(entity as u8), Some(entity));
+        }
+        assert_eq!(EntityType::from_u8(0), None);
+        assert_eq!(EntityType::from_u8(31), None);
+    }
+
+    #[test]
+    fn rejects_strings_that_are_not_generated_ids() {
+        for raw in [
+            "",
+            "iron-sword",
+            "12a",
+            "-1",
+            "+5",
+            " 1",
+            "0",
+            "1048575",
+            "99999999999999999999999",
+        ] {
+            assert!(decode_id(raw).is_none(), "{:?} should not decode", raw);
+        }
+    }
+
+    #[test]
+    fn accepts_slugs() {
+        for slug in ["iron-sword", "a", "a_1", "1st-place", "0x", &"a".repeat(64)] {
+            assert!(is_valid_slug(slug), "{:?} should be a slug", slug);
+        }
+    }
+
+    #[test]
+    fn rejects_numeric_and_malformed_slugs() {
+        for slug in [
+            "",
+            "0",
+            "123",
+            "59426388671682816",
+            "Iron",
+            "iron sword",
+            "iron.sword",
+            "natsume:iron",
+            "épée",
+            &"a".repeat(65),
+        ] {
+            assert!(!is_valid_slug(slug), "{:?} should be rejected", slug);
+        }
+    }
+}
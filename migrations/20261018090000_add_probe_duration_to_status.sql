@@ -0,0 +1,3 @@
+ALTER TABLE status ADD COLUMN IF NOT EXISTS probe_duration_ms INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_status_server_id_timestamp ON status (server_id, timestamp DESC);
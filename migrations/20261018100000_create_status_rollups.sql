@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS status_rollups (
+    server_id      TEXT             NOT NULL,
+    resolution     TEXT             NOT NULL CHECK (resolution IN ('hour', 'day')),
+    bucket_start   BIGINT           NOT NULL,
+    samples        INTEGER          NOT NULL,
+    online_samples INTEGER          NOT NULL,
+    uptime_pct     DOUBLE PRECISION NOT NULL,
+    players_min    INTEGER,
+    players_avg    DOUBLE PRECISION,
+    players_max    INTEGER,
+    latency_min    INTEGER,
+    latency_avg    DOUBLE PRECISION,
+    latency_max    INTEGER,
+    PRIMARY KEY (server_id, resolution, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_rollups_resolution_bucket ON status_rollups (resolution, bucket_start);
+CREATE INDEX IF NOT EXISTS idx_status_timestamp ON status (timestamp);
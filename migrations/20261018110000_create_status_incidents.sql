@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS status_incidents (
+    id               BIGSERIAL   PRIMARY KEY,
+    server_id        TEXT        NOT NULL,
+    started_at       BIGINT      NOT NULL,
+    ended_at         BIGINT,
+    failed_probes    INTEGER     NOT NULL DEFAULT 0,
+    notes            TEXT,
+    notes_updated_by TEXT,
+    notes_updated_at TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_status_incidents_open ON status_incidents (server_id) WHERE ended_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_status_incidents_server_started ON status_incidents (server_id, started_at DESC);
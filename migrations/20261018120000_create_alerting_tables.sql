@@ -0,0 +1,36 @@
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id              BIGSERIAL   PRIMARY KEY,
+    name            TEXT        NOT NULL,
+    server_id       TEXT,
+    kind            TEXT        NOT NULL CHECK (kind IN ('offline', 'players_above', 'latency_above')),
+    threshold       INTEGER,
+    duration_secs   INTEGER     NOT NULL DEFAULT 300,
+    webhook_url     TEXT        NOT NULL,
+    secret          TEXT,
+    enabled         BOOLEAN     NOT NULL DEFAULT TRUE,
+    notify_recovery BOOLEAN     NOT NULL DEFAULT TRUE,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS alert_states (
+    rule_id    BIGINT  NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    server_id  TEXT    NOT NULL,
+    firing     BOOLEAN NOT NULL DEFAULT FALSE,
+    changed_at BIGINT  NOT NULL,
+    PRIMARY KEY (rule_id, server_id)
+);
+
+CREATE TABLE IF NOT EXISTS alert_deliveries (
+    id          BIGSERIAL   PRIMARY KEY,
+    rule_id     BIGINT      NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    server_id   TEXT        NOT NULL,
+    event       TEXT        NOT NULL CHECK (event IN ('firing', 'resolved', 'test')),
+    payload     JSONB       NOT NULL,
+    status_code INTEGER,
+    error       TEXT,
+    attempts    INTEGER     NOT NULL DEFAULT 1,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_deliveries_rule_created ON alert_deliveries (rule_id, created_at DESC);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS network_daily_peaks (
+    day     BIGINT  PRIMARY KEY,
+    players INTEGER NOT NULL,
+    at      BIGINT  NOT NULL
+);
@@ -0,0 +1,59 @@
+-- `tickets` used to be created by hand with messages stored as a JSONB array.
+-- Create it for fresh databases and move existing messages into their own table.
+CREATE TABLE IF NOT EXISTS tickets (
+    id         TEXT      PRIMARY KEY,
+    user_id    TEXT      NOT NULL,
+    title      TEXT      NOT NULL,
+    status     TEXT      NOT NULL DEFAULT 'open',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tickets_user_id ON tickets (user_id);
+
+CREATE TABLE IF NOT EXISTS ticket_messages (
+    id         BIGSERIAL PRIMARY KEY,
+    ticket_id  TEXT      NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+    sender     TEXT      NOT NULL,
+    content    TEXT      NOT NULL,
+    sent_at    TIMESTAMP NOT NULL DEFAULT NOW(),
+    edited_at  TIMESTAMP,
+    deleted_at TIMESTAMP,
+    deleted_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_messages_ticket_sent ON ticket_messages (ticket_id, sent_at);
+
+CREATE TABLE IF NOT EXISTS ticket_message_revisions (
+    id         BIGSERIAL PRIMARY KEY,
+    message_id BIGINT    NOT NULL REFERENCES ticket_messages(id) ON DELETE CASCADE,
+    action     TEXT      NOT NULL CHECK (action IN ('edit', 'delete')),
+    content    TEXT      NOT NULL,
+    edited_by  TEXT,
+    edited_at  TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_message_revisions_message_id ON ticket_message_revisions (message_id);
+
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1
+        FROM information_schema.columns
+        WHERE table_schema = 'public'
+          AND table_name = 'tickets'
+          AND column_name = 'messages'
+    ) THEN
+        INSERT INTO ticket_messages (ticket_id, sender, content, sent_at)
+        SELECT
+            t.id,
+            COALESCE(m.value->>'sender', 'unknown'),
+            COALESCE(m.value->>'content', ''),
+            COALESCE((m.value->>'sent_at')::timestamp, t.created_at)
+        FROM tickets t
+        CROSS JOIN LATERAL jsonb_array_elements(COALESCE(t.messages, '[]'::jsonb)) WITH ORDINALITY AS m(value, idx)
+        ORDER BY t.id, m.idx;
+
+        ALTER TABLE tickets DROP COLUMN messages;
+    END IF;
+END $$;
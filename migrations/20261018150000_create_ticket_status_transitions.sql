@@ -0,0 +1,38 @@
+-- Statuses used to be free-form; fold anything outside the lifecycle back to `open`.
+UPDATE tickets
+SET status = CASE LOWER(status)
+    WHEN 'open'            THEN 'open'
+    WHEN 'awaiting_staff'  THEN 'awaiting_staff'
+    WHEN 'awaiting_player' THEN 'awaiting_player'
+    WHEN 'resolved'        THEN 'resolved'
+    WHEN 'closed'          THEN 'closed'
+    WHEN 'reopened'        THEN 'reopened'
+    ELSE 'open'
+END;
+
+ALTER TABLE tickets DROP CONSTRAINT IF EXISTS tickets_status_check;
+ALTER TABLE tickets
+    ADD CONSTRAINT tickets_status_check CHECK (
+        status IN ('open', 'awaiting_staff', 'awaiting_player', 'resolved', 'closed', 'reopened')
+    );
+
+-- When the ticket entered its current status; the inactivity auto-close
+-- counts from here or from the player's last message, whichever is later.
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMP;
+UPDATE tickets SET status_changed_at = updated_at WHERE status_changed_at IS NULL;
+ALTER TABLE tickets ALTER COLUMN status_changed_at SET NOT NULL;
+ALTER TABLE tickets ALTER COLUMN status_changed_at SET DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS idx_tickets_status_changed ON tickets (status, status_changed_at);
+
+CREATE TABLE IF NOT EXISTS ticket_status_transitions (
+    id              BIGSERIAL PRIMARY KEY,
+    ticket_id       TEXT      NOT NULL REFERENCES tickets(id) ON DELETE CASCADE,
+    from_status     TEXT,
+    to_status       TEXT      NOT NULL,
+    actor           TEXT      NOT NULL,
+    reason          TEXT,
+    transitioned_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_status_transitions_ticket ON ticket_status_transitions (ticket_id, transitioned_at);
@@ -0,0 +1,20 @@
+ALTER TABLE tickets
+    ADD COLUMN IF NOT EXISTS assignee_id TEXT,
+    ADD COLUMN IF NOT EXISTS priority    TEXT   NOT NULL DEFAULT 'normal'
+        CHECK (priority IN ('low', 'normal', 'high', 'urgent')),
+    ADD COLUMN IF NOT EXISTS category    TEXT
+        CHECK (category IN ('bug', 'appeal', 'purchase', 'report')),
+    ADD COLUMN IF NOT EXISTS labels      TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_tickets_assignee ON tickets (assignee_id, status);
+CREATE INDEX IF NOT EXISTS idx_tickets_category ON tickets (category);
+CREATE INDEX IF NOT EXISTS idx_tickets_labels   ON tickets USING GIN (labels);
+
+-- Staff roster per category for round-robin assignment of new tickets.
+CREATE TABLE IF NOT EXISTS ticket_staff (
+    discord_id       TEXT      NOT NULL,
+    category         TEXT      NOT NULL CHECK (category IN ('bug', 'appeal', 'purchase', 'report')),
+    active           BOOLEAN   NOT NULL DEFAULT TRUE,
+    last_assigned_at TIMESTAMP,
+    PRIMARY KEY (discord_id, category)
+);
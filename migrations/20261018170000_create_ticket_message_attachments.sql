@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS ticket_message_attachments (
+    message_id BIGINT NOT NULL REFERENCES ticket_messages(id) ON DELETE CASCADE,
+    file_id    TEXT   NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, file_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ticket_message_attachments_file_id ON ticket_message_attachments (file_id);
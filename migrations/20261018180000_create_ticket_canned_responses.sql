@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS ticket_canned_responses (
+    id         BIGSERIAL PRIMARY KEY,
+    name       TEXT      NOT NULL UNIQUE,
+    category   TEXT      CHECK (category IN ('bug', 'appeal', 'purchase', 'report')),
+    content    TEXT      NOT NULL,
+    created_by TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE ticket_messages
+    ADD COLUMN IF NOT EXISTS internal           BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS canned_response_id BIGINT  REFERENCES ticket_canned_responses(id) ON DELETE SET NULL;
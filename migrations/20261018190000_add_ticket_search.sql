@@ -0,0 +1,12 @@
+-- 'simple' keeps tokens unstemmed so English and Japanese text both match as typed.
+ALTER TABLE tickets
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+        GENERATED ALWAYS AS (to_tsvector('simple', title)) STORED;
+
+ALTER TABLE ticket_messages
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+        GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_tickets_search_vector ON tickets USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_ticket_messages_search_vector ON ticket_messages USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_tickets_created_at ON tickets (created_at DESC);
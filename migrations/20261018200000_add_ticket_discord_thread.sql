@@ -0,0 +1,4 @@
+-- Discord thread the ticket is mirrored into; replies posted there come back through the bot endpoint.
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS discord_thread_id TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tickets_discord_thread_id ON tickets (discord_thread_id) WHERE discord_thread_id IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Ids are now assigned by the server; client-chosen ids survive as a slug
+-- alias. Existing ids stay valid as ids and are mirrored to the slug so the
+-- same value keeps resolving once new rows use generated ids.
+ALTER TABLE items   ADD COLUMN IF NOT EXISTS slug TEXT;
+ALTER TABLE recipes ADD COLUMN IF NOT EXISTS slug TEXT;
+ALTER TABLE tickets ADD COLUMN IF NOT EXISTS slug TEXT;
+
+UPDATE items   SET slug = id WHERE slug IS NULL AND id !~ '^[0-9]+$';
+UPDATE recipes SET slug = id WHERE slug IS NULL AND id !~ '^[0-9]+$';
+UPDATE tickets SET slug = id WHERE slug IS NULL AND id !~ '^[0-9]+$';
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_items_slug   ON items (slug)   WHERE slug IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_recipes_slug ON recipes (slug) WHERE slug IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tickets_slug ON tickets (slug) WHERE slug IS NOT NULL;
@@ -0,0 +1,18 @@
+-- Namespaced `namespace:path` key per item. Existing items whose slug is a
+-- valid resource-location path get `natsume:<slug>`.
+ALTER TABLE items ADD COLUMN IF NOT EXISTS key TEXT;
+
+UPDATE items
+SET key = 'natsume:' || slug
+WHERE key IS NULL AND slug ~ '^[a-z0-9_./-]+$';
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_items_key ON items (key) WHERE key IS NOT NULL;
+
+-- Keys an item was known by before being renamed, so old keys keep resolving.
+CREATE TABLE IF NOT EXISTS item_key_aliases (
+    key        TEXT      PRIMARY KEY,
+    item_id    TEXT      NOT NULL REFERENCES items(id) ON DELETE CASCADE,
+    renamed_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_key_aliases_item_id ON item_key_aliases (item_id);
@@ -0,0 +1,13 @@
+CREATE INDEX IF NOT EXISTS idx_file_uploads_status_updated_at ON file_uploads (status, updated_at);
+
+-- One row per pass of the stale upload reaper, listing what it aborted.
+CREATE TABLE IF NOT EXISTS file_upload_reaper_runs (
+    id       BIGSERIAL PRIMARY KEY,
+    ran_at   TIMESTAMP NOT NULL DEFAULT NOW(),
+    ttl_secs BIGINT    NOT NULL,
+    expired  JSONB     NOT NULL DEFAULT '[]',
+    orphaned JSONB     NOT NULL DEFAULT '[]',
+    failures JSONB     NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_file_upload_reaper_runs_ran_at ON file_upload_reaper_runs (ran_at DESC);
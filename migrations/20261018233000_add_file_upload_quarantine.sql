@@ -0,0 +1,7 @@
+-- Completed uploads whose object fails verification are kept for inspection
+-- under a quarantine key instead of being published.
+ALTER TABLE file_uploads DROP CONSTRAINT IF EXISTS file_uploads_status_check;
+ALTER TABLE file_uploads ADD CONSTRAINT file_uploads_status_check
+    CHECK (status IN ('in_progress', 'completed', 'aborted', 'quarantined'));
+
+ALTER TABLE file_uploads ADD COLUMN IF NOT EXISTS quarantine_reason TEXT;
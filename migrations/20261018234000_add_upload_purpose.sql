@@ -0,0 +1,6 @@
+-- Purpose an upload was made for, deciding the policy it is checked against.
+ALTER TABLE file_uploads ADD COLUMN IF NOT EXISTS purpose TEXT NOT NULL DEFAULT 'general';
+ALTER TABLE files ADD COLUMN IF NOT EXISTS purpose TEXT NOT NULL DEFAULT 'general';
+
+UPDATE files SET purpose = 'ticket_attachment'
+WHERE id IN (SELECT file_id FROM ticket_message_attachments);
@@ -0,0 +1,6 @@
+-- Dimensions of image uploads, and whether a thumbnail and preview were
+-- generated for them under derived/<file id>/.
+ALTER TABLE files ADD COLUMN IF NOT EXISTS width INTEGER;
+ALTER TABLE files ADD COLUMN IF NOT EXISTS height INTEGER;
+ALTER TABLE files ADD COLUMN IF NOT EXISTS frames INTEGER;
+ALTER TABLE files ADD COLUMN IF NOT EXISTS has_previews BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,17 @@
+-- Stored objects shared by every file with the same content. The object is
+-- deleted once no file references it any more.
+CREATE TABLE IF NOT EXISTS file_blobs (
+    sha256     TEXT PRIMARY KEY,
+    key        TEXT      NOT NULL,
+    size       BIGINT    NOT NULL,
+    ref_count  INTEGER   NOT NULL CHECK (ref_count >= 0),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE files ADD COLUMN IF NOT EXISTS key TEXT;
+UPDATE files SET key = 'files/' || user_id || '/' || id || '/' || filename WHERE key IS NULL;
+ALTER TABLE files ALTER COLUMN key SET NOT NULL;
+
+-- Files uploaded before hashing have no blob and own their object.
+ALTER TABLE files ADD COLUMN IF NOT EXISTS sha256 TEXT REFERENCES file_blobs(sha256);
+CREATE INDEX IF NOT EXISTS idx_files_sha256 ON files (sha256);
@@ -0,0 +1,16 @@
+-- Private files are stored under private/, which the CDN does not serve, and
+-- are downloaded through expiring signed URLs.
+ALTER TABLE file_uploads ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'public'
+    CHECK (visibility IN ('public', 'private'));
+ALTER TABLE files ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'public'
+    CHECK (visibility IN ('public', 'private'));
+
+-- Identical content is only shared between files of the same visibility, as
+-- each visibility keeps its objects under its own prefix.
+ALTER TABLE files DROP CONSTRAINT IF EXISTS files_sha256_fkey;
+ALTER TABLE files DROP CONSTRAINT IF EXISTS files_blob_fkey;
+ALTER TABLE file_blobs ADD COLUMN IF NOT EXISTS visibility TEXT NOT NULL DEFAULT 'public';
+ALTER TABLE file_blobs DROP CONSTRAINT IF EXISTS file_blobs_pkey;
+ALTER TABLE file_blobs ADD PRIMARY KEY (sha256, visibility);
+ALTER TABLE files ADD CONSTRAINT files_blob_fkey
+    FOREIGN KEY (sha256, visibility) REFERENCES file_blobs (sha256, visibility);
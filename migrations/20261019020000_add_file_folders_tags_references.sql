@@ -0,0 +1,19 @@
+ALTER TABLE files
+    ADD COLUMN IF NOT EXISTS folder TEXT   NOT NULL DEFAULT '',
+    ADD COLUMN IF NOT EXISTS tags   TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_files_folder      ON files (folder text_pattern_ops);
+CREATE INDEX IF NOT EXISTS idx_files_tags        ON files USING GIN (tags);
+CREATE INDEX IF NOT EXISTS idx_files_uploaded_at ON files (uploaded_at DESC);
+
+-- Items and resource packs using a file. Ticket usage is read from
+-- ticket_message_attachments instead.
+CREATE TABLE IF NOT EXISTS file_references (
+    file_id     TEXT      NOT NULL REFERENCES files(id) ON DELETE CASCADE,
+    entity_type TEXT      NOT NULL CHECK (entity_type IN ('item', 'resource_pack')),
+    entity_id   TEXT      NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (file_id, entity_type, entity_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_file_references_entity ON file_references (entity_type, entity_id);
@@ -0,0 +1,2 @@
+-- Storage usage is summed per user to enforce quotas.
+CREATE INDEX IF NOT EXISTS idx_files_user_id ON files (user_id);
@@ -0,0 +1,14 @@
+-- Files whose object still has to move to the prefix of `visibility`. Rows
+-- are removed by the API once the move is done.
+CREATE TABLE IF NOT EXISTS file_visibility_backfill (
+    file_id    TEXT PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    visibility TEXT      NOT NULL CHECK (visibility IN ('public', 'private')),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Ticket attachments uploaded before private files existed are public, but
+-- new ones default to private.
+INSERT INTO file_visibility_backfill (file_id, visibility)
+SELECT id, 'private' FROM files
+WHERE purpose = 'ticket_attachment' AND visibility = 'public'
+ON CONFLICT (file_id) DO NOTHING;
@@ -0,0 +1,5 @@
+-- A state change whose notification failed is retried with a backoff until it
+-- is delivered or given up, after which the state moves on regardless.
+ALTER TABLE alert_states ADD COLUMN IF NOT EXISTS delivery_failures INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE alert_states ADD COLUMN IF NOT EXISTS delivery_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE alert_states ADD COLUMN IF NOT EXISTS retry_at BIGINT;